@@ -0,0 +1,374 @@
+use crate::sshconfig::{glob_match, Key};
+
+const DEFAULT_CIPHERS: &[&str] = &[
+    "chacha20-poly1305@openssh.com",
+    "aes128-ctr",
+    "aes192-ctr",
+    "aes256-ctr",
+    "aes128-gcm@openssh.com",
+    "aes256-gcm@openssh.com",
+];
+
+const OTHER_CIPHERS: &[&str] = &["3des-cbc", "aes128-cbc", "aes192-cbc", "aes256-cbc"];
+
+const DEFAULT_MACS: &[&str] = &[
+    "umac-64-etm@openssh.com",
+    "umac-128-etm@openssh.com",
+    "hmac-sha2-256-etm@openssh.com",
+    "hmac-sha2-512-etm@openssh.com",
+    "hmac-sha1-etm@openssh.com",
+    "umac-64@openssh.com",
+    "umac-128@openssh.com",
+    "hmac-sha2-256",
+    "hmac-sha2-512",
+    "hmac-sha1",
+];
+
+const OTHER_MACS: &[&str] = &[
+    "hmac-sha1-96",
+    "hmac-md5",
+    "hmac-md5-96",
+    "hmac-sha1-96-etm@openssh.com",
+    "hmac-md5-etm@openssh.com",
+    "hmac-md5-96-etm@openssh.com",
+];
+
+const DEFAULT_KEX: &[&str] = &[
+    "mlkem768x25519-sha256",
+    "sntrup761x25519-sha512",
+    "sntrup761x25519-sha512@openssh.com",
+    "curve25519-sha256",
+    "curve25519-sha256@libssh.org",
+    "ecdh-sha2-nistp256",
+    "ecdh-sha2-nistp384",
+    "ecdh-sha2-nistp521",
+    "diffie-hellman-group-exchange-sha256",
+    "diffie-hellman-group16-sha512",
+    "diffie-hellman-group18-sha512",
+    "diffie-hellman-group14-sha256",
+];
+
+const OTHER_KEX: &[&str] = &[
+    "diffie-hellman-group1-sha1",
+    "diffie-hellman-group14-sha1",
+    "diffie-hellman-group-exchange-sha1",
+];
+
+const DEFAULT_KEY_TYPES: &[&str] = &[
+    "ssh-ed25519-cert-v01@openssh.com",
+    "ecdsa-sha2-nistp256-cert-v01@openssh.com",
+    "ecdsa-sha2-nistp384-cert-v01@openssh.com",
+    "ecdsa-sha2-nistp521-cert-v01@openssh.com",
+    "sk-ssh-ed25519-cert-v01@openssh.com",
+    "sk-ecdsa-sha2-nistp256-cert-v01@openssh.com",
+    "rsa-sha2-512-cert-v01@openssh.com",
+    "rsa-sha2-256-cert-v01@openssh.com",
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+    "rsa-sha2-512",
+    "rsa-sha2-256",
+];
+
+const OTHER_KEY_TYPES: &[&str] = &[
+    "ssh-rsa",
+    "ssh-rsa-cert-v01@openssh.com",
+    "ssh-dss",
+    "ssh-dss-cert-v01@openssh.com",
+];
+
+const DEFAULT_CA_SIGNATURES: &[&str] = &[
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+    "rsa-sha2-512",
+    "rsa-sha2-256",
+];
+
+const OTHER_CA_SIGNATURES: &[&str] = &["ssh-rsa", "ssh-dss"];
+
+/// Broken or deprecated algorithms, including some that recent OpenSSH
+/// releases no longer know about.
+const WEAK: &[&str] = &[
+    "ssh-rsa",
+    "ssh-rsa-cert-v01@openssh.com",
+    "ssh-dss",
+    "ssh-dss-cert-v01@openssh.com",
+    "3des-cbc",
+    "aes128-cbc",
+    "aes192-cbc",
+    "aes256-cbc",
+    "blowfish-cbc",
+    "cast128-cbc",
+    "arcfour",
+    "arcfour128",
+    "arcfour256",
+    "rijndael-cbc@lysator.liu.se",
+    "hmac-md5",
+    "hmac-md5-96",
+    "hmac-md5-etm@openssh.com",
+    "hmac-md5-96-etm@openssh.com",
+    "hmac-sha1-96",
+    "hmac-sha1-96-etm@openssh.com",
+    "hmac-ripemd160",
+    "diffie-hellman-group1-sha1",
+    "diffie-hellman-group14-sha1",
+    "diffie-hellman-group-exchange-sha1",
+];
+
+/// The OpenSSH default list of an algorithm list key, and the other
+/// algorithms it supports. `None` for keys that are not algorithm lists.
+/// Deprecated names, such as `PubkeyAcceptedKeyTypes`, take the list of the
+/// key they were renamed to.
+pub fn known(key: Key) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    let key = key.deprecation().and_then(|d| d.replacement).unwrap_or(key);
+    match key {
+        Key::Ciphers => Some((DEFAULT_CIPHERS, OTHER_CIPHERS)),
+        Key::MACs => Some((DEFAULT_MACS, OTHER_MACS)),
+        Key::KexAlgorithms => Some((DEFAULT_KEX, OTHER_KEX)),
+        Key::HostKeyAlgorithms
+        | Key::PubkeyAcceptedAlgorithms
+        | Key::HostbasedAcceptedAlgorithms => Some((DEFAULT_KEY_TYPES, OTHER_KEY_TYPES)),
+        Key::CASignatureAlgorithms => Some((DEFAULT_CA_SIGNATURES, OTHER_CA_SIGNATURES)),
+        _ => None,
+    }
+}
+
+pub fn is_weak(name: &str) -> bool {
+    WEAK.contains(&name)
+}
+
+/// Whether `name` is an algorithm OpenSSH supports for `key`.
+pub fn is_known(key: Key, name: &str) -> bool {
+    known(key).is_some_and(|(defaults, others)| defaults.contains(&name) || others.contains(&name))
+}
+
+/// The algorithms ssh ends up using for `value`, in order of preference.
+///
+/// A value starting with `+` appends to the defaults, `-` removes from them
+/// and `^` puts its algorithms first. Patterns are matched against the
+/// supported algorithms, names that match nothing are kept as they are.
+pub fn effective(key: Key, value: Option<&str>) -> Vec<String> {
+    let Some((default_names, others)) = known(key) else {
+        return vec![];
+    };
+    let defaults: Vec<String> = default_names.iter().map(|x| x.to_string()).collect();
+    let Some(value) = value.map(str::trim) else {
+        return defaults;
+    };
+
+    let all: Vec<&str> = default_names.iter().chain(others).copied().collect();
+    let expand = |list: &str| -> Vec<String> {
+        let mut result: Vec<String> = vec![];
+        for pattern in list.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let mut matched: Vec<String> = all
+                .iter()
+                .filter(|name| glob_match(pattern, name))
+                .map(|name| name.to_string())
+                .collect();
+            if matched.is_empty() && !pattern.contains(['*', '?']) {
+                matched.push(pattern.to_owned());
+            }
+            for name in matched {
+                if !result.contains(&name) {
+                    result.push(name);
+                }
+            }
+        }
+        result
+    };
+
+    if let Some(list) = value.strip_prefix('+') {
+        let mut result = defaults;
+        for name in expand(list) {
+            if !result.contains(&name) {
+                result.push(name);
+            }
+        }
+        result
+    } else if let Some(list) = value.strip_prefix('-') {
+        let patterns: Vec<&str> = list.split(',').map(str::trim).collect();
+        defaults
+            .into_iter()
+            .filter(|name| !patterns.iter().any(|p| glob_match(p, name)))
+            .collect()
+    } else if let Some(list) = value.strip_prefix('^') {
+        let mut result = expand(list);
+        let rest: Vec<String> = defaults
+            .into_iter()
+            .filter(|name| !result.contains(name))
+            .collect();
+        result.extend(rest);
+        result
+    } else {
+        expand(value)
+    }
+}
+
+/// The shortest value giving `enabled`, using the `+`, `-` and `^` forms
+/// when they apply. `None` when `enabled` is the default list.
+pub fn compact(key: Key, enabled: &[String]) -> Option<String> {
+    let (defaults, _) = known(key)?;
+    let defaults: Vec<String> = defaults.iter().map(|x| x.to_string()).collect();
+    if enabled == defaults {
+        return None;
+    }
+
+    let extra: Vec<String> = enabled
+        .iter()
+        .filter(|x| !defaults.contains(x))
+        .cloned()
+        .collect();
+    let kept: Vec<String> = enabled
+        .iter()
+        .filter(|x| defaults.contains(x))
+        .cloned()
+        .collect();
+
+    let candidates = [
+        (kept == defaults && enabled.ends_with(&extra)).then(|| format!("+{}", extra.join(","))),
+        (kept == defaults && enabled.starts_with(&extra)).then(|| format!("^{}", extra.join(","))),
+        (extra.is_empty() && is_subsequence(&kept, &defaults)).then(|| {
+            let removed: Vec<&str> = defaults
+                .iter()
+                .filter(|x| !kept.contains(x))
+                .map(|x| x.as_str())
+                .collect();
+            format!("-{}", removed.join(","))
+        }),
+    ];
+
+    let full = enabled.join(",");
+    candidates
+        .into_iter()
+        .flatten()
+        .chain([full])
+        .min_by_key(|x| x.len())
+}
+
+fn is_subsequence(items: &[String], list: &[String]) -> bool {
+    let mut rest = list.iter();
+    items.iter().all(|x| rest.any(|y| y == x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn with(first: &[&str], rest: &[&str]) -> Vec<String> {
+        names(first).into_iter().chain(names(rest)).collect()
+    }
+
+    #[test]
+    fn uses_the_defaults_without_a_value() {
+        assert_eq!(effective(Key::Ciphers, None), names(DEFAULT_CIPHERS));
+        assert_eq!(effective(Key::MACs, None), names(DEFAULT_MACS));
+        assert!(effective(Key::User, Some("aes128-ctr")).is_empty());
+        assert_eq!(compact(Key::Ciphers, &names(DEFAULT_CIPHERS)), None);
+        assert_eq!(compact(Key::User, &names(&["x"])), None);
+    }
+
+    #[test]
+    fn appends_with_plus() {
+        assert_eq!(
+            effective(Key::Ciphers, Some("+3des-cbc,aes128-ctr")),
+            with(DEFAULT_CIPHERS, &["3des-cbc"])
+        );
+        assert_eq!(
+            effective(Key::KexAlgorithms, Some("+diffie-hellman-group1-sha1")),
+            with(DEFAULT_KEX, &["diffie-hellman-group1-sha1"])
+        );
+    }
+
+    #[test]
+    fn removes_with_minus() {
+        let expected: Vec<String> = names(DEFAULT_CIPHERS)
+            .into_iter()
+            .filter(|x| !x.ends_with("-ctr"))
+            .collect();
+        assert_eq!(effective(Key::Ciphers, Some("-aes*-ctr")), expected);
+        assert_eq!(
+            effective(Key::Ciphers, Some("-chacha20-poly1305@openssh.com")),
+            names(&DEFAULT_CIPHERS[1..])
+        );
+    }
+
+    #[test]
+    fn puts_first_with_caret() {
+        let rest: Vec<&str> = DEFAULT_CIPHERS
+            .iter()
+            .copied()
+            .filter(|x| *x != "aes256-gcm@openssh.com")
+            .collect();
+        assert_eq!(
+            effective(Key::Ciphers, Some("^aes256-gcm@openssh.com,3des-cbc")),
+            with(&["aes256-gcm@openssh.com", "3des-cbc"], &rest)
+        );
+    }
+
+    #[test]
+    fn expands_patterns_and_keeps_unknown_names() {
+        assert_eq!(
+            effective(Key::Ciphers, Some("aes*-cbc, my-cipher, nothing*")),
+            names(&["aes128-cbc", "aes192-cbc", "aes256-cbc", "my-cipher"])
+        );
+        assert_eq!(
+            effective(Key::Ciphers, Some("aes256-ctr,aes2*,aes256-ctr")),
+            names(&["aes256-ctr", "aes256-gcm@openssh.com", "aes256-cbc"])
+        );
+        // Deprecated names share the list of their replacement.
+        assert_eq!(
+            effective(Key::PubkeyAcceptedKeyTypes, Some("+ssh-rsa")),
+            with(DEFAULT_KEY_TYPES, &["ssh-rsa"])
+        );
+    }
+
+    #[test]
+    fn compacts_to_the_shortest_form() {
+        let ciphers = |list: &[&str]| compact(Key::Ciphers, &names(list));
+        assert_eq!(
+            compact(Key::Ciphers, &with(DEFAULT_CIPHERS, &["3des-cbc"])),
+            Some("+3des-cbc".to_owned())
+        );
+        assert_eq!(
+            compact(Key::Ciphers, &with(&["3des-cbc"], DEFAULT_CIPHERS)),
+            Some("^3des-cbc".to_owned())
+        );
+        assert_eq!(
+            ciphers(&DEFAULT_CIPHERS[1..]),
+            Some("-chacha20-poly1305@openssh.com".to_owned())
+        );
+        // Listing one algorithm is shorter than removing the others.
+        assert_eq!(ciphers(&["aes256-ctr"]), Some("aes256-ctr".to_owned()));
+        // A reordered default list has no shorter form.
+        let mut reordered = names(DEFAULT_CIPHERS);
+        reordered.swap(0, 1);
+        assert_eq!(compact(Key::Ciphers, &reordered), Some(reordered.join(",")));
+    }
+
+    #[test]
+    fn round_trips_each_form() {
+        for value in [
+            "+3des-cbc",
+            "+3des-cbc,my-cipher",
+            "^aes128-cbc",
+            "-aes128-ctr",
+            "-aes192-ctr,aes256-gcm@openssh.com",
+            "aes256-ctr",
+            "aes256-cbc,aes128-ctr",
+        ] {
+            let enabled = effective(Key::Ciphers, Some(value));
+            assert_eq!(compact(Key::Ciphers, &enabled).as_deref(), Some(value));
+        }
+    }
+}
@@ -1,24 +1,186 @@
-use std::{collections::HashMap, io};
+use std::{
+    collections::{hash_map, HashMap, HashSet},
+    error::Error,
+    io::{self, BufWriter},
+    path::PathBuf,
+    process::Child,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
 
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::event::{
+    self, Event, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+};
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Layout, Rect},
+    layout::{Constraint, Layout, Position, Rect},
     prelude::Backend,
-    style::{palette::tailwind::SLATE, Modifier, Style, Stylize},
-    symbols,
+    style::{Style, Stylize},
     text::Line,
     widgets::{
         Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph,
-        StatefulWidget, Widget,
+        StatefulWidget, Widget, Wrap,
     },
     Terminal,
 };
 use tui_textarea::{CursorMove, TextArea};
 
-use crate::sshconfig::{self, Config, ALL_KEYS};
+use crate::{
+    agent, algorithms, audit, certs,
+    completion::Completion,
+    connect,
+    forward::{self, Forward},
+    fuzzy,
+    grouping::{self, Grouping, Row},
+    keygen::{self, KeyType},
+    keys, knownhosts, lint,
+    migrate::{self, Change},
+    mux,
+    reachability::{self, Status},
+    settings::{
+        contract_tilde, expand_tilde, Action, Settings, ALGORITHM_ACTIONS, COMMENT_ACTIONS,
+        CONNECT_ACTIONS, EDIT_ACTIONS, FILTER_ACTIONS, FORWARD_ACTIONS, KEYGEN_ACTIONS,
+        KEYS_ACTIONS, KNOWN_HOSTS_ACTIONS, MAIN_ACTIONS, MIGRATION_ACTIONS, NEW_ACTIONS,
+        PICK_ACTIONS, SELECT_ACTIONS, TUNNELS_ACTIONS,
+    },
+    sshconfig::{self, Config, Key, ValueKind, Version, ALL_KEYS},
+    sshconfigfile::save_config,
+    topology, tui, tunnels,
+};
 
-const SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
+/// Below this width the panes are shown one at a time.
+const NARROW_WIDTH: u16 = 100;
+
+/// From this width a third pane shows the effective config of the host.
+const WIDE_WIDTH: u16 = 160;
+
+/// Two clicks on the same cell within this delay make a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+type Hints = &'static [(&'static [Action], &'static str)];
+
+const MAIN_HINTS: Hints = &[
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Delete], "delete"),
+    (&[Action::New], "new"),
+    (&[Action::AddKey], "add key"),
+    (&[Action::ToggleAllKeys], "all keys"),
+    (&[Action::EditComment], "comment"),
+    (&[Action::EditTags], "tags"),
+    (&[Action::GroupBy], "group"),
+    (&[Action::ToggleGroup], "fold"),
+    (&[Action::Filter], "filter"),
+    (&[Action::Migrate], "migrate"),
+    (&[Action::Connect], "connect"),
+    (&[Action::CheckReachability], "check reachability"),
+    (&[Action::KnownHosts], "known hosts"),
+    (&[Action::Keys], "keys"),
+    (&[Action::NewKey], "new key"),
+    (&[Action::MuxCheck], "mux check"),
+    (&[Action::MuxExit], "mux exit"),
+    (&[Action::Tunnels], "tunnels"),
+    (&[Action::ToggleHelp], "help"),
+    (&[Action::ToggleProblems], "problems"),
+    (&[Action::Quit], "write and quit"),
+    (&[Action::Open], "select"),
+];
+
+const SELECT_HINTS: Hints = &[
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Delete], "delete"),
+    (&[Action::AddKey], "add key"),
+    (&[Action::ToggleAllKeys], "all keys"),
+    (&[Action::UseSafer], "safer value"),
+    (&[Action::EditComment], "comment"),
+    (&[Action::EditTags], "tags"),
+    (&[Action::ToggleHelp], "help"),
+    (&[Action::ToggleProblems], "problems"),
+    (&[Action::Quit], "write and quit"),
+    (&[Action::Open], "edit"),
+    (&[Action::Back], "back"),
+];
+
+const EDIT_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::Confirm], "save"),
+    (&[Action::Complete], "complete"),
+    (&[Action::ToggleHelp], "help"),
+];
+
+const FORWARD_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::NextField, Action::PreviousField], "field"),
+    (&[Action::Down, Action::Up], "forward"),
+    (&[Action::Confirm], "save"),
+    (&[Action::ToggleHelp], "help"),
+];
+
+const ALGORITHM_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Toggle], "toggle"),
+    (&[Action::MoveDown, Action::MoveUp], "reorder"),
+    (&[Action::Confirm], "save"),
+    (&[Action::ToggleHelp], "help"),
+];
+
+const MIGRATION_HINTS: Hints = &[
+    (&[Action::Cancel], "skip"),
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Toggle], "toggle"),
+    (&[Action::Confirm], "apply"),
+    (&[Action::ToggleHelp], "help"),
+];
+
+const CONNECT_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Toggle], "save first"),
+    (&[Action::Confirm], "connect"),
+];
+
+const KNOWN_HOSTS_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Toggle], "mark"),
+    (&[Action::ShowOrphans], "orphans"),
+    (&[Action::Confirm], "remove marked"),
+];
+
+const KEYS_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::AddToAgent], "add to agent"),
+];
+
+const TUNNELS_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (&[Action::Down, Action::Up], "move"),
+    (&[Action::Toggle], "start/stop"),
+];
+
+const KEYGEN_HINTS: Hints = &[
+    (&[Action::Cancel], "back"),
+    (
+        &[Action::NextField, Action::PreviousField],
+        "next/previous field",
+    ),
+    (&[Action::Down, Action::Up], "key type"),
+    (&[Action::Confirm], "create"),
+];
+
+const NEW_HINTS: Hints = &[(&[Action::Cancel], "back"), (&[Action::Confirm], "save")];
+
+const COMMENT_HINTS: Hints = &[(&[Action::Cancel], "back"), (&[Action::Confirm], "save")];
+
+const FILTER_HINTS: Hints = &[(&[Action::Cancel], "back"), (&[Action::Confirm], "apply")];
+
+const PICK_HINTS: Hints = &[
+    (&[Action::Up, Action::Down], "move"),
+    (&[Action::ToggleHelp], "help"),
+    (&[Action::Cancel], "back"),
+    (&[Action::Confirm], "edit"),
+];
 
 /// This struct holds the current state of the app. In particular, it has the `todo_list` field
 /// which is a wrapper around `ListState`. Keeping track of the state lets us render the
@@ -31,12 +193,56 @@ pub struct App {
     config_list: ConfigList,
     config_content_list: ConfigContentList,
     current_state: Option<AppState>,
+    show_all_keys: bool,
+    show_help: bool,
+    show_problems: bool,
+    /// OpenSSH version deprecated keys are migrated for.
+    openssh_version: Version,
+    out_file: PathBuf,
+    /// The file ssh should read to see the config as last saved: the input
+    /// file until the config is written to `out_file`.
+    config_file: PathBuf,
+    /// The config as last read or saved, formatted, to tell whether there
+    /// are pending edits.
+    saved: String,
+    /// Command line to run once the terminal is handed over.
+    launch: Option<Vec<String>>,
+    /// Block and `IdentityFile` value of the key the launched ssh-keygen
+    /// creates, set on the block once it succeeds.
+    new_key: Option<(usize, String)>,
+    /// Shown in place of the key hints until the next key press.
+    message: Option<String>,
+    /// Results of the running reachability checks.
+    checks: Option<Receiver<(String, Status)>>,
+    /// Lines of the user known_hosts files.
+    known_hosts: Vec<knownhosts::Entry>,
+    /// Keys of `~/.ssh` and the ones the config names elsewhere.
+    keys: Vec<keys::KeyFile>,
+    /// Tunnels started from sshe, reaped when they exit. They keep running
+    /// after sshe exits.
+    tunnel_children: Vec<Child>,
+    /// Keys loaded in ssh-agent, or why they could not be listed.
+    agent: Result<Vec<keys::PublicKey>, String>,
+    settings: Settings,
+    areas: Areas,
+    last_click: Option<(Instant, Position)>,
 }
 enum AppState {
     Main(Main),
     New(New),
     Select(Select),
     Edit(Edit),
+    Pick(Pick),
+    Comment(Comment),
+    Filter(Filter),
+    ForwardForm(ForwardForm),
+    AlgorithmForm(AlgorithmForm),
+    Migration(Migration),
+    Connect(Connect),
+    KnownHosts(KnownHosts),
+    Keys(Keys),
+    KeygenForm(KeygenForm),
+    Tunnels(Tunnels),
 }
 
 pub struct Main;
@@ -53,25 +259,153 @@ pub struct Edit {
     config_idx: usize,
     key: sshconfig::Key,
     textarea: TextArea<'static>,
+    completion: Option<Completion>,
+}
+
+/// Form over the forwards of a `LocalForward`, `RemoteForward` or
+/// `DynamicForward` key: one entry per line of the key, plus an empty one to
+/// add a forward. `textarea` holds the field being typed in.
+pub struct ForwardForm {
+    config_idx: usize,
+    key: Key,
+    entries: Vec<Vec<String>>,
+    entry: usize,
+    field: usize,
+    textarea: TextArea<'static>,
+}
+
+/// Checklist over the algorithms of a list key such as `Ciphers`, in order
+/// of preference, with whether each one is enabled.
+pub struct AlgorithmForm {
+    config_idx: usize,
+    key: Key,
+    items: Vec<(String, bool)>,
+    state: ListState,
+}
+
+/// Prompt listing the deprecated keys to rename or remove, with whether
+/// each change is to be applied.
+pub struct Migration {
+    changes: Vec<(Change, bool)>,
+    state: ListState,
+}
+
+/// Choice of the command used to connect to a host, and whether pending
+/// edits are saved first.
+pub struct Connect {
+    alias: String,
+    state: ListState,
+    modified: bool,
+    save: bool,
+}
+
+/// The known_hosts lines of a host, or the orphaned ones, with whether
+/// each is marked for removal.
+pub struct KnownHosts {
+    alias: String,
+    orphans: bool,
+    entries: Vec<(knownhosts::Entry, bool)>,
+    state: ListState,
+}
+
+/// The key inventory, with the hosts using each key.
+pub struct Keys {
+    state: ListState,
+}
+
+/// The hosts with local forwards, to start and stop `ssh -N` for them.
+/// `running` and `listening` are read when the view opens and after each
+/// start or stop.
+pub struct Tunnels {
+    hosts: Vec<String>,
+    running: Vec<tunnels::Tunnel>,
+    listening: HashSet<u16>,
+    state: ListState,
+}
+
+/// Wizard creating a key pair for a host with ssh-keygen. `values` holds
+/// the file and comment, `textarea` the one being typed in.
+pub struct KeygenForm {
+    config_idx: usize,
+    alias: String,
+    key_type: KeyType,
+    values: [String; 2],
+    /// 0 for the key type, then the file and comment.
+    field: usize,
+    textarea: TextArea<'static>,
+    error: Option<String>,
+}
+
+/// Fuzzy search over every known keyword, used to add a key that is not set
+/// on the selected config yet.
+pub struct Pick {
+    config_idx: usize,
+    textarea: TextArea<'static>,
+    state: ListState,
+}
+
+/// Edit the comment describing a host, shown under it in the list, or the
+/// tags kept in its `# sshe:` comment.
+pub struct Comment {
+    config_idx: usize,
+    textarea: TextArea<'static>,
+    from_select: bool,
+    field: CommentField,
+}
+
+#[derive(Clone, Copy)]
+enum CommentField {
+    Description,
+    Tags,
+}
+
+/// Only show hosts carrying the given tags.
+pub struct Filter {
+    textarea: TextArea<'static>,
 }
 
 impl Main {
     fn handle_key(self, app: &mut App, key: KeyEvent) {
-        let new_state = match key.code {
-            KeyCode::Char('q') | KeyCode::Esc => self.quit(app),
-            KeyCode::Char('j') | KeyCode::Down => self.select_next(app),
-            KeyCode::Char('k') | KeyCode::Up => self.select_previous(app),
-            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => self.state_next(app),
-            KeyCode::Char('g') | KeyCode::Home => self.select_first(app),
-            KeyCode::Char('G') | KeyCode::End => self.select_last(app),
-            KeyCode::Char('d') | KeyCode::Delete => self.delete(app),
-            KeyCode::Char('n') => self.state_new(),
+        let new_state = match app.settings.keys.action(&key, MAIN_ACTIONS) {
+            Some(Action::Quit) => self.quit(app),
+            Some(Action::Down) => self.select_next(app),
+            Some(Action::Up) => self.select_previous(app),
+            Some(Action::Open) => self.state_next(app),
+            Some(Action::First) => self.select_first(app),
+            Some(Action::Last) => self.select_last(app),
+            Some(Action::Delete) => self.delete(app),
+            Some(Action::New) => self.state_new(),
+            Some(Action::AddKey) => self.state_pick(app),
+            Some(Action::ToggleAllKeys) => self.toggle_all_keys(app),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
+            Some(Action::ToggleProblems) => self.toggle_problems(app),
+            Some(Action::EditComment) => self.state_comment(app, CommentField::Description),
+            Some(Action::EditTags) => self.state_comment(app, CommentField::Tags),
+            Some(Action::GroupBy) => self.cycle_grouping(app),
+            Some(Action::ToggleGroup) => self.toggle_group(app),
+            Some(Action::Filter) => self.state_filter(app),
+            Some(Action::Migrate) => self.state_migration(app),
+            Some(Action::Connect) => self.state_connect(app),
+            Some(Action::CheckReachability) => self.check_reachability(app),
+            Some(Action::KnownHosts) => self.state_known_hosts(app),
+            Some(Action::NewKey) => self.state_keygen(app),
+            Some(Action::MuxCheck) => self.mux_control(app, "check"),
+            Some(Action::MuxExit) => self.mux_control(app, "exit"),
+            Some(Action::Keys) => AppState::Keys(Keys {
+                state: ListState::default().with_selected(Some(0)),
+            }),
+            Some(Action::Tunnels) => AppState::Tunnels(Tunnels::new(app)),
             _ => AppState::Main(self),
         };
 
         app.current_state = Some(new_state);
     }
 
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = app.pane_mouse(mouse).unwrap_or(AppState::Main(self));
+        app.current_state = Some(new_state);
+    }
+
     fn quit(self, app: &mut App) -> AppState {
         app.should_exit = true;
         AppState::Main(self)
@@ -90,11 +424,11 @@ impl Main {
     }
 
     fn state_next(self, app: &mut App) -> AppState {
-        if let Some(idx) = app.config_list.state.selected() {
+        if let Some(idx) = app.config_list.selected() {
             app.config_content_list.state = ListState::default().with_selected(Some(0));
             AppState::Select(Select { idx })
         } else {
-            AppState::Main(self)
+            self.toggle_group(app)
         }
     }
 
@@ -105,6 +439,185 @@ impl Main {
         AppState::New(New { textarea })
     }
 
+    fn state_pick(self, app: &mut App) -> AppState {
+        if let Some(idx) = app.config_list.selected() {
+            AppState::Pick(Pick::new(idx))
+        } else {
+            AppState::Main(self)
+        }
+    }
+
+    fn toggle_all_keys(self, app: &mut App) -> AppState {
+        app.show_all_keys = !app.show_all_keys;
+        AppState::Main(self)
+    }
+
+    fn state_comment(self, app: &mut App, field: CommentField) -> AppState {
+        if let Some(idx) = app.config_list.selected() {
+            AppState::Comment(Comment::new(app, idx, false, field))
+        } else {
+            AppState::Main(self)
+        }
+    }
+
+    fn cycle_grouping(self, app: &mut App) -> AppState {
+        let idx = app.config_list.selected();
+        app.config_list.grouping = app.config_list.grouping.next();
+        app.config_list.select_config(idx);
+        AppState::Main(self)
+    }
+
+    /// Fold or unfold the section under the cursor, or the one containing
+    /// the selected host.
+    fn toggle_group(self, app: &mut App) -> AppState {
+        let list = &mut app.config_list;
+        let rows = list.rows();
+        let Some(selected) = list.state.selected() else {
+            return AppState::Main(self);
+        };
+
+        let group = rows
+            .iter()
+            .enumerate()
+            .take(selected.min(rows.len().saturating_sub(1)) + 1)
+            .rev()
+            .find_map(|(i, row)| match row {
+                Row::Group { name, .. } => Some((i, name.clone())),
+                Row::Host(_) => None,
+            });
+
+        if let Some((i, name)) = group {
+            if !list.collapsed.remove(&name) {
+                list.collapsed.insert(name);
+            }
+            list.state.select(Some(i));
+        }
+        app.config_content_list.state = ListState::default();
+
+        AppState::Main(self)
+    }
+
+    /// Offer to connect to the first alias of the selected host. Pattern
+    /// only blocks have nothing to connect to.
+    fn state_connect(self, app: &mut App) -> AppState {
+        let Some(idx) = app.config_list.selected() else {
+            return AppState::Main(self);
+        };
+        let cfg = &app.config_list.items[idx];
+        let Some(alias) = topology::aliases(std::slice::from_ref(cfg))
+            .first()
+            .copied()
+        else {
+            app.message = Some(format!("Host {} has no alias to connect to", cfg.host));
+            return AppState::Main(self);
+        };
+
+        let modified = app.is_modified();
+        AppState::Connect(Connect {
+            alias: alias.to_owned(),
+            state: ListState::default().with_selected(Some(0)),
+            modified,
+            save: modified,
+        })
+    }
+
+    /// Check every host in the background, the results show up in the list
+    /// as they come.
+    fn check_reachability(self, app: &mut App) -> AppState {
+        let list = &mut app.config_list;
+        let aliases: Vec<String> = topology::aliases(&list.items)
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        list.reachability = aliases.iter().map(|a| (a.clone(), None)).collect();
+        app.checks = Some(reachability::check_all(
+            &list.items,
+            &aliases,
+            reachability::TIMEOUT,
+        ));
+        AppState::Main(self)
+    }
+
+    /// Run `ssh -O operation` for the selected host and show what ssh said.
+    fn mux_control(self, app: &mut App, operation: &str) -> AppState {
+        let Some(idx) = app.config_list.selected() else {
+            return AppState::Main(self);
+        };
+        let configs = &app.config_list.items;
+        let Some(alias) = topology::aliases(std::slice::from_ref(&configs[idx]))
+            .first()
+            .copied()
+        else {
+            return AppState::Main(self);
+        };
+        if mux::control_path(configs, alias).is_none() {
+            app.message = Some(format!("Host {} has no ControlPath", alias));
+            return AppState::Main(self);
+        }
+
+        app.message = Some(match mux::control(&app.config_file, alias, operation) {
+            Ok(text) if text.is_empty() => format!("ssh -O {} {} succeeded", operation, alias),
+            Ok(text) => text,
+            Err(text) => format!("ssh -O {} {} failed: {}", operation, alias, text),
+        });
+        app.config_list.mux = mux::statuses(&app.config_list.items);
+        AppState::Main(self)
+    }
+
+    fn state_keygen(self, app: &mut App) -> AppState {
+        let Some(idx) = app.config_list.selected() else {
+            return AppState::Main(self);
+        };
+        let cfg = &app.config_list.items[idx];
+        let Some(alias) = topology::aliases(std::slice::from_ref(cfg))
+            .first()
+            .copied()
+        else {
+            app.message = Some(format!("Host {} has no alias to name the key", cfg.host));
+            return AppState::Main(self);
+        };
+        AppState::KeygenForm(KeygenForm::new(idx, alias))
+    }
+
+    fn state_known_hosts(self, app: &mut App) -> AppState {
+        let Some(idx) = app.config_list.selected() else {
+            return AppState::Main(self);
+        };
+        let cfg = &app.config_list.items[idx];
+        let Some(alias) = topology::aliases(std::slice::from_ref(cfg))
+            .first()
+            .copied()
+        else {
+            app.message = Some(format!("Host {} has no alias to look up", cfg.host));
+            return AppState::Main(self);
+        };
+        AppState::KnownHosts(KnownHosts::new(app, alias.to_owned(), false))
+    }
+
+    fn state_migration(self, app: &mut App) -> AppState {
+        match Migration::new(app) {
+            Some(migration) => AppState::Migration(migration),
+            None => AppState::Main(self),
+        }
+    }
+
+    fn state_filter(self, app: &mut App) -> AppState {
+        let mut textarea = TextArea::new(vec![app.config_list.filter.join(",")]);
+        textarea.set_cursor_line_style(Style::default());
+        textarea.move_cursor(CursorMove::End);
+        AppState::Filter(Filter { textarea })
+    }
+
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::Main(self)
+    }
+
+    fn toggle_problems(self, app: &mut App) -> AppState {
+        app.show_problems = !app.show_problems;
+        AppState::Main(self)
+    }
+
     fn select_first(self, app: &mut App) -> AppState {
         app.config_list.state.select_first();
         AppState::Main(self)
@@ -116,8 +629,13 @@ impl Main {
     }
 
     fn delete(self, app: &mut App) -> AppState {
-        if let Some(i) = app.config_list.state.selected() {
-            app.config_list.items.remove(i);
+        if let Some(i) = app.config_list.selected() {
+            let removed = app.config_list.items.remove(i);
+            // The global options stay at the top of the file.
+            if let Some(next) = app.config_list.items.get_mut(i) {
+                next.preamble.extend(removed.preamble);
+            }
+            app.config_changed();
         }
 
         AppState::Main(self)
@@ -126,15 +644,25 @@ impl Main {
 
 impl New {
     fn handle_key(self, app: &mut App, key: KeyEvent) {
-        let new_state = match key.code {
-            KeyCode::Esc => self.state_back(),
-            KeyCode::Enter => self.state_save(app),
+        let new_state = match app.settings.keys.popup_action(&key, NEW_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(),
+            Some(Action::Confirm) => self.state_save(app),
             _ => self.other_input(key),
         };
 
         app.current_state = Some(new_state);
     }
 
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = if app.is_click_outside_popup(mouse) {
+            self.state_back()
+        } else {
+            AppState::New(self)
+        };
+
+        app.current_state = Some(new_state);
+    }
+
     fn state_back(self) -> AppState {
         AppState::Main(Main)
     }
@@ -146,8 +674,9 @@ impl New {
 
         app.config_list.items.push(Config {
             host: content,
-            columns: HashMap::new(),
+            ..Default::default()
         });
+        app.config_changed();
 
         AppState::Main(Main)
     }
@@ -160,19 +689,31 @@ impl New {
 
 impl Select {
     fn handle_key(self, app: &mut App, key: KeyEvent) {
-        let new_state = match key.code {
-            KeyCode::Char('q') | KeyCode::Esc => self.quit(app),
-            KeyCode::Char('j') | KeyCode::Down => self.select_next(app),
-            KeyCode::Char('k') | KeyCode::Up => self.select_previous(app),
-            KeyCode::Char('h') | KeyCode::Left => self.state_back(app),
-            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => self.state_next(app),
-            KeyCode::Char('d') | KeyCode::Delete => self.delete(app),
+        let new_state = match app.settings.keys.action(&key, SELECT_ACTIONS) {
+            Some(Action::Quit) => self.quit(app),
+            Some(Action::Down) => self.select_next(app),
+            Some(Action::Up) => self.select_previous(app),
+            Some(Action::Back) => self.state_back(app),
+            Some(Action::Open) => self.state_next(app),
+            Some(Action::Delete) => self.delete(app),
+            Some(Action::AddKey) => self.state_pick(),
+            Some(Action::ToggleAllKeys) => self.toggle_all_keys(app),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
+            Some(Action::ToggleProblems) => self.toggle_problems(app),
+            Some(Action::EditComment) => self.state_comment(app, CommentField::Description),
+            Some(Action::EditTags) => self.state_comment(app, CommentField::Tags),
+            Some(Action::UseSafer) => self.use_safer(app),
             _ => AppState::Select(self),
         };
 
         app.current_state = Some(new_state);
     }
 
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = app.pane_mouse(mouse).unwrap_or(AppState::Select(self));
+        app.current_state = Some(new_state);
+    }
+
     fn quit(self, app: &mut App) -> AppState {
         app.should_exit = true;
         AppState::Select(self)
@@ -196,51 +737,150 @@ impl Select {
     fn state_next(self, app: &mut App) -> AppState {
         let cfg_idx = self.idx;
 
-        if let Some(i) = app.config_content_list.state.selected() {
-            let value = app.config_list.items[cfg_idx]
-                .columns
-                .get(&ALL_KEYS[i])
-                .cloned();
-            let mut v = vec![];
-            if let Some(x) = value {
-                v.push(x);
-            }
-            let mut textarea = TextArea::new(v);
-            textarea.set_cursor_line_style(Style::default());
-            textarea.move_cursor(CursorMove::End);
-            AppState::Edit(Edit {
-                key: ALL_KEYS[i],
-                config_idx: cfg_idx,
-                textarea,
-            })
+        let key = app
+            .config_content_list
+            .state
+            .selected()
+            .and_then(|i| app.visible_keys(cfg_idx).get(i).copied());
+
+        if let Some(key) = key {
+            editor(app, cfg_idx, key)
         } else {
             AppState::Select(self)
         }
     }
 
     fn delete(self, app: &mut App) -> AppState {
-        if let Some(i) = app.config_content_list.state.selected() {
-            let cfg_idx = self.idx;
-            let key = ALL_KEYS[i];
+        let cfg_idx = self.idx;
+        let key = app
+            .config_content_list
+            .state
+            .selected()
+            .and_then(|i| app.visible_keys(cfg_idx).get(i).copied());
+
+        if let Some(key) = key {
+            app.config_list.items[cfg_idx].remove_key(key);
+            app.config_changed();
+            app.clamp_content_selection(cfg_idx);
+        }
 
-            app.config_list.items[cfg_idx].columns.remove(&key);
+        AppState::Select(self)
+    }
+
+    /// Replace the risky value under the cursor with the safer one.
+    fn use_safer(self, app: &mut App) -> AppState {
+        if let Some(risk) = app.risk_at_cursor(self.idx) {
+            risk.apply(&mut app.config_list.items);
+            app.config_changed();
+            app.clamp_content_selection(self.idx);
         }
+        AppState::Select(self)
+    }
+
+    fn state_pick(self) -> AppState {
+        AppState::Pick(Pick::new(self.idx))
+    }
+
+    fn state_comment(self, app: &mut App, field: CommentField) -> AppState {
+        AppState::Comment(Comment::new(app, self.idx, true, field))
+    }
+
+    fn toggle_all_keys(self, app: &mut App) -> AppState {
+        let key = app
+            .config_content_list
+            .state
+            .selected()
+            .and_then(|i| app.visible_keys(self.idx).get(i).copied());
+        app.show_all_keys = !app.show_all_keys;
+        app.select_content_key(self.idx, key);
+        AppState::Select(self)
+    }
 
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::Select(self)
+    }
+
+    fn toggle_problems(self, app: &mut App) -> AppState {
+        app.show_problems = !app.show_problems;
         AppState::Select(self)
     }
 }
 
 impl Edit {
+    fn new(app: &App, config_idx: usize, key: sshconfig::Key) -> Self {
+        // Repeated keys such as `IdentityFile` are edited one line each.
+        let v = match app.config_list.items[config_idx].columns.get(&key) {
+            Some(x) => x.lines().map(str::to_owned).collect(),
+            None => vec![],
+        };
+        let mut textarea = TextArea::new(v);
+        textarea.set_cursor_line_style(Style::default());
+        textarea.move_cursor(CursorMove::End);
+        Edit {
+            key,
+            config_idx,
+            textarea,
+            completion: None,
+        }
+    }
+
     fn handle_key(self, app: &mut App, key: KeyEvent) {
-        let new_state = match key.code {
-            KeyCode::Esc => self.state_back(),
-            KeyCode::Enter => self.state_save(app),
+        let new_state = match app.settings.keys.popup_action(&key, EDIT_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(),
+            Some(Action::Confirm) => self.state_save(app),
+            Some(Action::Complete) => self.complete_next(app),
+            Some(Action::CompletePrevious) => self.complete_previous(app),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
             _ => self.other_input(key),
         };
 
         app.current_state = Some(new_state);
     }
 
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = if app.is_click_outside_popup(mouse) {
+            self.state_back()
+        } else {
+            AppState::Edit(self)
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn complete_next(mut self, app: &App) -> AppState {
+        if self.completion.is_none() {
+            let input = &self.textarea.lines()[self.textarea.cursor().0];
+            self.completion = Completion::new(self.key, input, &app.config_list.items);
+        }
+        if let Some(value) = self.completion.as_mut().map(Completion::next) {
+            self.set_value(&value);
+        }
+        AppState::Edit(self)
+    }
+
+    fn complete_previous(mut self, app: &App) -> AppState {
+        if self.completion.is_none() {
+            let input = &self.textarea.lines()[self.textarea.cursor().0];
+            self.completion = Completion::new(self.key, input, &app.config_list.items);
+        }
+        if let Some(value) = self.completion.as_mut().map(Completion::previous) {
+            self.set_value(&value);
+        }
+        AppState::Edit(self)
+    }
+
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::Edit(self)
+    }
+
+    fn set_value(&mut self, value: &str) {
+        self.textarea.move_cursor(CursorMove::End);
+        self.textarea.delete_line_by_head();
+        self.textarea.insert_str(value);
+    }
+
     fn state_back(self) -> AppState {
         let idx = self.config_idx;
         AppState::Select(Select { idx })
@@ -250,7 +890,7 @@ impl Edit {
         let textarea = &self.textarea;
         let idx = self.config_idx;
 
-        let content = textarea.lines()[0].to_owned();
+        let content = textarea.lines().join("\n");
         let cfg = &mut app.config_list.items[self.config_idx];
 
         if let Some(v) = cfg.columns.get_mut(&self.key) {
@@ -258,205 +898,2643 @@ impl Edit {
         } else {
             cfg.columns.insert(self.key, content);
         }
+        app.config_changed();
+        app.select_content_key(idx, Some(self.key));
 
         AppState::Select(Select { idx })
     }
 
     fn other_input(mut self, key: KeyEvent) -> AppState {
-        _ = self.textarea.input(key);
+        if self.textarea.input(key) {
+            self.completion = None;
+        }
         AppState::Edit(self)
     }
 }
 
-#[derive(Default)]
-struct ConfigList {
-    items: Vec<Config>,
-    state: ListState,
-}
-
-#[derive(Default)]
-struct ConfigContentList {
-    state: ListState,
-}
-
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            should_exit: false,
-            config_list: ConfigList::default(),
-            config_content_list: ConfigContentList::default(),
-            current_state: Some(AppState::Main(Main)),
-        }
+/// The popup used to edit `key`: a form for forwards, a text box otherwise.
+fn editor(app: &App, config_idx: usize, key: Key) -> AppState {
+    match key.value_kind() {
+        ValueKind::Forward => AppState::ForwardForm(ForwardForm::new(app, config_idx, key)),
+        ValueKind::Algorithms => AppState::AlgorithmForm(AlgorithmForm::new(app, config_idx, key)),
+        _ => AppState::Edit(Edit::new(app, config_idx, key)),
     }
 }
 
-impl App {
-    pub fn with_config(cfg: Vec<Config>) -> Self {
-        Self {
-            config_list: ConfigList {
-                items: cfg,
-                state: Default::default(),
-            },
-            ..Default::default()
-        }
+impl ForwardForm {
+    fn new(app: &App, config_idx: usize, key: Key) -> Self {
+        let width = forward::field_names(key).len();
+        let value = app.config_list.items[config_idx].columns.get(&key);
+        let mut entries: Vec<Vec<String>> = value
+            .into_iter()
+            .flat_map(|v| v.lines())
+            .map(|line| match Forward::parse(key, line) {
+                Ok(forward) => forward.fields(),
+                // Keep what was written so that it can be fixed.
+                Err(_) => {
+                    let mut args = line.split_whitespace();
+                    vec![
+                        String::new(),
+                        args.next().unwrap_or_default().to_owned(),
+                        args.collect::<Vec<_>>().join(" "),
+                    ]
+                }
+            })
+            .map(|mut fields| {
+                fields.resize(width, String::new());
+                fields
+            })
+            .collect();
+        entries.push(vec![String::new(); width]);
+
+        let mut form = ForwardForm {
+            config_idx,
+            key,
+            entries,
+            entry: 0,
+            field: 0,
+            textarea: TextArea::default(),
+        };
+        form.load();
+        form
     }
 
-    pub fn config(&self) -> &[Config] {
-        &self.config_list.items
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.popup_action(&key, FORWARD_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(),
+            Some(Action::Confirm) => self.state_save(app),
+            Some(Action::Down) => self.move_entry(1),
+            Some(Action::Up) => self.move_entry(-1),
+            Some(Action::NextField) => self.move_field(1),
+            Some(Action::PreviousField) => self.move_field(-1),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
+            _ => self.other_input(key),
+        };
+
+        app.current_state = Some(new_state);
     }
 
-    pub fn run(&mut self, mut terminal: Terminal<impl Backend>) -> io::Result<()> {
-        while !self.should_exit {
-            terminal.draw(|f| f.render_widget(&mut *self, f.size()))?;
-            if let Event::Key(key) = event::read()? {
-                self.handle_key(key);
-            };
-        }
-        Ok(())
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = if app.is_click_outside_popup(mouse) {
+            self.state_back()
+        } else {
+            AppState::ForwardForm(self)
+        };
+
+        app.current_state = Some(new_state);
     }
 
-    fn handle_key(&mut self, key: KeyEvent) {
-        if key.kind != KeyEventKind::Press {
-            return;
-        }
+    /// Fields of the current entry, with what is being typed.
+    fn current_fields(&self) -> Vec<String> {
+        let mut fields = self.entries[self.entry].clone();
+        fields[self.field] = self.textarea.lines()[0].clone();
+        fields
+    }
 
-        match self.current_state.take() {
-            Some(AppState::Main(main)) => main.handle_key(self, key),
-            Some(AppState::Select(select)) => select.handle_key(self, key),
-            Some(AppState::Edit(edit)) => edit.handle_key(self, key),
-            Some(AppState::New(new)) => new.handle_key(self, key),
-            None => unreachable!(),
+    fn store(&mut self) {
+        self.entries[self.entry] = self.current_fields();
+        if self
+            .entries
+            .last()
+            .is_some_and(|e| e.iter().any(|x| !x.is_empty()))
+        {
+            self.entries
+                .push(vec![String::new(); self.entries[0].len()]);
         }
     }
-}
 
-impl Widget for &mut App {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let [header_area, main_area, footer_area] = Layout::vertical([
-            Constraint::Length(2),
-            Constraint::Fill(1),
-            Constraint::Length(1),
-        ])
-        .areas(area);
+    fn load(&mut self) {
+        let value = self.entries[self.entry][self.field].clone();
+        self.textarea = TextArea::new(vec![value]);
+        self.textarea.set_cursor_line_style(Style::default());
+        self.textarea.move_cursor(CursorMove::End);
+    }
 
-        let [index_area, selected_area] =
-            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(main_area);
+    fn move_entry(mut self, delta: isize) -> AppState {
+        self.store();
+        let len = self.entries.len() as isize;
+        self.entry = (self.entry as isize + delta).rem_euclid(len) as usize;
+        self.load();
+        AppState::ForwardForm(self)
+    }
 
-        App::render_header(header_area, buf);
-        self.render_footer(footer_area, buf);
-        self.render_list(index_area, buf);
-        self.render_selected(selected_area, buf);
-        self.render_new_textarea(buf);
-        self.render_edit_textarea(buf);
+    /// Move between fields, going on to the next or previous entry past the
+    /// last or first field.
+    fn move_field(mut self, delta: isize) -> AppState {
+        self.store();
+        let width = self.entries[0].len() as isize;
+        let len = self.entries.len() as isize;
+        let position =
+            (self.entry as isize * width + self.field as isize + delta).rem_euclid(len * width);
+        self.entry = (position / width) as usize;
+        self.field = (position % width) as usize;
+        self.load();
+        AppState::ForwardForm(self)
     }
-}
 
-/// Rendering logic for the app
-impl App {
-    fn render_edit_textarea(&mut self, buf: &mut Buffer) {
-        let border_style = match self.current_state {
-            Some(AppState::Edit(..)) => symbols::border::ROUNDED,
-            _ => symbols::border::PLAIN,
-        };
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::ForwardForm(self)
+    }
 
-        let (key, textarea) = match &mut self.current_state {
-            Some(AppState::Edit(edit)) => (edit.key, &mut edit.textarea),
-            _ => return,
-        };
+    fn state_back(self) -> AppState {
+        let idx = self.config_idx;
+        AppState::Select(Select { idx })
+    }
 
-        let pref_width = 30;
-        let pref_height = 3;
-        let width = std::cmp::min(buf.area.width, pref_width);
-        let height = std::cmp::min(buf.area.height, pref_height);
+    /// Write the forwards back to the key, one per line, or stay on the
+    /// first entry that does not validate.
+    fn state_save(mut self, app: &mut App) -> AppState {
+        self.store();
+        let mut lines = vec![];
+        for (i, fields) in self.entries.iter().enumerate() {
+            match Forward::from_fields(self.key, fields) {
+                Ok(Some(forward)) => lines.push(forward.to_string()),
+                Ok(None) => {}
+                Err(_) => {
+                    self.entry = i;
+                    self.load();
+                    return AppState::ForwardForm(self);
+                }
+            }
+        }
 
-        let s_x = (buf.area.width - width) / 2;
-        let s_y = (buf.area.height - height) / 2;
+        let idx = self.config_idx;
+        let cfg = &mut app.config_list.items[idx];
+        if lines.is_empty() {
+            cfg.remove_key(self.key);
+        } else {
+            cfg.columns.insert(self.key, lines.join("\n"));
+        }
+        app.config_changed();
+        app.select_content_key(idx, Some(self.key));
+
+        AppState::Select(Select { idx })
+    }
+
+    fn other_input(mut self, key: KeyEvent) -> AppState {
+        _ = self.textarea.input(key);
+        AppState::ForwardForm(self)
+    }
+}
+
+impl AlgorithmForm {
+    /// Start from the effective list, followed by the other supported
+    /// algorithms, disabled.
+    fn new(app: &App, config_idx: usize, key: Key) -> Self {
+        let value = app.config_list.items[config_idx].columns.get(&key);
+        let enabled = algorithms::effective(key, value.map(|x| x.as_str()));
+        let (defaults, others) = algorithms::known(key).unwrap_or_default();
+        let disabled = defaults
+            .iter()
+            .chain(others.iter())
+            .filter(|x| !enabled.iter().any(|e| e == *x))
+            .map(|x| (x.to_string(), false))
+            .collect::<Vec<_>>();
+
+        AlgorithmForm {
+            config_idx,
+            key,
+            items: enabled
+                .into_iter()
+                .map(|x| (x, true))
+                .chain(disabled)
+                .collect(),
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.action(&key, ALGORITHM_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(),
+            Some(Action::Confirm) => self.state_save(app),
+            Some(Action::Down) => self.select_next(),
+            Some(Action::Up) => self.select_previous(),
+            Some(Action::First) => self.select_first(),
+            Some(Action::Last) => self.select_last(),
+            Some(Action::Toggle) => self.toggle(),
+            Some(Action::MoveUp) => self.move_item(-1),
+            Some(Action::MoveDown) => self.move_item(1),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
+            _ => AppState::AlgorithmForm(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let area = app.areas.pick_list;
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => self.select_next(),
+            MouseEventKind::ScrollUp if area.contains(pos) => self.select_previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = app.register_click(pos);
+                let len = self.items.len();
+                if app.is_click_outside_popup(mouse) {
+                    self.state_back()
+                } else if let Some(i) = row_at(area, pos, self.state.offset(), vec![1; len]) {
+                    self.state.select(Some(i));
+                    if double {
+                        self.toggle()
+                    } else {
+                        AppState::AlgorithmForm(self)
+                    }
+                } else {
+                    AppState::AlgorithmForm(self)
+                }
+            }
+            _ => AppState::AlgorithmForm(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn enabled(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    fn select_next(mut self) -> AppState {
+        self.state.select_next();
+        AppState::AlgorithmForm(self)
+    }
+
+    fn select_previous(mut self) -> AppState {
+        self.state.select_previous();
+        AppState::AlgorithmForm(self)
+    }
+
+    fn select_first(mut self) -> AppState {
+        self.state.select_first();
+        AppState::AlgorithmForm(self)
+    }
+
+    fn select_last(mut self) -> AppState {
+        self.state.select_last();
+        AppState::AlgorithmForm(self)
+    }
+
+    fn toggle(mut self) -> AppState {
+        if let Some((_, on)) = self.state.selected().and_then(|i| self.items.get_mut(i)) {
+            *on = !*on;
+        }
+        AppState::AlgorithmForm(self)
+    }
+
+    /// Move the selected algorithm up or down the order of preference.
+    fn move_item(mut self, delta: isize) -> AppState {
+        if let Some(i) = self.state.selected() {
+            let j = i as isize + delta;
+            if (0..self.items.len() as isize).contains(&j) {
+                self.items.swap(i, j as usize);
+                self.state.select(Some(j as usize));
+            }
+        }
+        AppState::AlgorithmForm(self)
+    }
+
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::AlgorithmForm(self)
+    }
+
+    fn state_back(self) -> AppState {
+        let idx = self.config_idx;
+        AppState::Select(Select { idx })
+    }
+
+    /// Write the shortest value giving the enabled algorithms, removing the
+    /// key when they are the defaults. ssh refuses an empty list, so nothing
+    /// is saved then.
+    fn state_save(self, app: &mut App) -> AppState {
+        let enabled = self.enabled();
+        if enabled.is_empty() {
+            return AppState::AlgorithmForm(self);
+        }
+
+        let idx = self.config_idx;
+        let cfg = &mut app.config_list.items[idx];
+        match algorithms::compact(self.key, &enabled) {
+            Some(value) => {
+                cfg.columns.insert(self.key, value);
+            }
+            None => cfg.remove_key(self.key),
+        }
+        app.config_changed();
+        app.select_content_key(idx, Some(self.key));
+
+        AppState::Select(Select { idx })
+    }
+}
+
+impl Migration {
+    /// Every change is checked to start with. `None` when there is nothing
+    /// to migrate.
+    fn new(app: &App) -> Option<Self> {
+        let changes = migrate::changes(&app.config_list.items, app.openssh_version);
+        if changes.is_empty() {
+            return None;
+        }
+        Some(Migration {
+            changes: changes.into_iter().map(|c| (c, true)).collect(),
+            state: ListState::default().with_selected(Some(0)),
+        })
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.action(&key, MIGRATION_ACTIONS) {
+            Some(Action::Cancel) => AppState::Main(Main),
+            Some(Action::Confirm) => self.apply(app),
+            Some(Action::Down) => self.select_next(),
+            Some(Action::Up) => self.select_previous(),
+            Some(Action::Toggle) => self.toggle(),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
+            _ => AppState::Migration(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let area = app.areas.pick_list;
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => self.select_next(),
+            MouseEventKind::ScrollUp if area.contains(pos) => self.select_previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = app.register_click(pos);
+                let heights: Vec<usize> = self
+                    .changes
+                    .iter()
+                    .map(|(c, _)| c.diff(&app.config_list.items).len() + 2)
+                    .collect();
+                if app.is_click_outside_popup(mouse) {
+                    AppState::Main(Main)
+                } else if let Some(i) = row_at(area, pos, self.state.offset(), heights) {
+                    self.state.select(Some(i));
+                    if double {
+                        self.toggle()
+                    } else {
+                        AppState::Migration(self)
+                    }
+                } else {
+                    AppState::Migration(self)
+                }
+            }
+            _ => AppState::Migration(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn select_next(mut self) -> AppState {
+        self.state.select_next();
+        AppState::Migration(self)
+    }
+
+    fn select_previous(mut self) -> AppState {
+        self.state.select_previous();
+        AppState::Migration(self)
+    }
+
+    fn toggle(mut self) -> AppState {
+        if let Some((_, on)) = self.state.selected().and_then(|i| self.changes.get_mut(i)) {
+            *on = !*on;
+        }
+        AppState::Migration(self)
+    }
+
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::Migration(self)
+    }
+
+    fn apply(self, app: &mut App) -> AppState {
+        for (change, _) in self.changes.iter().filter(|(_, on)| *on) {
+            change.apply(&mut app.config_list.items);
+        }
+        app.config_changed();
+        AppState::Main(Main)
+    }
+}
+
+impl KnownHosts {
+    fn new(app: &App, alias: String, orphans: bool) -> Self {
+        let configs = &app.config_list.items;
+        let entries = if orphans {
+            knownhosts::orphans(&app.known_hosts, configs)
+        } else {
+            knownhosts::matching(&app.known_hosts, configs, &alias)
+        };
+        KnownHosts {
+            alias,
+            orphans,
+            entries: entries.into_iter().map(|e| (e.clone(), false)).collect(),
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.action(&key, KNOWN_HOSTS_ACTIONS) {
+            Some(Action::Cancel) => AppState::Main(Main),
+            Some(Action::Confirm) => self.remove(app),
+            Some(Action::Down) => self.select_next(),
+            Some(Action::Up) => self.select_previous(),
+            Some(Action::Toggle) => self.toggle(),
+            Some(Action::ShowOrphans) => {
+                AppState::KnownHosts(KnownHosts::new(app, self.alias, !self.orphans))
+            }
+            _ => AppState::KnownHosts(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let area = app.areas.pick_list;
+        let len = self.entries.len();
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => self.select_next(),
+            MouseEventKind::ScrollUp if area.contains(pos) => self.select_previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = app.register_click(pos);
+                if app.is_click_outside_popup(mouse) {
+                    AppState::Main(Main)
+                } else if let Some(i) = row_at(area, pos, self.state.offset(), vec![1; len]) {
+                    self.state.select(Some(i));
+                    if double {
+                        self.toggle()
+                    } else {
+                        AppState::KnownHosts(self)
+                    }
+                } else {
+                    AppState::KnownHosts(self)
+                }
+            }
+            _ => AppState::KnownHosts(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn select_next(mut self) -> AppState {
+        self.state.select_next();
+        AppState::KnownHosts(self)
+    }
+
+    fn select_previous(mut self) -> AppState {
+        self.state.select_previous();
+        AppState::KnownHosts(self)
+    }
+
+    fn toggle(mut self) -> AppState {
+        if let Some((_, on)) = self.state.selected().and_then(|i| self.entries.get_mut(i)) {
+            *on = !*on;
+        }
+        AppState::KnownHosts(self)
+    }
+
+    /// Remove the marked lines from their files and read them again.
+    fn remove(self, app: &mut App) -> AppState {
+        let marked: Vec<&knownhosts::Entry> = self
+            .entries
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(e, _)| e)
+            .collect();
+        if marked.is_empty() {
+            return AppState::KnownHosts(self);
+        }
+
+        let files = knownhosts::files(&app.config_list.items);
+        app.message = match knownhosts::remove(&marked).and_then(|_| knownhosts::load(&files)) {
+            Ok(entries) => {
+                app.known_hosts = entries;
+                Some(format!(
+                    "Removed {} known_hosts lines, the previous files are kept as .old",
+                    marked.len()
+                ))
+            }
+            Err(e) => Some(format!("Unable to remove known_hosts lines: {}", e)),
+        };
+        AppState::Main(Main)
+    }
+}
+
+impl KeygenForm {
+    fn new(config_idx: usize, alias: &str) -> Self {
+        let key_type = KeyType::ALL[0];
+        KeygenForm {
+            config_idx,
+            alias: alias.to_owned(),
+            key_type,
+            values: [
+                keygen::default_file(alias, key_type),
+                keygen::default_comment(),
+            ],
+            field: 0,
+            textarea: TextArea::default(),
+            error: None,
+        }
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.popup_action(&key, KEYGEN_ACTIONS) {
+            Some(Action::Cancel) => AppState::Main(Main),
+            Some(Action::Confirm) => self.create(app),
+            Some(Action::NextField) => self.move_field(1),
+            Some(Action::PreviousField) => self.move_field(-1),
+            Some(Action::Down) => self.cycle_type(1),
+            Some(Action::Up) => self.cycle_type(-1),
+            _ => self.other_input(key),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = if app.is_click_outside_popup(mouse) {
+            AppState::Main(Main)
+        } else {
+            AppState::KeygenForm(self)
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    /// The file and comment, with what is being typed.
+    fn current_values(&self) -> [String; 2] {
+        let mut values = self.values.clone();
+        if self.field > 0 {
+            values[self.field - 1] = self.textarea.lines()[0].clone();
+        }
+        values
+    }
+
+    fn move_field(mut self, delta: isize) -> AppState {
+        self.values = self.current_values();
+        self.field = (self.field as isize + delta).rem_euclid(3) as usize;
+        if self.field > 0 {
+            self.textarea = TextArea::new(vec![self.values[self.field - 1].clone()]);
+            self.textarea.set_cursor_line_style(Style::default());
+            self.textarea.move_cursor(CursorMove::End);
+        }
+        AppState::KeygenForm(self)
+    }
+
+    /// Change the key type, along with the file while it is the default.
+    fn cycle_type(mut self, delta: isize) -> AppState {
+        if self.field != 0 {
+            return AppState::KeygenForm(self);
+        }
+        let all = KeyType::ALL;
+        let i = all.iter().position(|t| *t == self.key_type).unwrap_or(0) as isize;
+        let key_type = all[(i + delta).rem_euclid(all.len() as isize) as usize];
+        if self.values[0] == keygen::default_file(&self.alias, self.key_type) {
+            self.values[0] = keygen::default_file(&self.alias, key_type);
+        }
+        self.key_type = key_type;
+        AppState::KeygenForm(self)
+    }
+
+    /// Leave ssh-keygen for [`App::run`] to start, the key is set on the
+    /// host once it succeeds.
+    fn create(mut self, app: &mut App) -> AppState {
+        self.values = self.current_values();
+        let [file, comment] = &self.values;
+        let file = file.trim();
+        if file.is_empty() {
+            self.error = Some("A file is needed".to_owned());
+            return AppState::KeygenForm(self);
+        }
+        let path = expand_tilde(file);
+        if let Err(e) = keygen::prepare(&path) {
+            self.error = Some(e);
+            return AppState::KeygenForm(self);
+        }
+
+        app.launch = Some(keygen::command_line(self.key_type, &path, comment.trim()));
+        app.new_key = Some((self.config_idx, file.to_owned()));
+        AppState::Main(Main)
+    }
+
+    fn other_input(mut self, key: KeyEvent) -> AppState {
+        if self.field > 0 {
+            _ = self.textarea.input(key);
+        }
+        AppState::KeygenForm(self)
+    }
+}
+
+impl Tunnels {
+    fn new(app: &App) -> Self {
+        let mut tunnels = Tunnels {
+            hosts: tunnels::hosts(&app.config_list.items),
+            running: vec![],
+            listening: HashSet::new(),
+            state: ListState::default().with_selected(Some(0)),
+        };
+        tunnels.refresh();
+        tunnels
+    }
+
+    fn refresh(&mut self) {
+        self.running = tunnels::load(&tunnels::state_dir());
+        self.listening = tunnels::listening();
+    }
+
+    fn handle_key(mut self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.action(&key, TUNNELS_ACTIONS) {
+            Some(Action::Cancel) | Some(Action::Confirm) => AppState::Main(Main),
+            Some(Action::Toggle) => self.toggle(app),
+            Some(Action::Down) => {
+                self.state.select_next();
+                AppState::Tunnels(self)
+            }
+            Some(Action::Up) => {
+                self.state.select_previous();
+                AppState::Tunnels(self)
+            }
+            _ => AppState::Tunnels(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let area = app.areas.pick_list;
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => {
+                self.state.select_next();
+                AppState::Tunnels(self)
+            }
+            MouseEventKind::ScrollUp if area.contains(pos) => {
+                self.state.select_previous();
+                AppState::Tunnels(self)
+            }
+            MouseEventKind::Down(MouseButton::Left) if app.is_click_outside_popup(mouse) => {
+                AppState::Main(Main)
+            }
+            _ => AppState::Tunnels(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    /// Stop the tunnel of the selected host, or start it when none runs.
+    fn toggle(mut self, app: &mut App) -> AppState {
+        let Some(alias) = self.state.selected().and_then(|i| self.hosts.get(i)) else {
+            return AppState::Tunnels(self);
+        };
+        app.tunnel_children
+            .retain_mut(|c| !matches!(c.try_wait(), Ok(Some(_))));
+
+        let dir = tunnels::state_dir();
+        let running = tunnels::load(&dir).iter().any(|t| t.alias == *alias);
+        app.message = Some(if running {
+            match tunnels::stop(&dir, alias) {
+                Ok(()) => format!("Stopped the tunnel of {}", alias),
+                Err(e) => e,
+            }
+        } else {
+            match tunnels::start("ssh", &dir, &app.config_file, alias) {
+                Ok(child) => {
+                    app.tunnel_children.push(child);
+                    format!("Started a tunnel for {}", alias)
+                }
+                Err(e) => format!("The tunnel of {} failed: {}", alias, e),
+            }
+        });
+        self.refresh();
+        AppState::Tunnels(self)
+    }
+}
+
+impl Keys {
+    fn handle_key(mut self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.action(&key, KEYS_ACTIONS) {
+            Some(Action::Cancel) | Some(Action::Confirm) => AppState::Main(Main),
+            Some(Action::AddToAgent) => self.add_to_agent(app),
+            Some(Action::Down) => {
+                self.state.select_next();
+                AppState::Keys(self)
+            }
+            Some(Action::Up) => {
+                self.state.select_previous();
+                AppState::Keys(self)
+            }
+            _ => AppState::Keys(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    /// Load the selected key in the agent, leaving keys with a passphrase
+    /// to ssh-add.
+    fn add_to_agent(self, app: &mut App) -> AppState {
+        let Some(key) = self.state.selected().and_then(|i| app.keys.get(i)) else {
+            return AppState::Keys(self);
+        };
+        let path = contract_tilde(&key.path);
+        if !key.private {
+            app.message = Some(format!("{} is a public key", path));
+            return AppState::Keys(self);
+        }
+
+        let lifetime = app.settings.agent.lifetime;
+        match agent::add_file(&key.path, lifetime) {
+            Ok(true) => {
+                app.agent = agent::list();
+                app.message = Some(format!("Added {} to the agent", path));
+            }
+            Ok(false) => app.launch = Some(agent::add_command(&key.path, lifetime)),
+            Err(e) => app.message = Some(format!("Unable to add {} to the agent: {}", path, e)),
+        }
+        AppState::Keys(self)
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let area = app.areas.pick_list;
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => {
+                self.state.select_next();
+                AppState::Keys(self)
+            }
+            MouseEventKind::ScrollUp if area.contains(pos) => {
+                self.state.select_previous();
+                AppState::Keys(self)
+            }
+            MouseEventKind::Down(MouseButton::Left) if app.is_click_outside_popup(mouse) => {
+                AppState::Main(Main)
+            }
+            _ => AppState::Keys(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+}
+
+impl Connect {
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.action(&key, CONNECT_ACTIONS) {
+            Some(Action::Cancel) => AppState::Main(Main),
+            Some(Action::Confirm) => self.launch(app),
+            Some(Action::Down) => self.select_next(),
+            Some(Action::Up) => self.select_previous(),
+            Some(Action::Toggle) => self.toggle_save(),
+            _ => AppState::Connect(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let area = app.areas.pick_list;
+        let len = app.settings.connect.commands.len();
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => self.select_next(),
+            MouseEventKind::ScrollUp if area.contains(pos) => self.select_previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = app.register_click(pos);
+                if app.is_click_outside_popup(mouse) {
+                    AppState::Main(Main)
+                } else if let Some(i) = row_at(area, pos, self.state.offset(), vec![1; len]) {
+                    self.state.select(Some(i));
+                    if double {
+                        self.launch(app)
+                    } else {
+                        AppState::Connect(self)
+                    }
+                } else {
+                    AppState::Connect(self)
+                }
+            }
+            _ => AppState::Connect(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn select_next(mut self) -> AppState {
+        self.state.select_next();
+        AppState::Connect(self)
+    }
+
+    fn select_previous(mut self) -> AppState {
+        self.state.select_previous();
+        AppState::Connect(self)
+    }
+
+    fn toggle_save(mut self) -> AppState {
+        self.save = self.modified && !self.save;
+        AppState::Connect(self)
+    }
+
+    /// Save if asked to, then leave the command line for [`App::run`] to
+    /// start once the terminal is released.
+    fn launch(self, app: &mut App) -> AppState {
+        let Some(template) = self
+            .state
+            .selected()
+            .and_then(|i| app.settings.connect.commands.get(i))
+        else {
+            return AppState::Connect(self);
+        };
+
+        let template = template.clone();
+        if self.save {
+            if let Err(e) = app.save() {
+                app.message = Some(format!("Unable to save: {}", e));
+                return AppState::Main(Main);
+            }
+        }
+        app.launch = Some(connect::command_line(
+            &template,
+            &self.alias,
+            &app.config_file,
+        ));
+        AppState::Main(Main)
+    }
+}
+
+impl Pick {
+    fn new(config_idx: usize) -> Self {
+        let mut textarea = TextArea::new(vec![]);
+        textarea.set_cursor_line_style(Style::default());
+        Pick {
+            config_idx,
+            textarea,
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.popup_action(&key, PICK_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(app),
+            Some(Action::Confirm) => self.state_next(app),
+            Some(Action::Down) => self.select_next(),
+            Some(Action::Up) => self.select_previous(),
+            Some(Action::ToggleHelp) => self.toggle_help(app),
+            _ => self.other_input(key),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(mut self, app: &mut App, mouse: MouseEvent) {
+        let pos = Position::new(mouse.column, mouse.row);
+        let len = self.matches().len();
+        let area = app.areas.pick_list;
+
+        let new_state = match mouse.kind {
+            MouseEventKind::ScrollDown if area.contains(pos) => self.select_next(),
+            MouseEventKind::ScrollUp if area.contains(pos) => self.select_previous(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = app.register_click(pos);
+                if app.is_click_outside_popup(mouse) {
+                    self.state_back(app)
+                } else if let Some(i) = row_at(area, pos, self.state.offset(), vec![1; len]) {
+                    self.state.select(Some(i));
+                    if double {
+                        self.state_next(app)
+                    } else {
+                        AppState::Pick(self)
+                    }
+                } else {
+                    AppState::Pick(self)
+                }
+            }
+            _ => AppState::Pick(self),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    /// Keys matching the current query, best match first. Deprecated keys
+    /// are not offered.
+    fn matches(&self) -> Vec<sshconfig::Key> {
+        let keys: Vec<sshconfig::Key> = ALL_KEYS
+            .iter()
+            .filter(|k| k.deprecation().is_none())
+            .copied()
+            .collect();
+        let query = &self.textarea.lines()[0];
+        fuzzy::filter(query, keys.iter().map(|k| k.str()))
+            .into_iter()
+            .map(|i| keys[i])
+            .collect()
+    }
+
+    fn state_back(self, app: &mut App) -> AppState {
+        let idx = self.config_idx;
+        if app.config_content_list.state.selected().is_none() {
+            app.config_content_list.state.select(Some(0));
+        }
+        AppState::Select(Select { idx })
+    }
+
+    fn state_next(self, app: &mut App) -> AppState {
+        let key = self
+            .state
+            .selected()
+            .and_then(|i| self.matches().get(i).copied());
+
+        if let Some(key) = key {
+            editor(app, self.config_idx, key)
+        } else {
+            AppState::Pick(self)
+        }
+    }
+
+    fn toggle_help(self, app: &mut App) -> AppState {
+        app.show_help = !app.show_help;
+        AppState::Pick(self)
+    }
+
+    fn select_next(mut self) -> AppState {
+        self.state.select_next();
+        AppState::Pick(self)
+    }
+
+    fn select_previous(mut self) -> AppState {
+        self.state.select_previous();
+        AppState::Pick(self)
+    }
+
+    fn other_input(mut self, key: KeyEvent) -> AppState {
+        if self.textarea.input(key) {
+            self.state.select(Some(0));
+        }
+        AppState::Pick(self)
+    }
+}
+
+impl Comment {
+    fn new(app: &App, config_idx: usize, from_select: bool, field: CommentField) -> Self {
+        let cfg = &app.config_list.items[config_idx];
+        let value = match field {
+            CommentField::Description => cfg.description().unwrap_or_default(),
+            CommentField::Tags => cfg.tags().join(","),
+        };
+        let mut textarea = TextArea::new(vec![value]);
+        textarea.set_cursor_line_style(Style::default());
+        textarea.move_cursor(CursorMove::End);
+        Comment {
+            config_idx,
+            textarea,
+            from_select,
+            field,
+        }
+    }
+
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.popup_action(&key, COMMENT_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(),
+            Some(Action::Confirm) => self.state_save(app),
+            _ => self.other_input(key),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = if app.is_click_outside_popup(mouse) {
+            self.state_back()
+        } else {
+            AppState::Comment(self)
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn state_back(self) -> AppState {
+        if self.from_select {
+            AppState::Select(Select {
+                idx: self.config_idx,
+            })
+        } else {
+            AppState::Main(Main)
+        }
+    }
+
+    fn state_save(self, app: &mut App) -> AppState {
+        let content = &self.textarea.lines()[0];
+        let cfg = &mut app.config_list.items[self.config_idx];
+        match self.field {
+            CommentField::Description => cfg.set_description(content),
+            CommentField::Tags => {
+                // The `Tag` keyword is edited from the Config pane.
+                let keyword = cfg.columns.get(&Key::Tag).cloned();
+                let tags: Vec<String> = split_tags(content)
+                    .into_iter()
+                    .filter(|t| Some(t) != keyword.as_ref())
+                    .collect();
+                cfg.set_tags(&tags);
+            }
+        }
+        app.config_changed();
+        self.state_back()
+    }
+
+    fn other_input(mut self, key: KeyEvent) -> AppState {
+        _ = self.textarea.input(key);
+        AppState::Comment(self)
+    }
+}
+
+impl Filter {
+    fn handle_key(self, app: &mut App, key: KeyEvent) {
+        let new_state = match app.settings.keys.popup_action(&key, FILTER_ACTIONS) {
+            Some(Action::Cancel) => self.state_back(),
+            Some(Action::Confirm) => self.state_save(app),
+            _ => self.other_input(key),
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn handle_mouse(self, app: &mut App, mouse: MouseEvent) {
+        let new_state = if app.is_click_outside_popup(mouse) {
+            self.state_back()
+        } else {
+            AppState::Filter(self)
+        };
+
+        app.current_state = Some(new_state);
+    }
+
+    fn state_back(self) -> AppState {
+        AppState::Main(Main)
+    }
+
+    fn state_save(self, app: &mut App) -> AppState {
+        let idx = app.config_list.selected();
+        app.config_list.filter = split_tags(&self.textarea.lines()[0]);
+        app.config_list.select_config(idx);
+        app.config_content_list.state = ListState::default();
+        self.state_back()
+    }
+
+    fn other_input(mut self, key: KeyEvent) -> AppState {
+        _ = self.textarea.input(key);
+        AppState::Filter(self)
+    }
+}
+
+/// Tags typed by the user, separated by commas or spaces.
+fn split_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in text.split([',', ' ']).filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+    tags
+}
+
+#[derive(Default)]
+struct ConfigList {
+    items: Vec<Config>,
+    state: ListState,
+    grouping: Grouping,
+    collapsed: HashSet<String>,
+    filter: Vec<String>,
+    /// Reachability of host aliases, `None` while the check runs.
+    reachability: HashMap<String, Option<Status>>,
+    /// Certificates the hosts use, read at startup.
+    certificates: Vec<certs::CertFile>,
+    /// ControlMaster socket state of host aliases with a `ControlPath`.
+    mux: HashMap<String, mux::Status>,
+    /// Risky settings of `items`, updated by [`App::config_changed`].
+    risks: Vec<audit::Risk>,
+    /// Lint findings of `items`, updated by [`App::config_changed`].
+    findings: Vec<lint::Finding>,
+}
+
+impl ConfigList {
+    fn rows(&self) -> Vec<Row> {
+        grouping::rows(&self.items, self.grouping, &self.collapsed, &self.filter)
+    }
+
+    /// Index in `items` of the host under the cursor, `None` when nothing or
+    /// a section header is selected.
+    fn selected(&self) -> Option<usize> {
+        match self.rows().get(self.state.selected()?)? {
+            Row::Host(i) => Some(*i),
+            Row::Group { .. } => None,
+        }
+    }
+
+    /// Rows of the list, with badges on hosts that have risky settings and
+    /// on checked hosts.
+    fn items(&self) -> Vec<ListItem<'static>> {
+        let indent = if self.grouping == Grouping::None {
+            ""
+        } else {
+            "  "
+        };
+        self.rows()
+            .into_iter()
+            .map(|row| match row {
+                Row::Group {
+                    name,
+                    count,
+                    collapsed,
+                } => {
+                    let marker = if collapsed { '▸' } else { '▾' };
+                    ListItem::new(Line::raw(format!("{} {} ({})", marker, name, count)).bold())
+                }
+                Row::Host(i) => {
+                    let cfg = &self.items[i];
+                    let mut host = Line::raw(format!("{}{}", indent, cfg.host));
+                    if audit::is_risky(&self.risks, i) {
+                        host.push_span(" ⚠ risky".red());
+                    }
+                    let status = cfg
+                        .host
+                        .split_whitespace()
+                        .find_map(|alias| self.reachability.get(alias));
+                    match status {
+                        Some(None) => host.push_span(" … checking".dim()),
+                        Some(Some(Status::Reachable { banner })) => host.push_span(
+                            format!(" ● {}", banner.as_deref().unwrap_or("reachable")).green(),
+                        ),
+                        Some(Some(Status::Proxied)) => host.push_span(" ↪ via jump host".dim()),
+                        Some(Some(status)) => host.push_span(format!(" ✗ {}", status).red()),
+                        None => {}
+                    }
+                    let master = cfg
+                        .host
+                        .split_whitespace()
+                        .find_map(|alias| self.mux.get(alias));
+                    match master {
+                        Some(mux::Status::Connected) => {
+                            host.push_span(" ⇄ connected (mux)".green())
+                        }
+                        Some(mux::Status::Stale) => host.push_span(" ⇄ stale socket".yellow()),
+                        Some(mux::Status::Down) | None => {}
+                    }
+                    if let Some(validity) = self.invalid_certificate(i) {
+                        host.push_span(format!(" ✗ certificate {}", validity).red());
+                    }
+                    let mut lines = vec![host];
+                    if let Some(description) = cfg.description() {
+                        lines.push(Line::raw(format!("{}{}", indent, description)).dim());
+                    }
+                    ListItem::new(lines)
+                }
+            })
+            .collect()
+    }
+
+    /// How the first certificate of the host at `idx` that is not valid now
+    /// falls outside its window.
+    fn invalid_certificate(&self, idx: usize) -> Option<certs::Validity> {
+        let alias = *topology::aliases(std::slice::from_ref(&self.items[idx])).first()?;
+        let now = certs::now();
+        certs::of(&self.certificates, &self.items, alias)
+            .into_iter()
+            .filter_map(|f| f.certificate.as_ref().ok())
+            .map(|c| c.validity(now))
+            .find(|v| !v.is_valid())
+    }
+
+    /// Move the cursor to the first row showing the host at `idx`, or to
+    /// the top when it is not shown.
+    fn select_config(&mut self, idx: Option<usize>) {
+        let row = idx.and_then(|idx| {
+            self.rows()
+                .iter()
+                .position(|r| matches!(r, Row::Host(i) if *i == idx))
+        });
+        if row.is_some() || self.state.selected().is_some() {
+            self.state.select(Some(row.unwrap_or(0)));
+        }
+    }
+}
+
+#[derive(Default)]
+struct ConfigContentList {
+    state: ListState,
+}
+
+/// Where things were drawn on the last frame, for mouse hit testing. Lists
+/// are stored without their border and padding so that rows start at `y`.
+#[derive(Default)]
+struct Areas {
+    list: Rect,
+    content: Rect,
+    popup: Rect,
+    pick_list: Rect,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            should_exit: false,
+            config_list: ConfigList::default(),
+            config_content_list: ConfigContentList::default(),
+            current_state: Some(AppState::Main(Main)),
+            show_all_keys: false,
+            show_help: false,
+            show_problems: false,
+            openssh_version: migrate::LATEST,
+            out_file: PathBuf::new(),
+            config_file: PathBuf::new(),
+            saved: String::new(),
+            launch: None,
+            new_key: None,
+            message: None,
+            checks: None,
+            known_hosts: vec![],
+            keys: vec![],
+            agent: Ok(vec![]),
+            tunnel_children: vec![],
+            settings: Settings::default(),
+            areas: Areas::default(),
+            last_click: None,
+        }
+    }
+}
+
+impl App {
+    pub fn with_config(cfg: Vec<Config>) -> Self {
+        Self {
+            config_list: ConfigList {
+                mux: mux::statuses(&cfg),
+                risks: audit::audit(&cfg),
+                findings: lint::lint(&cfg),
+                items: cfg,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn with_settings(self, settings: Settings) -> Self {
+        Self { settings, ..self }
+    }
+
+    /// Files the config was read from and is written to. What was read is
+    /// the reference for pending edits.
+    pub fn with_files(mut self, in_file: PathBuf, out_file: PathBuf) -> Self {
+        self.config_file = in_file;
+        self.out_file = out_file;
+        self.saved = self.formatted();
+        self
+    }
+
+    pub fn with_keys(self, keys: Vec<keys::KeyFile>) -> Self {
+        Self { keys, ..self }
+    }
+
+    pub fn with_agent(self, agent: Result<Vec<keys::PublicKey>, String>) -> Self {
+        Self { agent, ..self }
+    }
+
+    pub fn with_certificates(mut self, certificates: Vec<certs::CertFile>) -> Self {
+        self.config_list.certificates = certificates;
+        self
+    }
+
+    pub fn with_known_hosts(self, known_hosts: Vec<knownhosts::Entry>) -> Self {
+        Self {
+            known_hosts,
+            ..self
+        }
+    }
+
+    /// Migrate deprecated keys for OpenSSH `version`, prompting for it
+    /// right away when the config has any.
+    pub fn with_openssh_version(mut self, version: Version) -> Self {
+        self.openssh_version = version;
+        if let Some(migration) = Migration::new(&self) {
+            self.current_state = Some(AppState::Migration(migration));
+        }
+        self
+    }
+
+    /// The config as it would be written.
+    fn formatted(&self) -> String {
+        let mut formatted = vec![];
+        let written = save_config(
+            &mut BufWriter::new(&mut formatted),
+            &self.config_list.items,
+            &self.settings.format,
+        );
+        match written {
+            Ok(()) => String::from_utf8_lossy(&formatted).into_owned(),
+            Err(_) => String::new(),
+        }
+    }
+
+    fn is_modified(&self) -> bool {
+        self.formatted() != self.saved
+    }
+
+    /// Write the config to the out file, which ssh is pointed at from then
+    /// on.
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        let formatted = self.formatted();
+        std::fs::write(&self.out_file, &formatted)?;
+        self.saved = formatted;
+        self.config_file = self.out_file.clone();
+        Ok(())
+    }
+
+    /// Keys shown in the Config pane for the config at `cfg_idx`: only the
+    /// ones that are set, unless all keys were toggled on.
+    fn visible_keys(&self, cfg_idx: usize) -> Vec<sshconfig::Key> {
+        let cfg = &self.config_list.items[cfg_idx];
+        ALL_KEYS
+            .iter()
+            .filter(|k| {
+                cfg.columns.contains_key(k) || (self.show_all_keys && k.deprecation().is_none())
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Update what is derived from the config after an edit.
+    fn config_changed(&mut self) {
+        self.config_list.risks = audit::audit(&self.config_list.items);
+        self.config_list.findings = lint::lint(&self.config_list.items);
+    }
+
+    fn select_content_key(&mut self, cfg_idx: usize, key: Option<sshconfig::Key>) {
+        let pos = key.and_then(|k| self.visible_keys(cfg_idx).iter().position(|x| *x == k));
+        self.config_content_list
+            .state
+            .select(Some(pos.unwrap_or(0)));
+        self.clamp_content_selection(cfg_idx);
+    }
+
+    /// The key whose help is shown: the one under the cursor in the Config
+    /// pane, or the one being edited or picked.
+    fn help_key(&self) -> Option<sshconfig::Key> {
+        match self.current_state.as_ref()? {
+            AppState::Select(select) => {
+                let i = self.config_content_list.state.selected()?;
+                self.visible_keys(select.idx).get(i).copied()
+            }
+            AppState::Edit(edit) => Some(edit.key),
+            AppState::ForwardForm(form) => Some(form.key),
+            AppState::AlgorithmForm(form) => Some(form.key),
+            AppState::Migration(migration) => {
+                let i = migration.state.selected()?;
+                migration.changes.get(i).map(|(c, _)| c.key)
+            }
+            AppState::Pick(pick) => {
+                let i = pick.state.selected()?;
+                pick.matches().get(i).copied()
+            }
+            AppState::KnownHosts(..) => Some(Key::UserKnownHostsFile),
+            AppState::Keys(..) | AppState::KeygenForm(..) => Some(Key::IdentityFile),
+            AppState::Tunnels(..) => Some(Key::LocalForward),
+            AppState::Main(..)
+            | AppState::Connect(..)
+            | AppState::New(..)
+            | AppState::Comment(..)
+            | AppState::Filter(..) => None,
+        }
+    }
+
+    fn clamp_content_selection(&mut self, cfg_idx: usize) {
+        let len = self.visible_keys(cfg_idx).len();
+        if let Some(i) = self.config_content_list.state.selected() {
+            self.config_content_list
+                .state
+                .select(Some(i.min(len.saturating_sub(1))));
+        }
+    }
+
+    pub fn run(&mut self, mut terminal: Terminal<impl Backend>) -> io::Result<()> {
+        while !self.should_exit {
+            terminal.draw(|f| f.render_widget(&mut *self, f.size()))?;
+            // Redraw regularly while checks are running to show their results.
+            if self.checks.is_some() {
+                self.receive_checks();
+                if !event::poll(Duration::from_millis(100))? {
+                    continue;
+                }
+            }
+            match event::read()? {
+                Event::Key(key) => self.handle_key(key),
+                Event::Mouse(mouse) => self.handle_mouse(mouse),
+                _ => {}
+            };
+            if let Some(line) = self.launch.take() {
+                let succeeded = self.run_command(&line)?;
+                if let Some((idx, file)) = self.new_key.take().filter(|_| succeeded) {
+                    self.use_new_key(idx, &file);
+                }
+                // The command may have loaded keys, as ssh-add does, or
+                // started a master connection.
+                self.agent = agent::list();
+                self.config_list.mux = mux::statuses(&self.config_list.items);
+                terminal.clear()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the reachability checks that are done since the last call.
+    fn receive_checks(&mut self) {
+        let Some(checks) = &self.checks else {
+            return;
+        };
+        loop {
+            match checks.try_recv() {
+                Ok((alias, status)) => {
+                    self.config_list.reachability.insert(alias, Some(status));
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        self.checks = None;
+    }
+
+    /// Add the key ssh-keygen just created to the block at `idx`. The keys
+    /// the block already names stay, for the host to be reachable until the
+    /// new one is installed on it. `IdentitiesOnly` is only set when unset.
+    fn use_new_key(&mut self, idx: usize, file: &str) {
+        let cfg = &mut self.config_list.items[idx];
+        let mut added = vec![format!("IdentityFile {}", file)];
+        match cfg.columns.get_mut(&Key::IdentityFile) {
+            Some(v) => {
+                v.push('\n');
+                v.push_str(file);
+            }
+            None => {
+                cfg.columns.insert(Key::IdentityFile, file.to_owned());
+            }
+        }
+        if let hash_map::Entry::Vacant(only) = cfg.columns.entry(Key::IdentitiesOnly) {
+            only.insert("yes".to_owned());
+            added.push("IdentitiesOnly yes".to_owned());
+        }
+        self.message = Some(format!(
+            "Added {} to Host {}, install {}.pub on the server",
+            added.join(" and "),
+            cfg.host,
+            file
+        ));
+        if let Err(e) = keygen::fix_permissions(&expand_tilde(file)) {
+            self.message = Some(format!("Unable to set the permissions of {}: {}", file, e));
+        }
+        if let Ok(keys) = keys::inventory(&self.config_list.items) {
+            self.keys = keys;
+        }
+        self.config_changed();
+        self.select_content_key(idx, Some(Key::IdentityFile));
+    }
+
+    /// Hand the terminal over to `line` until it exits, telling whether it
+    /// succeeded.
+    fn run_command(&mut self, line: &[String]) -> io::Result<bool> {
+        tui::restore_terminal()?;
+        let status = connect::command(line).map(|mut c| c.status());
+        tui::resume_terminal(self.settings.ui.mouse)?;
+
+        let succeeded = matches!(&status, Some(Ok(status)) if status.success());
+        self.message = match status {
+            Some(Ok(status)) if status.success() => None,
+            Some(Ok(status)) => Some(format!("`{}` ended with {}", line.join(" "), status)),
+            Some(Err(e)) => Some(format!("Unable to run `{}`: {}", line[0], e)),
+            None => Some("The connect command is empty".to_owned()),
+        };
+        Ok(succeeded)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        self.message = None;
+
+        match self.current_state.take() {
+            Some(AppState::Main(main)) => main.handle_key(self, key),
+            Some(AppState::Select(select)) => select.handle_key(self, key),
+            Some(AppState::Edit(edit)) => edit.handle_key(self, key),
+            Some(AppState::New(new)) => new.handle_key(self, key),
+            Some(AppState::Comment(comment)) => comment.handle_key(self, key),
+            Some(AppState::Filter(filter)) => filter.handle_key(self, key),
+            Some(AppState::Pick(pick)) => pick.handle_key(self, key),
+            Some(AppState::ForwardForm(form)) => form.handle_key(self, key),
+            Some(AppState::AlgorithmForm(form)) => form.handle_key(self, key),
+            Some(AppState::Migration(migration)) => migration.handle_key(self, key),
+            Some(AppState::Connect(connect)) => connect.handle_key(self, key),
+            Some(AppState::KnownHosts(known_hosts)) => known_hosts.handle_key(self, key),
+            Some(AppState::Keys(keys)) => keys.handle_key(self, key),
+            Some(AppState::KeygenForm(form)) => form.handle_key(self, key),
+            Some(AppState::Tunnels(tunnels)) => tunnels.handle_key(self, key),
+            None => unreachable!(),
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match self.current_state.take() {
+            Some(AppState::Main(main)) => main.handle_mouse(self, mouse),
+            Some(AppState::Select(select)) => select.handle_mouse(self, mouse),
+            Some(AppState::Edit(edit)) => edit.handle_mouse(self, mouse),
+            Some(AppState::New(new)) => new.handle_mouse(self, mouse),
+            Some(AppState::Comment(comment)) => comment.handle_mouse(self, mouse),
+            Some(AppState::Filter(filter)) => filter.handle_mouse(self, mouse),
+            Some(AppState::Pick(pick)) => pick.handle_mouse(self, mouse),
+            Some(AppState::ForwardForm(form)) => form.handle_mouse(self, mouse),
+            Some(AppState::AlgorithmForm(form)) => form.handle_mouse(self, mouse),
+            Some(AppState::Migration(migration)) => migration.handle_mouse(self, mouse),
+            Some(AppState::Connect(connect)) => connect.handle_mouse(self, mouse),
+            Some(AppState::KnownHosts(known_hosts)) => known_hosts.handle_mouse(self, mouse),
+            Some(AppState::Keys(keys)) => keys.handle_mouse(self, mouse),
+            Some(AppState::KeygenForm(form)) => form.handle_mouse(self, mouse),
+            Some(AppState::Tunnels(tunnels)) => tunnels.handle_mouse(self, mouse),
+            None => unreachable!(),
+        }
+    }
+
+    /// Mouse handling shared by the host list and the Config pane. Returns
+    /// the new state, or `None` to stay in the current one.
+    fn pane_mouse(&mut self, mouse: MouseEvent) -> Option<AppState> {
+        let pos = Position::new(mouse.column, mouse.row);
+
+        match mouse.kind {
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let down = mouse.kind == MouseEventKind::ScrollDown;
+                if self.areas.list.contains(pos) {
+                    self.config_content_list.state = ListState::default();
+                    if down {
+                        self.config_list.state.select_next();
+                    } else {
+                        self.config_list.state.select_previous();
+                    }
+                    Some(AppState::Main(Main))
+                } else if self.areas.content.contains(pos) {
+                    if down {
+                        self.config_content_list.state.select_next();
+                    } else {
+                        self.config_content_list.state.select_previous();
+                    }
+                    None
+                } else {
+                    None
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let double = self.register_click(pos);
+                let heights: Vec<usize> = self
+                    .config_list
+                    .items()
+                    .iter()
+                    .map(|item| item.height())
+                    .collect();
+
+                if let Some(i) = row_at(
+                    self.areas.list,
+                    pos,
+                    self.config_list.state.offset(),
+                    heights,
+                ) {
+                    self.config_list.state.select(Some(i));
+                    self.config_content_list.state = ListState::default();
+                    if double {
+                        Some(Main.state_next(self))
+                    } else {
+                        Some(AppState::Main(Main))
+                    }
+                } else if self.areas.content.contains(pos) {
+                    let idx = self.config_list.selected()?;
+                    let keys = self.visible_keys(idx).len();
+                    let offset = self.config_content_list.state.offset();
+                    let i = row_at(self.areas.content, pos, offset, vec![1; keys])?;
+                    self.config_content_list.state.select(Some(i));
+
+                    let select = Select { idx };
+                    if double {
+                        Some(select.state_next(self))
+                    } else {
+                        Some(AppState::Select(select))
+                    }
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Record a left click and tell whether it completes a double-click.
+    fn register_click(&mut self, pos: Position) -> bool {
+        let now = Instant::now();
+        let double = matches!(
+            self.last_click,
+            Some((t, p)) if p == pos && now.duration_since(t) < DOUBLE_CLICK
+        );
+        self.last_click = if double { None } else { Some((now, pos)) };
+        double
+    }
+
+    fn is_click_outside_popup(&self, mouse: MouseEvent) -> bool {
+        let pos = Position::new(mouse.column, mouse.row);
+        mouse.kind == MouseEventKind::Down(MouseButton::Left) && !self.areas.popup.contains(pos)
+    }
+}
+
+impl Widget for &mut App {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let footer = self.message.clone().unwrap_or_else(|| self.footer_text());
+        let [header_area, main_area, footer_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Fill(1),
+            Constraint::Length(wrapped_height(&footer, area.width)),
+        ])
+        .areas(area);
+
+        self.areas = Areas::default();
+        App::render_header(header_area, buf);
+        let footer = match &self.message {
+            Some(message) => Line::raw(message.clone()).red(),
+            None => Line::raw(footer),
+        };
+        Paragraph::new(footer)
+            .centered()
+            .wrap(Wrap { trim: true })
+            .render(footer_area, buf);
+
+        if main_area.width < NARROW_WIDTH {
+            self.render_narrow(main_area, buf);
+        } else if self.show_help || self.show_problems || main_area.width >= WIDE_WIDTH {
+            let [index_area, selected_area, side_area] = Layout::horizontal([
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ])
+            .areas(main_area);
+            self.render_list(index_area, buf);
+            self.render_selected(selected_area, buf);
+            if self.show_help {
+                self.render_help(side_area, buf);
+            } else if self.show_problems {
+                self.render_problems(side_area, buf);
+            } else {
+                self.render_effective(side_area, buf);
+            }
+        } else {
+            let [index_area, selected_area] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(main_area);
+            self.render_list(index_area, buf);
+            self.render_selected(selected_area, buf);
+        }
+
+        self.render_new_textarea(buf);
+        self.render_comment_textarea(buf);
+        self.render_filter_textarea(buf);
+        self.render_edit_textarea(buf);
+        self.render_pick(buf);
+        self.render_forward_form(buf);
+        self.render_algorithm_form(buf);
+        self.render_migration(buf);
+        self.render_connect(buf);
+        self.render_known_hosts(buf);
+        self.render_keys(buf);
+        self.render_keygen_form(buf);
+        self.render_tunnels(buf);
+    }
+}
+
+/// Rendering logic for the app
+impl App {
+    /// Below [`NARROW_WIDTH`] only one pane fits: the host list while moving
+    /// through hosts, the Config pane once one is selected. Help goes under
+    /// the Config pane, problems under the host list.
+    fn render_narrow(&mut self, area: Rect, buf: &mut Buffer) {
+        let on_list = matches!(
+            self.current_state,
+            Some(AppState::Main(..) | AppState::New(..))
+        );
+        if on_list && self.show_problems {
+            let [list_area, problems_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+            self.render_list(list_area, buf);
+            self.render_problems(problems_area, buf);
+        } else if on_list {
+            self.render_list(area, buf);
+        } else if self.show_help {
+            let [selected_area, help_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+            self.render_selected(selected_area, buf);
+            self.render_help(help_area, buf);
+        } else {
+            self.render_selected(area, buf);
+        }
+    }
+
+    fn render_edit_textarea(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+
+        let (key, textarea, completion) = match &mut self.current_state {
+            Some(AppState::Edit(edit)) => (edit.key, &mut edit.textarea, &edit.completion),
+            _ => return,
+        };
+
+        let height = textarea.lines().len().max(1) as u16 + 2;
+        let area = popup_area(buf.area, text_popup_width(key.str(), textarea), height);
+        self.areas.popup = area;
+        let mut block = Block::bordered()
+            .title(key.str())
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        if let Some((i, n)) = completion.as_ref().map(Completion::position) {
+            block = block.title_bottom(Line::raw(format!(" {}/{} ", i, n)).right_aligned());
+        }
+        Clear.render(area, buf);
+        textarea.set_block(block);
+        textarea.widget().render(area, buf);
+    }
+
+    fn render_new_textarea(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+
+        let textarea = match &mut self.current_state {
+            Some(AppState::New(new)) => &mut new.textarea,
+            _ => return,
+        };
+
+        let area = popup_area(buf.area, text_popup_width("New config", textarea), 3);
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title("New config")
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        Clear.render(area, buf);
+        textarea.set_block(block);
+        textarea.widget().render(area, buf);
+    }
+
+    fn render_comment_textarea(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+
+        let (config_idx, field, textarea) = match &mut self.current_state {
+            Some(AppState::Comment(comment)) => {
+                (comment.config_idx, comment.field, &mut comment.textarea)
+            }
+            _ => return,
+        };
+
+        let host = &self.config_list.items[config_idx].host;
+        let title = match field {
+            CommentField::Description => format!("Comment for {}", host),
+            CommentField::Tags => format!("Tags for {}", host),
+        };
+        let area = popup_area(buf.area, text_popup_width(&title, textarea), 3);
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        Clear.render(area, buf);
+        textarea.set_block(block);
+        textarea.widget().render(area, buf);
+    }
+
+    fn render_filter_textarea(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+
+        let textarea = match &mut self.current_state {
+            Some(AppState::Filter(filter)) => &mut filter.textarea,
+            _ => return,
+        };
+
+        let title = "Filter by tags";
+        let area = popup_area(buf.area, text_popup_width(title, textarea), 3);
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        Clear.render(area, buf);
+        textarea.set_block(block);
+        textarea.widget().render(area, buf);
+    }
+
+    fn render_forward_form(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let configs = &self.config_list.items;
+
+        let form = match &mut self.current_state {
+            Some(AppState::ForwardForm(form)) => form,
+            _ => return,
+        };
+
+        let current = form.current_fields();
+        let mut lines: Vec<Line> = form
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, fields)| {
+                let fields = if i == form.entry { &current } else { fields };
+                let marker = if i == form.entry { "> " } else { "  " };
+                match Forward::from_fields(form.key, fields) {
+                    Ok(Some(forward)) => Line::raw(format!("{}{}", marker, forward)),
+                    Ok(None) => Line::raw(format!("{}(new forward)", marker)).dim(),
+                    Err(_) => Line::raw(format!("{}{}", marker, fields.join(" ").trim())).red(),
+                }
+            })
+            .collect();
+        lines.push(Line::raw(""));
+
+        let names = forward::field_names(form.key);
+        let label_width = names.iter().map(|x| x.len()).max().unwrap_or(0) + 2;
+        let fields_top = lines.len() as u16;
+        for (i, name) in names.iter().enumerate() {
+            let value = if i == form.field { "" } else { &current[i] };
+            let label = format!("{:width$}", format!("{}:", name), width = label_width);
+            lines.push(Line::from(vec![label.bold(), value.to_owned().into()]));
+        }
+        lines.push(Line::raw(""));
+
+        match Forward::from_fields(form.key, &current) {
+            Ok(Some(forward)) => {
+                for i in forward::conflicts(configs, form.config_idx, form.key, &forward) {
+                    let message = format!("⚠ Host {} binds the same local port", configs[i].host);
+                    lines.push(Line::raw(message).yellow());
+                }
+            }
+            Ok(None) => {}
+            Err(error) => lines.push(Line::raw(format!("✗ {}", error)).red()),
+        }
+
+        let title = format!("{} for {}", form.key.str(), configs[form.config_idx].host);
+        let area = popup_area(buf.area, 70, lines.len() as u16 + 2);
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+
+        let field_area = Rect::new(
+            inner.x + label_width as u16,
+            inner.y + fields_top + form.field as u16,
+            inner.width.saturating_sub(label_width as u16),
+            1,
+        )
+        .intersection(inner);
+        form.textarea.widget().render(field_area, buf);
+    }
+
+    fn render_algorithm_form(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let configs = &self.config_list.items;
+
+        let form = match &mut self.current_state {
+            Some(AppState::AlgorithmForm(form)) => form,
+            _ => return,
+        };
+
+        let items: Vec<ListItem> = form
+            .items
+            .iter()
+            .map(|(name, on)| {
+                let check = if *on { "[x] " } else { "[ ] " };
+                let mut line = Line::raw(format!("{}{}", check, name));
+                if algorithms::is_weak(name) {
+                    line.push_span(" weak".red());
+                } else if !algorithms::is_known(form.key, name) {
+                    line.push_span(" unknown".yellow());
+                }
+                if !on {
+                    line = line.dim();
+                }
+                ListItem::new(line)
+            })
+            .collect();
 
-        let area = Rect::new(s_x, s_y, width, height);
+        let enabled = form.enabled();
+        let summary = if enabled.is_empty() {
+            Line::raw("✗ enable at least one algorithm").red()
+        } else {
+            match algorithms::compact(form.key, &enabled) {
+                Some(value) => Line::raw(format!("{} {}", form.key.str(), value)),
+                None => Line::raw("OpenSSH defaults, the key is removed").dim(),
+            }
+        };
+
+        let title = format!("{} for {}", form.key.str(), configs[form.config_idx].host);
+        let height = items.len() as u16 + 5;
+        let area = popup_area(buf.area, 70, height.min(buf.area.height.saturating_sub(4)));
+        self.areas.popup = area;
         let block = Block::bordered()
-            .title(key.str())
-            .border_set(border_style)
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
             .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
         Clear.render(area, buf);
-        textarea.set_block(block);
-        textarea.widget().render(area, buf);
+        block.render(area, buf);
+
+        let [list_area, summary_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]).areas(inner);
+        self.areas.pick_list = list_area;
+        let list = List::new(items)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, list_area, buf, &mut form.state);
+
+        Paragraph::new(vec![Line::raw(""), summary])
+            .wrap(Wrap { trim: true })
+            .render(summary_area, buf);
     }
 
-    fn render_new_textarea(&mut self, buf: &mut Buffer) {
-        let border_style = match self.current_state {
-            Some(AppState::New(..)) => symbols::border::ROUNDED,
-            _ => symbols::border::PLAIN,
+    fn render_migration(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let configs = &self.config_list.items;
+
+        let migration = match &mut self.current_state {
+            Some(AppState::Migration(migration)) => migration,
+            _ => return,
         };
 
-        let textarea = match &mut self.current_state {
-            Some(AppState::New(new)) => &mut new.textarea,
+        let items: Vec<ListItem> = migration
+            .changes
+            .iter()
+            .map(|(change, on)| {
+                let check = if *on { "[x] " } else { "[ ] " };
+                let host = &configs[change.config_idx].host;
+                let mut lines = vec![Line::raw(format!("{}Host {}", check, host))];
+                for line in change.diff(configs) {
+                    let added = line.starts_with('+');
+                    let line = Line::raw(format!("    {}", line));
+                    lines.push(if added { line.green() } else { line.red() });
+                }
+                lines.push(Line::raw(format!("    {}", change.explanation)).dim());
+                if !on {
+                    lines = lines.into_iter().map(|l| l.dim()).collect();
+                }
+                ListItem::new(lines)
+            })
+            .collect();
+
+        let (major, minor) = self.openssh_version;
+        let title = format!("Migrate to OpenSSH {}.{}", major, minor);
+        let height: usize = items.iter().map(|i| i.height()).sum();
+        let area = popup_area(
+            buf.area,
+            80,
+            (height as u16 + 2).min(buf.area.height.saturating_sub(4)),
+        );
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        self.areas.pick_list = block.inner(area);
+        Clear.render(area, buf);
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut migration.state);
+    }
+
+    fn render_connect(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let connect = match &mut self.current_state {
+            Some(AppState::Connect(connect)) => connect,
             _ => return,
         };
 
-        let pref_width = 30;
-        let pref_height = 3;
-        let width = std::cmp::min(buf.area.width, pref_width);
-        let height = std::cmp::min(buf.area.height, pref_height);
+        // Saving first points the connection at the out file.
+        let file = if connect.save {
+            &self.out_file
+        } else {
+            &self.config_file
+        };
+        let items: Vec<ListItem> = self
+            .settings
+            .connect
+            .commands
+            .iter()
+            .map(|template| {
+                let line = connect::command_line(template, &connect.alias, file);
+                ListItem::new(line.join(" "))
+            })
+            .collect();
+
+        let save = if !connect.modified {
+            Line::raw("No pending edits").dim()
+        } else if connect.save {
+            Line::raw(format!("[x] save to {} first", self.out_file.display()))
+        } else {
+            Line::raw("[ ] save first, the connection will not see the pending edits").yellow()
+        };
+
+        let height = items.len() as u16 + 4;
+        let area = popup_area(buf.area, 80, height.min(buf.area.height.saturating_sub(4)));
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title(format!("Connect to {}", connect.alias))
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        Clear.render(area, buf);
+        block.render(area, buf);
+
+        let [list_area, save_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(2)]).areas(inner);
+        self.areas.pick_list = list_area;
+        let list = List::new(items)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, list_area, buf, &mut connect.state);
+
+        Paragraph::new(vec![Line::raw(""), save]).render(save_area, buf);
+    }
+
+    fn render_known_hosts(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let known_hosts = match &mut self.current_state {
+            Some(AppState::KnownHosts(known_hosts)) => known_hosts,
+            _ => return,
+        };
 
-        let s_x = (buf.area.width - width) / 2;
-        let s_y = (buf.area.height - height) / 2;
+        let items: Vec<ListItem> = known_hosts
+            .entries
+            .iter()
+            .map(|(entry, on)| {
+                let check = if *on { "[x] " } else { "[ ] " };
+                let line = Line::raw(format!("{}{}  {}", check, entry.location(), entry));
+                match entry.marker {
+                    _ if *on => ListItem::new(line.red()),
+                    Some(knownhosts::Marker::Revoked) => ListItem::new(line.yellow()),
+                    _ => ListItem::new(line),
+                }
+            })
+            .collect();
 
-        let area = Rect::new(s_x, s_y, width, height);
+        let (title, empty) = if known_hosts.orphans {
+            (
+                "Orphaned known_hosts lines".to_owned(),
+                "Every line is used by a host of the config",
+            )
+        } else {
+            (
+                format!("Known hosts of {}", known_hosts.alias),
+                "No known_hosts line for this host",
+            )
+        };
+        let height = items.len().max(1) as u16 + 2;
+        let area = popup_area(buf.area, 80, height.min(buf.area.height.saturating_sub(4)));
+        self.areas.popup = area;
         let block = Block::bordered()
-            .title("New config")
-            .border_set(border_style)
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
             .padding(Padding::horizontal(1));
+        self.areas.pick_list = block.inner(area);
         Clear.render(area, buf);
-        textarea.set_block(block);
-        textarea.widget().render(area, buf);
+
+        if items.is_empty() {
+            Paragraph::new(empty).dim().block(block).render(area, buf);
+            return;
+        }
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut known_hosts.state);
+    }
+
+    fn render_keygen_form(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let form = match &mut self.current_state {
+            Some(AppState::KeygenForm(form)) => form,
+            _ => return,
+        };
+
+        let label_width = 10;
+        let mut types = vec![format!("{:label_width$}", "Type:").bold()];
+        for key_type in KeyType::ALL {
+            let name = format!("{} ", key_type.name());
+            types.push(if *key_type == form.key_type {
+                name.reversed()
+            } else {
+                name.dim()
+            });
+        }
+        let values = form.current_values();
+        let mut lines = vec![Line::from(types)];
+        for (i, name) in ["File:", "Comment:"].iter().enumerate() {
+            let value = if form.field == i + 1 { "" } else { &values[i] };
+            lines.push(Line::from(vec![
+                format!("{:label_width$}", name).bold(),
+                value.to_owned().into(),
+            ]));
+        }
+        lines.push(Line::raw(""));
+        lines.push(Line::raw("ssh-keygen asks for the passphrase next").dim());
+        if let Some(error) = &form.error {
+            lines.push(Line::raw(format!("✗ {}", error)).red());
+        }
+
+        let title = format!("New key for {}", form.alias);
+        let area = popup_area(buf.area, 70, lines.len() as u16 + 2);
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title(title)
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        let inner = block.inner(area);
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block)
+            .render(area, buf);
+
+        if form.field > 0 {
+            let field_area = Rect::new(
+                inner.x + label_width as u16,
+                inner.y + form.field as u16,
+                inner.width.saturating_sub(label_width as u16),
+                1,
+            )
+            .intersection(inner);
+            form.textarea.widget().render(field_area, buf);
+        }
+    }
+
+    fn render_tunnels(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let tunnels_state = match &mut self.current_state {
+            Some(AppState::Tunnels(tunnels)) => tunnels,
+            _ => return,
+        };
+
+        let configs = &self.config_list.items;
+        let items: Vec<ListItem> = tunnels_state
+            .hosts
+            .iter()
+            .map(|alias| {
+                let mut host = Line::raw(alias.clone());
+                match tunnels_state.running.iter().find(|t| t.alias == *alias) {
+                    Some(tunnel) => {
+                        host.push_span(format!("  ● running, pid {}", tunnel.pid).green())
+                    }
+                    None => host.push_span("  stopped".dim()),
+                }
+                let ports = tunnels::ports(configs, alias);
+                let summary = tunnels::port_summary(&ports, &tunnels_state.listening);
+                ListItem::new(vec![host, Line::raw(format!("  {}", summary)).dim()])
+            })
+            .collect();
+
+        let height = (items.len() * 2).max(1) as u16 + 2;
+        let area = popup_area(buf.area, 70, height.min(buf.area.height.saturating_sub(4)));
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title("Tunnels")
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        self.areas.pick_list = block.inner(area);
+        Clear.render(area, buf);
+
+        if items.is_empty() {
+            Paragraph::new("No host has a LocalForward or DynamicForward")
+                .dim()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut tunnels_state.state);
+    }
+
+    fn render_keys(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let keys_state = match &mut self.current_state {
+            Some(AppState::Keys(keys)) => keys,
+            _ => return,
+        };
+
+        // Unused keys and missing references are what to act on.
+        let configs = &self.config_list.items;
+        let references = keys::references(configs);
+        let mut items: Vec<ListItem> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let users = keys::users(&references, key);
+                let mut path = Line::raw(contract_tilde(&key.path));
+                match keys::usage(key, &users, configs) {
+                    Some(usage) => path.push_span(format!("  {}", usage).dim()),
+                    None => {
+                        path.push_span("  unused");
+                        path = path.yellow();
+                    }
+                }
+                if self.agent.as_ref().is_ok_and(|a| agent::is_loaded(a, key)) {
+                    path.push_span("  ● in agent".green());
+                }
+                let summary = keys::summary(key);
+                ListItem::new(vec![path, Line::raw(format!("  {}", summary)).dim()])
+            })
+            .collect();
+        items.extend(references.iter().filter(|r| r.is_missing()).map(|r| {
+            ListItem::new(vec![
+                Line::raw(format!("✗ {}", contract_tilde(&r.path))).red(),
+                Line::raw(format!(
+                    "  missing, IdentityFile of Host {}",
+                    configs[r.config_idx].host
+                ))
+                .dim(),
+            ])
+        }));
+
+        let height = (items.len() * 2).max(1) as u16 + 2;
+        let area = popup_area(buf.area, 80, height.min(buf.area.height.saturating_sub(4)));
+        self.areas.popup = area;
+        let block = Block::bordered()
+            .title("Keys")
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        self.areas.pick_list = block.inner(area);
+        Clear.render(area, buf);
+
+        if items.is_empty() {
+            Paragraph::new("No key in ~/.ssh")
+                .dim()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+        StatefulWidget::render(list, area, buf, &mut keys_state.state);
+    }
+
+    fn render_pick(&mut self, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let pick = match &mut self.current_state {
+            Some(AppState::Pick(pick)) => pick,
+            _ => return,
+        };
+
+        let area = popup_area(buf.area, 80, 20);
+        let [input_area, list_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(area);
+        self.areas.popup = area;
+        Clear.render(area, buf);
+
+        let block = Block::bordered()
+            .title("Add key")
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style())
+            .padding(Padding::horizontal(1));
+        pick.textarea.set_block(block);
+        pick.textarea.widget().render(input_area, buf);
+
+        let cfg = &self.config_list.items[pick.config_idx];
+        let items: Vec<ListItem> = pick
+            .matches()
+            .into_iter()
+            .map(|k| {
+                let mark = if cfg.columns.contains_key(&k) {
+                    "*"
+                } else {
+                    " "
+                };
+                ListItem::new(format!("{} {:<32} {}", mark, k.str(), k.description()))
+            })
+            .collect();
+
+        let block = Block::bordered()
+            .border_set(theme.popup_border.set())
+            .border_style(theme.border_style());
+        self.areas.pick_list = block.inner(list_area);
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected_style())
+            .highlight_symbol("> ")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        StatefulWidget::render(list, list_area, buf, &mut pick.state);
+    }
+
+    /// Findings of [`lint::lint`], the selected host's first.
+    fn render_problems(&self, area: Rect, buf: &mut Buffer) {
+        let configs = &self.config_list.items;
+        let selected = self.config_list.selected();
+        let (mut findings, others): (Vec<&lint::Finding>, Vec<&lint::Finding>) = self
+            .config_list
+            .findings
+            .iter()
+            .partition(|f| Some(f.config_idx) == selected);
+        findings.extend(others);
+
+        let block = Block::bordered()
+            .title(Line::raw(format!(" Problems ({}) ", findings.len())).centered())
+            .borders(Borders::ALL)
+            .border_set(self.settings.theme.pane_border(false))
+            .border_style(self.settings.theme.border_style())
+            .padding(Padding::uniform(1));
+
+        if findings.is_empty() {
+            Paragraph::new("No problems found")
+                .centered()
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+
+        let mut lines = vec![];
+        for f in findings.iter() {
+            let severity = match f.rule.severity() {
+                lint::Severity::Error => format!("✗ {}", f.rule.id()).red(),
+                lint::Severity::Warning => format!("⚠ {}", f.rule.id()).yellow(),
+            };
+            let mut heading =
+                Line::from(vec![severity, format!("  {}", f.location(configs)).into()]);
+            let mut message = Line::raw(format!("  {}", f.message));
+            if Some(f.config_idx) != selected {
+                heading = heading.dim();
+                message = message.dim();
+            }
+            lines.extend([heading, message]);
+        }
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block)
+            .render(area, buf);
+    }
+
+    fn render_help(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(Line::raw(" Help ").centered())
+            .borders(Borders::ALL)
+            .border_set(self.settings.theme.pane_border(false))
+            .border_style(self.settings.theme.border_style())
+            .padding(Padding::uniform(1));
+
+        let Some(key) = self.help_key() else {
+            Paragraph::new("Select a key to see its description")
+                .centered()
+                .wrap(Wrap { trim: true })
+                .block(block)
+                .render(area, buf);
+            return;
+        };
+
+        let values = match key.value_kind() {
+            ValueKind::Enum(values) => values.join(", "),
+            ValueKind::Path => "a file path".to_owned(),
+            ValueKind::Host => "a host name".to_owned(),
+            ValueKind::Text => "free text".to_owned(),
+            ValueKind::Forward => "[bind_address:]port, then host:hostport or a socket".to_owned(),
+            ValueKind::Algorithms => {
+                "a comma list, or +, - or ^ and a list to change the defaults".to_owned()
+            }
+        };
+        let related = key
+            .related()
+            .iter()
+            .map(|k| k.str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut lines = vec![
+            Line::raw(key.str()).bold(),
+            Line::raw(key.description()),
+            Line::raw(""),
+            Line::from(vec!["Values: ".bold(), values.into()]),
+        ];
+        if let Some(default) = key.default_value() {
+            lines.push(Line::from(vec!["Default: ".bold(), default.into()]));
+        }
+        if !related.is_empty() {
+            lines.push(Line::from(vec!["Related: ".bold(), related.into()]));
+        }
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(block)
+            .render(area, buf);
+    }
+
+    /// The values ssh would end up using for the selected host, including
+    /// the ones inherited from wildcard blocks further down.
+    fn render_effective(&self, area: Rect, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let block = Block::bordered()
+            .title(Line::raw(" Effective config ").centered())
+            .borders(Borders::ALL)
+            .border_set(theme.pane_border(false))
+            .border_style(theme.border_style())
+            .padding(Padding::uniform(1));
+
+        let Some(idx) = self.config_list.selected() else {
+            Paragraph::new("Nothing selected")
+                .centered()
+                .block(block)
+                .render(area, buf);
+            return;
+        };
+
+        let configs = &self.config_list.items;
+        let alias = configs[idx]
+            .host
+            .split_whitespace()
+            .find(|h| !h.contains(['*', '?', '!']));
+        let Some(alias) = alias else {
+            Paragraph::new("Pattern block, no single host to resolve")
+                .centered()
+                .wrap(Wrap { trim: true })
+                .block(block)
+                .render(area, buf);
+            return;
+        };
+
+        let items: Vec<ListItem> = sshconfig::effective(configs, alias)
+            .into_iter()
+            .map(|(k, v, from)| {
+                let line = format!("{}: {}", k.str(), v);
+                if from == idx {
+                    ListItem::new(line)
+                } else {
+                    let source = format!("  (Host {})", configs[from].host);
+                    ListItem::new(Line::from(vec![line.dim(), source.dim().italic()]))
+                }
+            })
+            .collect();
+
+        Widget::render(List::new(items).block(block), area, buf);
     }
 
     fn render_header(area: Rect, buf: &mut Buffer) {
         Paragraph::new("SSHE").bold().centered().render(area, buf);
     }
 
-    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
-        let text = match self.current_state.as_ref().unwrap() {
-            AppState::Main(..) => "<arrow> move, d delete, n new, q write and quit, <right> select",
-            AppState::Select(..) => {
-                "<arrow> move, d delete, n new, q write and quit, <right> select, <left> back"
-            }
-            AppState::Edit(..) => "<esc> back, <enter> save",
-            AppState::New(..) => "<esc> back, <enter> save",
+    fn footer_text(&self) -> String {
+        let (prefix, hints, popup) = match self.current_state.as_ref().unwrap() {
+            AppState::Main(..) => ("", MAIN_HINTS, false),
+            AppState::Select(..) => ("", SELECT_HINTS, false),
+            AppState::Edit(..) => ("", EDIT_HINTS, true),
+            AppState::New(..) => ("", NEW_HINTS, true),
+            AppState::Comment(..) => ("", COMMENT_HINTS, true),
+            AppState::Filter(..) => ("tags separated by commas, ", FILTER_HINTS, true),
+            AppState::Pick(..) => ("type to search, ", PICK_HINTS, true),
+            AppState::ForwardForm(..) => ("clear a forward to remove it, ", FORWARD_HINTS, true),
+            AppState::AlgorithmForm(..) => ("", ALGORITHM_HINTS, false),
+            AppState::Migration(..) => ("deprecated keys found, ", MIGRATION_HINTS, false),
+            AppState::Connect(..) => ("", CONNECT_HINTS, false),
+            AppState::KnownHosts(..) => ("", KNOWN_HOSTS_HINTS, false),
+            AppState::Keys(..) => ("", KEYS_HINTS, false),
+            AppState::Tunnels(..) => ("", TUNNELS_HINTS, false),
+            AppState::KeygenForm(..) => ("", KEYGEN_HINTS, true),
         };
-        Paragraph::new(text).centered().render(area, buf);
+        let keys = &self.settings.keys;
+        let text = hints
+            .iter()
+            .map(|(actions, label)| format!("{} {}", keys.hint(actions, popup), label))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{}{}", prefix, text)
     }
 
     fn render_selected(&mut self, area: Rect, buf: &mut Buffer) {
-        let border_style = match self.current_state {
-            Some(AppState::Select(..)) => symbols::border::THICK,
-            _ => symbols::border::PLAIN,
-        };
+        // Details about the route and the key under the cursor go in boxes
+        // at the bottom, tall enough for their wrapped lines.
+        let mut area = area;
+        for (title, lines) in [
+            (" Risk ", self.risk_lines()),
+            (" Route ", self.route_lines()),
+            (" Known hosts ", self.known_hosts_lines()),
+            (" Multiplexing ", self.mux_lines()),
+            (" Agent ", self.agent_lines()),
+            (" Certificates ", self.certificate_lines()),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            let width = area.width.saturating_sub(4);
+            let height = lines
+                .iter()
+                .map(|l| wrapped_height(&l.to_string(), width))
+                .sum::<u16>()
+                + 4;
+            let [selected_area, box_area] = Layout::vertical([
+                Constraint::Fill(1),
+                Constraint::Length(height.min(area.height / 2)),
+            ])
+            .areas(area);
+            self.render_side_box(title, lines, box_area, buf);
+            area = selected_area;
+        }
+
+        let theme = &self.settings.theme;
+        let focused = matches!(self.current_state, Some(AppState::Select(..)));
 
+        let title = if self.show_all_keys {
+            " Config (all keys) "
+        } else {
+            " Config "
+        };
         let block = Block::bordered()
-            .title(Line::raw(" Config ").centered())
+            .title(Line::raw(title).centered())
             .borders(Borders::ALL)
-            .border_set(border_style)
+            .border_set(theme.pane_border(focused))
+            .border_style(theme.border_style())
             .padding(Padding::uniform(1));
+        self.areas.content = block.inner(area);
 
-        if let Some(i) = self.config_list.state.selected() {
+        if let Some(i) = self.config_list.selected() {
             let config = &self.config_list.items[i];
+            let risks = &self.config_list.risks;
             let mut items = vec![];
 
-            ALL_KEYS.iter().for_each(|k| {
+            self.visible_keys(i).iter().for_each(|k| {
                 let key = k.str();
                 let value = config
                     .columns
                     .get(k)
-                    .map(|x| x.to_string())
+                    .map(|x| x.lines().collect::<Vec<_>>().join(", "))
                     .unwrap_or("<None>".to_owned());
 
-                items.push(ListItem::new(format!("{}: {}", key, value)));
+                // Flag forwards whose local port another host binds too,
+                // algorithm lists enabling weak or unknown algorithms and
+                // identity files that do not exist.
+                let clashes = forward::forwards(config, *k)
+                    .iter()
+                    .any(|f| !forward::conflicts(&self.config_list.items, i, *k, f).is_empty());
+                let weak = config.columns.get(k).is_some_and(|v| {
+                    algorithms::effective(*k, Some(v))
+                        .iter()
+                        .any(|x| algorithms::is_weak(x) || !algorithms::is_known(*k, x))
+                });
+                let missing = *k == Key::IdentityFile
+                    && keys::references(std::slice::from_ref(config))
+                        .iter()
+                        .any(|r| r.is_missing());
+                let risky = risks.iter().any(|r| r.config_idx == i && r.key == *k);
+                let line = if risky {
+                    Line::from(vec![
+                        format!("{} ⚠", key).red(),
+                        format!(": {}", value).into(),
+                    ])
+                } else if clashes || weak || missing {
+                    Line::from(vec![
+                        format!("{} ⚠", key).yellow(),
+                        format!(": {}", value).into(),
+                    ])
+                } else {
+                    Line::raw(format!("{}: {}", key, value))
+                };
+                items.push(ListItem::new(line));
             });
 
             let list = List::new(items)
                 .block(block)
-                .highlight_style(SELECTED_STYLE)
+                .highlight_style(theme.selected_style())
                 .highlight_symbol("> ")
                 .highlight_spacing(HighlightSpacing::Always);
 
@@ -469,25 +3547,243 @@ impl App {
         };
     }
 
-    fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
-        let border_style = match self.current_state {
-            Some(AppState::Main(..)) => symbols::border::THICK,
-            _ => symbols::border::PLAIN,
+    /// The jump hosts crossed to reach the selected host as a staircase,
+    /// followed by the problems found on the way. Empty for direct hosts.
+    fn route_lines(&self) -> Vec<Line<'static>> {
+        let Some(idx) = self.config_list.selected() else {
+            return vec![];
+        };
+        let configs = &self.config_list.items;
+        let Some(alias) = configs[idx]
+            .host
+            .split_whitespace()
+            .find(|h| !h.contains(['*', '?', '!']))
+        else {
+            return vec![];
+        };
+
+        let route = topology::route(configs, alias);
+        if route.hops.is_empty() && route.problems.is_empty() {
+            return vec![];
+        }
+
+        let mut lines = vec![Line::raw("localhost").dim()];
+        let names = route
+            .hops
+            .iter()
+            .map(|h| h.to_string())
+            .chain([alias.to_owned()]);
+        for (depth, name) in names.enumerate() {
+            let indent = " ".repeat(depth * 4);
+            lines.push(Line::raw(format!("{}└─▶ {}", indent, name)));
+        }
+        for problem in route.problems {
+            lines.push(Line::raw(format!("✗ {}", problem)).red());
+        }
+        lines
+    }
+
+    /// The known_hosts lines ssh checks the key of the selected host
+    /// against. Empty when there are none.
+    fn known_hosts_lines(&self) -> Vec<Line<'static>> {
+        let Some(idx) = self.config_list.selected() else {
+            return vec![];
+        };
+        let configs = &self.config_list.items;
+        let Some(alias) = topology::aliases(std::slice::from_ref(&configs[idx]))
+            .first()
+            .copied()
+        else {
+            return vec![];
+        };
+        knownhosts::matching(&self.known_hosts, configs, alias)
+            .into_iter()
+            .map(|entry| {
+                let line = Line::raw(format!("{}  {}", entry.location(), entry));
+                match entry.marker {
+                    Some(knownhosts::Marker::Revoked) => line.yellow(),
+                    _ => line,
+                }
+            })
+            .collect()
+    }
+
+    /// The expanded `ControlPath` of the selected host and whether a master
+    /// listens on it. Empty when it has none.
+    fn mux_lines(&self) -> Vec<Line<'static>> {
+        let Some(idx) = self.config_list.selected() else {
+            return vec![];
+        };
+        let configs = &self.config_list.items;
+        let Some(alias) = topology::aliases(std::slice::from_ref(&configs[idx]))
+            .first()
+            .copied()
+        else {
+            return vec![];
+        };
+        let Some(path) = mux::control_path(configs, alias) else {
+            return vec![];
+        };
+        let status = self.config_list.mux.get(alias).copied();
+        let line = Line::raw(format!(
+            "{}  {}",
+            contract_tilde(&path),
+            status.unwrap_or(mux::Status::Down)
+        ));
+        vec![match status {
+            Some(mux::Status::Connected) => line.green(),
+            Some(mux::Status::Stale) => line.yellow(),
+            _ => line.dim(),
+        }]
+    }
+
+    /// Whether the keys the selected host names with `IdentityFile` are
+    /// loaded in ssh-agent. Empty when it names none.
+    fn agent_lines(&self) -> Vec<Line<'static>> {
+        let Some(idx) = self.config_list.selected() else {
+            return vec![];
+        };
+        let config = &self.config_list.items[idx];
+        let references = keys::references(std::slice::from_ref(config));
+        if references.is_empty() {
+            return vec![];
+        }
+        let identities = match &self.agent {
+            Ok(identities) => identities,
+            Err(e) => return vec![Line::raw(e.clone()).dim()],
+        };
+
+        references
+            .iter()
+            .filter(|r| !r.is_missing())
+            .map(|r| {
+                let path = contract_tilde(&r.path);
+                match keys::find(&self.keys, &r.path) {
+                    Some(key) if agent::is_loaded(identities, key) => {
+                        Line::raw(format!("● {}  loaded", path)).green()
+                    }
+                    Some(key) if key.public.is_some() => {
+                        Line::raw(format!("○ {}  not loaded", path)).yellow()
+                    }
+                    _ => Line::raw(format!("? {}  unknown key type", path)).dim(),
+                }
+            })
+            .collect()
+    }
+
+    /// The certificates of the selected host with their details, and what
+    /// keeps them from working. Empty when it has none.
+    fn certificate_lines(&self) -> Vec<Line<'static>> {
+        let Some(idx) = self.config_list.selected() else {
+            return vec![];
+        };
+        let configs = &self.config_list.items;
+        let Some(alias) = topology::aliases(std::slice::from_ref(&configs[idx]))
+            .first()
+            .copied()
+        else {
+            return vec![];
+        };
+
+        let now = certs::now();
+        let mut lines = vec![];
+        for file in certs::of(&self.config_list.certificates, configs, alias) {
+            lines.push(Line::raw(contract_tilde(&file.path)).bold());
+            match &file.certificate {
+                Ok(cert) => {
+                    lines.extend(certs::details(cert, now).into_iter().map(Line::raw));
+                    let user = certs::user(configs, alias);
+                    for problem in certs::problems(cert, &user, now) {
+                        lines.push(Line::raw(format!("✗ {}", problem)).red());
+                    }
+                }
+                Err(e) => lines.push(Line::raw(format!("✗ unreadable: {}", e)).red()),
+            }
+        }
+        lines
+    }
+
+    /// What is risky about the key under the cursor and what `UseSafer`
+    /// would change. Empty when it is fine or no key is focused.
+    fn risk_lines(&self) -> Vec<Line<'static>> {
+        let Some(AppState::Select(select)) = &self.current_state else {
+            return vec![];
         };
+        let Some(risk) = self.risk_at_cursor(select.idx) else {
+            return vec![];
+        };
+        let keys = &self.settings.keys;
+        vec![
+            Line::raw(format!("⚠ {}", risk.explanation)).red(),
+            Line::raw(format!(
+                "{} to {}",
+                keys.hint(&[Action::UseSafer], false),
+                risk.fix()
+            )),
+        ]
+    }
+
+    /// The risk found on the key under the cursor of the Config pane,
+    /// showing the host at `config_idx`.
+    fn risk_at_cursor(&self, config_idx: usize) -> Option<audit::Risk> {
+        let key = *self
+            .visible_keys(config_idx)
+            .get(self.config_content_list.state.selected()?)?;
+        self.config_list
+            .risks
+            .iter()
+            .find(|r| r.config_idx == config_idx && r.key == key)
+            .cloned()
+    }
+
+    /// A box under the Config pane, for the route and risk details.
+    fn render_side_box(
+        &self,
+        title: &str,
+        lines: Vec<Line<'static>>,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let theme = &self.settings.theme;
+        let block = Block::bordered()
+            .title(Line::raw(title).centered())
+            .borders(Borders::ALL)
+            .border_set(theme.pane_border(false))
+            .border_style(theme.border_style())
+            .padding(Padding::uniform(1));
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block)
+            .render(area, buf);
+    }
+
+    fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
+        let theme = &self.settings.theme;
+        let focused = matches!(self.current_state, Some(AppState::Main(..)));
 
+        let list = &self.config_list;
+        let mut title = match list.grouping {
+            Grouping::None => " Config list ".to_owned(),
+            grouping => format!(" Config list by {} ", grouping.name()),
+        };
+        if !list.filter.is_empty() {
+            title = format!("{}[{}] ", title, list.filter.join(","));
+        }
         let block = Block::bordered()
-            .title(Line::raw(" Config list ").centered())
+            .title(Line::raw(title).centered())
             .borders(Borders::ALL)
-            .border_set(border_style)
+            .border_set(theme.pane_border(focused))
+            .border_style(theme.border_style())
             .padding(Padding::uniform(1));
+        self.areas.list = block.inner(area);
 
-        // Iterate through all elements in the `items` and stylize them.
-        let items: Vec<ListItem> = self.config_list.items.iter().map(ListItem::from).collect();
+        let items = self.config_list.items();
 
         // Create a List from all list items and highlight the currently selected one
         let list = List::new(items)
             .block(block)
-            .highlight_style(SELECTED_STYLE)
+            .highlight_style(theme.selected_style())
             .highlight_symbol("> ")
             .highlight_spacing(HighlightSpacing::Always);
 
@@ -497,9 +3793,63 @@ impl App {
     }
 }
 
-impl From<&Config> for ListItem<'_> {
-    fn from(value: &Config) -> Self {
-        let line = Line::raw(value.host.to_owned());
-        ListItem::new(line)
+/// Number of lines `text` takes once word-wrapped to `width` columns.
+fn wrapped_height(text: &str, width: u16) -> u16 {
+    let width = usize::from(width.max(1));
+    let mut lines = 1;
+    let mut current = 0;
+    for word in text.split(' ').map(|w| Line::raw(w).width()) {
+        if current > 0 && current + 1 + word > width {
+            lines += 1;
+            current = word;
+        } else if current > 0 {
+            current += 1 + word;
+        } else {
+            current = word;
+        }
+    }
+    lines
+}
+
+/// Width of a one-line text popup: wide enough for its title and content,
+/// plus borders, padding and the cursor.
+fn text_popup_width(title: &str, textarea: &TextArea) -> u16 {
+    let content = textarea
+        .lines()
+        .iter()
+        .map(|l| Line::raw(l).width())
+        .chain([Line::raw(title).width()])
+        .max()
+        .unwrap_or(0);
+    u16::try_from(content + 5).unwrap_or(u16::MAX).max(30)
+}
+
+/// Center a `width` x `height` popup in `area`, shrinking it to fit.
+fn popup_area(area: Rect, width: u16, height: u16) -> Rect {
+    let width = std::cmp::min(area.width, width);
+    let height = std::cmp::min(area.height, height);
+
+    let s_x = area.x + (area.width - width) / 2;
+    let s_y = area.y + (area.height - height) / 2;
+
+    Rect::new(s_x, s_y, width, height)
+}
+
+/// Index of the list item drawn at `pos`, for a list drawn in `area`,
+/// scrolled by `offset` and whose items are `heights` rows high.
+fn row_at<I>(area: Rect, pos: Position, offset: usize, heights: I) -> Option<usize>
+where
+    I: IntoIterator<Item = usize>,
+{
+    if !area.contains(pos) {
+        return None;
+    }
+    let mut y = (pos.y - area.y) as usize;
+    for (i, height) in heights.into_iter().enumerate().skip(offset) {
+        if y < height {
+            return Some(i);
+        }
+        y -= height;
     }
+    None
 }
@@ -0,0 +1,296 @@
+use crate::{
+    algorithms,
+    sshconfig::{self, Config, Key},
+};
+
+/// A kind of risky setting. The ID is what `# sshe: ignore=` takes, like
+/// the lint rules.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    HostKeyCheckingOff,
+    DiscardedKnownHosts,
+    WildcardAgentForwarding,
+    PasswordAuthentication,
+    LegacyAlgorithms,
+}
+
+impl Check {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Check::HostKeyCheckingOff => "host-key-checking-off",
+            Check::DiscardedKnownHosts => "discarded-known-hosts",
+            Check::WildcardAgentForwarding => "wildcard-agent-forwarding",
+            Check::PasswordAuthentication => "password-authentication",
+            Check::LegacyAlgorithms => "legacy-algorithms",
+        }
+    }
+}
+
+/// A risky value of `key` in the block at `config_idx`. `safer` is the value
+/// to use instead, `None` when removing the key is enough.
+#[derive(Clone)]
+pub struct Risk {
+    pub check: Check,
+    pub config_idx: usize,
+    pub key: Key,
+    pub explanation: String,
+    pub safer: Option<String>,
+}
+
+impl Risk {
+    pub fn location(&self, configs: &[Config]) -> String {
+        format!("Host {}, {}", configs[self.config_idx].host, self.key.str())
+    }
+
+    /// What applying [`Risk::safer`] does, as shown to the user.
+    pub fn fix(&self) -> String {
+        match &self.safer {
+            Some(value) => format!("set {} {}", self.key.str(), value),
+            None => format!("remove {}", self.key.str()),
+        }
+    }
+
+    /// Replace the risky value in `configs` with the safer one.
+    pub fn apply(&self, configs: &mut [Config]) {
+        let cfg = &mut configs[self.config_idx];
+        match &self.safer {
+            Some(value) => {
+                cfg.columns.insert(self.key, value.clone());
+            }
+            None => cfg.remove_key(self.key),
+        }
+    }
+}
+
+/// Every risky setting of the config, by block. Risks silenced by an
+/// `ignore=` directive are left out.
+pub fn audit(configs: &[Config]) -> Vec<Risk> {
+    let mut risks = vec![];
+    for (i, cfg) in configs.iter().enumerate() {
+        for key in sshconfig::ALL_KEYS.iter().copied() {
+            let Some(value) = cfg.columns.get(&key) else {
+                continue;
+            };
+            let Some(risk) = check(i, cfg, key, value.trim()) else {
+                continue;
+            };
+            let ignored = cfg.ignored(Some(key));
+            if !ignored.iter().any(|x| *x == risk.check.id() || *x == "all") {
+                risks.push(risk);
+            }
+        }
+    }
+    risks
+}
+
+/// Whether the block at `config_idx` has a risky setting.
+pub fn is_risky(risks: &[Risk], config_idx: usize) -> bool {
+    risks.iter().any(|r| r.config_idx == config_idx)
+}
+
+fn check(config_idx: usize, cfg: &Config, key: Key, value: &str) -> Option<Risk> {
+    let is = |x: &str| value.eq_ignore_ascii_case(x);
+    let risk = |check, explanation: String, safer: Option<&str>| Risk {
+        check,
+        config_idx,
+        key,
+        explanation,
+        safer: safer.map(str::to_owned),
+    };
+    match key {
+        Key::StrictHostKeyChecking if is("no") || is("off") => Some(risk(
+            Check::HostKeyCheckingOff,
+            "unknown and changed host keys are accepted silently, a man in the middle goes \
+             unnoticed"
+                .to_owned(),
+            Some("accept-new"),
+        )),
+        Key::UserKnownHostsFile if value.split_whitespace().any(|f| f == "/dev/null") => Some(risk(
+            Check::DiscardedKnownHosts,
+            "host keys are never remembered, so every connection trusts whatever key it is shown"
+                .to_owned(),
+            None,
+        )),
+        Key::ForwardAgent
+            if !is("no") && cfg.host.split_whitespace().any(|p| p.contains(['*', '?'])) =>
+        {
+            Some(risk(
+                Check::WildcardAgentForwarding,
+                format!(
+                    "every host matching `{}` can use your agent keys while you are connected",
+                    cfg.host
+                ),
+                Some("no"),
+            ))
+        }
+        Key::PasswordAuthentication if is("yes") => Some(risk(
+            Check::PasswordAuthentication,
+            "passwords can be phished by a spoofed server and guessed, prefer keys".to_owned(),
+            Some("no"),
+        )),
+        _ if algorithms::known(key).is_some() => {
+            let enabled = algorithms::effective(key, Some(value));
+            let weak: Vec<&str> = enabled
+                .iter()
+                .filter(|x| algorithms::is_weak(x))
+                .map(|x| x.as_str())
+                .collect();
+            if weak.is_empty() {
+                return None;
+            }
+            let kept: Vec<String> = enabled
+                .iter()
+                .filter(|x| !algorithms::is_weak(x))
+                .cloned()
+                .collect();
+            // With nothing left, the defaults are the safer value.
+            let safer = if kept.is_empty() {
+                None
+            } else {
+                algorithms::compact(key, &kept)
+            };
+            Some(risk(
+                Check::LegacyAlgorithms,
+                format!("enables broken or deprecated {}", weak.join(", ")),
+                safer.as_deref(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sshconfigfile;
+
+    /// `key check` for each risk of `text`.
+    fn checks(text: &str) -> Vec<String> {
+        let configs = sshconfigfile::parse(text.as_bytes()).unwrap();
+        audit(&configs)
+            .iter()
+            .map(|r| format!("{} {}", r.key.str(), r.check.id()))
+            .collect()
+    }
+
+    /// `check-id safer-fix` for each risk of `text`.
+    fn fixes(text: &str) -> Vec<String> {
+        let configs = sshconfigfile::parse(text.as_bytes()).unwrap();
+        audit(&configs)
+            .iter()
+            .map(|r| format!("{} {}", r.check.id(), r.fix()))
+            .collect()
+    }
+
+    #[test]
+    fn flags_host_key_checking_off() {
+        assert_eq!(
+            fixes("Host a\n  StrictHostKeyChecking no\nHost b\n  StrictHostKeyChecking off\n"),
+            [
+                "host-key-checking-off set StrictHostKeyChecking accept-new",
+                "host-key-checking-off set StrictHostKeyChecking accept-new",
+            ]
+        );
+        assert!(fixes("Host a\n  StrictHostKeyChecking yes\n").is_empty());
+        assert!(fixes("Host a\n  StrictHostKeyChecking accept-new\n").is_empty());
+    }
+
+    #[test]
+    fn flags_discarded_known_hosts() {
+        assert_eq!(
+            fixes("Host a\n  UserKnownHostsFile ~/.ssh/known_hosts /dev/null\n"),
+            ["discarded-known-hosts remove UserKnownHostsFile"]
+        );
+        assert!(fixes("Host a\n  UserKnownHostsFile ~/.ssh/known_hosts\n").is_empty());
+    }
+
+    #[test]
+    fn flags_agent_forwarding_to_wildcards_only() {
+        assert_eq!(
+            fixes("Host *\n  ForwardAgent yes\nHost web?\n  ForwardAgent yes\n"),
+            [
+                "wildcard-agent-forwarding set ForwardAgent no",
+                "wildcard-agent-forwarding set ForwardAgent no",
+            ]
+        );
+        assert!(fixes("Host a\n  ForwardAgent yes\n").is_empty());
+        assert!(fixes("Host *\n  ForwardAgent no\n").is_empty());
+    }
+
+    #[test]
+    fn flags_password_authentication() {
+        assert_eq!(
+            fixes("Host a\n  PasswordAuthentication yes\n"),
+            ["password-authentication set PasswordAuthentication no"]
+        );
+        assert!(fixes("Host a\n  PasswordAuthentication no\n").is_empty());
+    }
+
+    #[test]
+    fn drops_weak_algorithms_in_the_fix() {
+        assert_eq!(
+            fixes("Host a\n  Ciphers aes256-ctr,3des-cbc\nHost b\n  KexAlgorithms +diffie-hellman-group1-sha1\n"),
+            [
+                "legacy-algorithms set Ciphers aes256-ctr",
+                "legacy-algorithms remove KexAlgorithms",
+            ]
+        );
+        assert!(fixes("Host a\n  Ciphers aes256-ctr\n").is_empty());
+    }
+
+    #[test]
+    fn applies_the_safer_value() {
+        let mut configs = sshconfigfile::parse(
+            "Host *\n  # agent everywhere\n  ForwardAgent yes\n  UserKnownHostsFile /dev/null\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        for risk in audit(&configs) {
+            risk.apply(&mut configs);
+        }
+        assert_eq!(configs[0].columns[&Key::ForwardAgent], "no");
+        assert!(!configs[0].columns.contains_key(&Key::UserKnownHostsFile));
+        assert!(audit(&configs).is_empty());
+    }
+
+    #[test]
+    fn skips_ignored_checks() {
+        let text = "\
+# sshe: ignore=password-authentication
+Host a
+  PasswordAuthentication yes
+  # sshe: ignore=all
+  StrictHostKeyChecking no
+  UserKnownHostsFile /dev/null
+";
+        assert_eq!(checks(text), ["UserKnownHostsFile discarded-known-hosts"]);
+    }
+
+    #[test]
+    fn flags_legacy_algorithms_under_deprecated_names() {
+        let text = "\
+Host a
+  PubkeyAcceptedKeyTypes +ssh-rsa
+Host b
+  HostbasedKeyTypes +ssh-dss
+Host c
+  PubkeyAcceptedAlgorithms +ssh-rsa
+";
+        assert_eq!(
+            checks(text),
+            [
+                "PubkeyAcceptedKeyTypes legacy-algorithms",
+                "HostbasedKeyTypes legacy-algorithms",
+                "PubkeyAcceptedAlgorithms legacy-algorithms",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_deprecated_name_in_the_fix() {
+        let configs =
+            sshconfigfile::parse("Host a\n  PubkeyAcceptedKeyTypes +ssh-rsa\n".as_bytes()).unwrap();
+        let risks = audit(&configs);
+        assert_eq!(risks[0].fix(), "remove PubkeyAcceptedKeyTypes");
+    }
+}
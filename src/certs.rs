@@ -0,0 +1,462 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    keys::{self, PublicKey},
+    sshconfig::{self, Config, Key},
+    wire::Reader,
+};
+
+/// Whether a certificate authenticates a user or a host.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CertType {
+    User,
+    Host,
+}
+
+/// Where the current time falls in a certificate's validity window.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Validity {
+    /// Valid for that many more seconds.
+    Valid(u64),
+    /// Expired that many seconds ago.
+    Expired(u64),
+    /// Valid in that many seconds.
+    NotYetValid(u64),
+}
+
+impl Validity {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Validity::Valid(..))
+    }
+}
+
+impl fmt::Display for Validity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Validity::Valid(u64::MAX) => write!(f, "valid"),
+            Validity::Valid(left) => write!(f, "valid for {}", duration(*left)),
+            Validity::Expired(ago) => write!(f, "expired {} ago", duration(*ago)),
+            Validity::NotYetValid(wait) => {
+                write!(f, "not yet valid, starts in {}", duration(*wait))
+            }
+        }
+    }
+}
+
+/// An OpenSSH certificate, as in a `-cert.pub` file.
+pub struct Certificate {
+    pub key: PublicKey,
+    pub serial: u64,
+    pub cert_type: CertType,
+    pub key_id: String,
+    /// Users or hosts the certificate is valid for, any when empty.
+    pub principals: Vec<String>,
+    /// Seconds since the epoch.
+    pub valid_after: u64,
+    pub valid_before: u64,
+    /// Name and value of each option.
+    pub critical_options: Vec<(String, String)>,
+    pub extensions: Vec<String>,
+    /// The key of the CA that signed the certificate.
+    pub ca: PublicKey,
+}
+
+impl Certificate {
+    /// Parse a `type base64 comment` certificate line.
+    pub fn parse(line: &str) -> Result<Certificate, String> {
+        let key = PublicKey::parse(line)?;
+        if !key.key_type.contains("-cert-") {
+            return Err(format!("{} is not a certificate type", key.key_type));
+        }
+        let mut reader = Reader::new(&key.blob);
+        reader.text()?;
+        reader.string()?;
+        // The key fields, whose size `PublicKey` already read.
+        let fields = match key.key_type.trim_end_matches("-cert-v01@openssh.com") {
+            "ssh-dss" => 4,
+            "ssh-ed25519" => 1,
+            "sk-ssh-ed25519@openssh.com" => 2,
+            t if t.starts_with("sk-ecdsa") => 3,
+            _ => 2,
+        };
+        for _ in 0..fields {
+            reader.string()?;
+        }
+
+        let serial = reader.u64()?;
+        let cert_type = match reader.u32()? {
+            1 => CertType::User,
+            2 => CertType::Host,
+            n => return Err(format!("unknown certificate type {}", n)),
+        };
+        let key_id = reader.text()?;
+        let mut principals = vec![];
+        let mut packed = Reader::new(reader.string()?);
+        while !packed.is_empty() {
+            principals.push(packed.text()?);
+        }
+        let valid_after = reader.u64()?;
+        let valid_before = reader.u64()?;
+        let critical_options = options(reader.string()?)?;
+        let extensions = options(reader.string()?)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        reader.string()?;
+        let ca = PublicKey::from_blob(reader.string()?.to_vec(), String::new())?;
+
+        Ok(Certificate {
+            key,
+            serial,
+            cert_type,
+            key_id,
+            principals,
+            valid_after,
+            valid_before,
+            critical_options,
+            extensions,
+            ca,
+        })
+    }
+
+    /// Where `now`, in seconds since the epoch, falls in the validity window.
+    pub fn validity(&self, now: u64) -> Validity {
+        if now < self.valid_after {
+            Validity::NotYetValid(self.valid_after - now)
+        } else if now >= self.valid_before {
+            Validity::Expired(now - self.valid_before)
+        } else if self.valid_before == u64::MAX {
+            Validity::Valid(u64::MAX)
+        } else {
+            Validity::Valid(self.valid_before - now)
+        }
+    }
+
+    /// Whether the certificate lets `principal` in.
+    pub fn allows(&self, principal: &str) -> bool {
+        self.principals.is_empty() || self.principals.iter().any(|p| p == principal)
+    }
+
+    /// The validity window, as `ssh-keygen -L` shows it but in UTC.
+    pub fn window(&self) -> String {
+        match (self.valid_after, self.valid_before) {
+            (0, u64::MAX) => "forever".to_owned(),
+            (0, before) => format!("before {}", timestamp(before)),
+            (after, u64::MAX) => format!("from {}", timestamp(after)),
+            (after, before) => format!("from {} to {}", timestamp(after), timestamp(before)),
+        }
+    }
+}
+
+/// The name and data pairs of the critical options or extensions. Each
+/// data is itself a string holding the value, empty for flags.
+fn options(data: &[u8]) -> Result<Vec<(String, String)>, String> {
+    let mut reader = Reader::new(data);
+    let mut options = vec![];
+    while !reader.is_empty() {
+        let name = reader.text()?;
+        let data = reader.string()?;
+        let value = if data.is_empty() {
+            String::new()
+        } else {
+            Reader::new(data).text()?
+        };
+        options.push((name, value));
+    }
+    Ok(options)
+}
+
+/// A certificate file a host uses.
+pub struct CertFile {
+    pub path: PathBuf,
+    /// Why the file could not be read as a certificate.
+    pub certificate: Result<Certificate, String>,
+}
+
+pub fn load(path: &Path) -> CertFile {
+    let certificate = match fs::read_to_string(path) {
+        Ok(text) => Certificate::parse(&text),
+        Err(e) => Err(e.to_string()),
+    };
+    CertFile {
+        path: path.to_owned(),
+        certificate,
+    }
+}
+
+/// The certificates of a block: its `CertificateFile` values and the
+/// `-cert.pub` files next to its `IdentityFile` keys, which ssh also
+/// loads.
+pub fn paths(cfg: &Config) -> Vec<PathBuf> {
+    let mut paths = keys::paths(cfg, Key::CertificateFile);
+    for key in keys::paths(cfg, Key::IdentityFile) {
+        let mut cert = key.as_os_str().to_owned();
+        cert.push("-cert.pub");
+        let cert = PathBuf::from(cert);
+        if cert.is_file() && !paths.contains(&cert) {
+            paths.push(cert);
+        }
+    }
+    paths
+}
+
+/// Every certificate the config uses.
+pub fn inventory(configs: &[Config]) -> Vec<CertFile> {
+    let mut found: Vec<PathBuf> = vec![];
+    for path in configs.iter().flat_map(paths) {
+        if !found.contains(&path) {
+            found.push(path);
+        }
+    }
+    found.iter().map(|p| load(p)).collect()
+}
+
+/// The loaded certificates ssh offers for `alias`, from every block
+/// matching it.
+pub fn of<'a>(inventory: &'a [CertFile], configs: &[Config], alias: &str) -> Vec<&'a CertFile> {
+    let used: Vec<PathBuf> = configs
+        .iter()
+        .filter(|c| c.matches(alias))
+        .flat_map(paths)
+        .collect();
+    inventory
+        .iter()
+        .filter(|c| used.contains(&c.path))
+        .collect()
+}
+
+/// The user ssh logs in as on `alias`.
+pub fn user(configs: &[Config], alias: &str) -> String {
+    sshconfig::effective(configs, alias)
+        .into_iter()
+        .find(|(k, _, _)| *k == Key::User)
+        .map(|(_, v, _)| v.trim().replace("%u", &local_user()))
+        .unwrap_or_else(local_user)
+}
+
+fn local_user() -> String {
+    std::env::var("USER").unwrap_or_default()
+}
+
+/// Seconds since the epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// The details `ssh-keygen -L` shows, one per line, with how long the
+/// certificate is still valid at `now`.
+pub fn details(cert: &Certificate, now: u64) -> Vec<String> {
+    let kind = match cert.cert_type {
+        CertType::User => "user",
+        CertType::Host => "host",
+    };
+    let mut lines = vec![
+        format!(
+            "{} {} certificate, serial {}",
+            cert.key.kind().trim_end_matches("-CERT"),
+            kind,
+            cert.serial
+        ),
+        format!("Key ID: {}", cert.key_id),
+    ];
+    if cert.principals.is_empty() {
+        lines.push("Principals: any".to_owned());
+    } else {
+        lines.push(format!("Principals: {}", cert.principals.join(", ")));
+    }
+    match cert.validity(now) {
+        Validity::Valid(left) if left != u64::MAX => {
+            lines.push(format!("Valid: {}, {} more", cert.window(), duration(left)))
+        }
+        _ => lines.push(format!("Valid: {}", cert.window())),
+    }
+    for (name, value) in cert.critical_options.iter() {
+        lines.push(
+            format!("Critical option: {} {}", name, value)
+                .trim_end()
+                .to_owned(),
+        );
+    }
+    if !cert.extensions.is_empty() {
+        lines.push(format!("Extensions: {}", cert.extensions.join(", ")));
+    }
+    lines.push(format!("CA: {} {}", cert.ca.kind(), cert.ca.fingerprint()));
+    lines
+}
+
+/// What keeps the certificate from working for `user` now, if anything.
+pub fn problems(cert: &Certificate, user: &str, now: u64) -> Vec<String> {
+    let mut problems = vec![];
+    let validity = cert.validity(now);
+    if !validity.is_valid() {
+        problems.push(validity.to_string());
+    }
+    if cert.cert_type == CertType::User && !cert.allows(user) {
+        problems.push(format!("no principal for user {}", user));
+    }
+    problems
+}
+
+/// A rough duration, such as `3h 20m` or `2d 4h`.
+fn duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours) {
+        (0, 0) if minutes == 0 => format!("{}s", secs),
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` for seconds since the epoch.
+fn timestamp(secs: u64) -> String {
+    // Civil date from days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ssh-keygen -s ca -I alice@example -n alice,deploy -V
+    /// 20240101000000Z:20250101000000Z -O force-command=/bin/true -O
+    /// source-address=10.0.0.0/8 -z 42` of an ed25519 key.
+    const USER: &str = "ssh-ed25519-cert-v01@openssh.com AAAAIHNzaC1lZDI1NTE5LWNlcnQtdjAxQG9wZW5zc2guY29tAAAAIMjg7HxUh5wPn+vkr9eWokC2AUkTgjAmoBSkilQ8aRmXAAAAIP3/ws+feycHCuu80w0TL1AEpjSNIbpOyYSg6bp6yDE6AAAAAAAAACoAAAABAAAADWFsaWNlQGV4YW1wbGUAAAATAAAABWFsaWNlAAAABmRlcGxveQAAAABlkgCAAAAAAGd0hYAAAABGAAAADWZvcmNlLWNvbW1hbmQAAAANAAAACS9iaW4vdHJ1ZQAAAA5zb3VyY2UtYWRkcmVzcwAAAA4AAAAKMTAuMC4wLjAvOAAAAIIAAAAVcGVybWl0LVgxMS1mb3J3YXJkaW5nAAAAAAAAABdwZXJtaXQtYWdlbnQtZm9yd2FyZGluZwAAAAAAAAAWcGVybWl0LXBvcnQtZm9yd2FyZGluZwAAAAAAAAAKcGVybWl0LXB0eQAAAAAAAAAOcGVybWl0LXVzZXItcmMAAAAAAAAAAAAAADMAAAALc3NoLWVkMjU1MTkAAAAgbyvOrP0OlXfqxX4KqalizACjxVpLRdhx4yf1/TFMOIwAAABTAAAAC3NzaC1lZDI1NTE5AAAAQDCXEcJHEuVv+1kdeJha/aihg7UnUKmRo+0BBRQkKGoKat6CIz3gm3QxLiAGGoduGz28cOpQIpjsOR4EJWFJ4Qg= user";
+
+    /// `ssh-keygen -s ca -h -I web01 -n web01.example.com -z 7` of an ECDSA
+    /// key.
+    const HOST: &str = "ecdsa-sha2-nistp256-cert-v01@openssh.com AAAAKGVjZHNhLXNoYTItbmlzdHAyNTYtY2VydC12MDFAb3BlbnNzaC5jb20AAAAgGagqpF9T/hm3UzoPF31u1ZSLeRhG0x26W9KMf38eZYEAAAAIbmlzdHAyNTYAAABBBLv06MLwvjhFgWtJaexXZCDpvDvPf8tuoPkZyolOnDHmNipJzkz+H/HmZ+QYQYZhwfNqeKnwiUbmwy8sltgvqicAAAAAAAAABwAAAAIAAAAFd2ViMDEAAAAVAAAAEXdlYjAxLmV4YW1wbGUuY29tAAAAAAAAAAD//////////wAAAAAAAAAAAAAAAAAAADMAAAALc3NoLWVkMjU1MTkAAAAgbyvOrP0OlXfqxX4KqalizACjxVpLRdhx4yf1/TFMOIwAAABTAAAAC3NzaC1lZDI1NTE5AAAAQNEFLK7Kmp/mQctBtxrBSzYa55OHsdsfBv4K64J4Hp9sAdXg3QH/G7eMR+/ZPpHvmUdHHiKuCEbjrvikNFOhHgQ= host";
+
+    /// `ssh-keygen -s ca -I forever -z 3` of an RSA key.
+    const RSA: &str = "ssh-rsa-cert-v01@openssh.com AAAAHHNzaC1yc2EtY2VydC12MDFAb3BlbnNzaC5jb20AAAAgUJCRyiiCz50iBr14U9LNJXrYUlz8kHNq8ZzCNEb3H00AAAADAQABAAABAQDTIhMnZ/Mtl/s509HqCcDtoHhCrT1O/CYNZCaC+zKk4qBzsVQvCA2rsXSgppfe2AoHdPWluIaDDxUEoHj6vBx4WhA2RP68Qz37CF3Z3flh1ajyfXrGEcu3Pn/UB25ZdJctT22C9fwYm4seNvIp3hWIIefFzYYLju8ksWddZrePXIasxy0mLJbEF7HVHwJZHdJLv0z82PRSqHHBBitai4d8F1rbLpTtBZCOx3rLTuietPrM5O08qtOlJC8MZVx3qiEusJ7dwcIcuVL6mjrUmftg1jChDNHAjXlituhr5fAwRM99/uatmejDl5svaTZREmzc9wKWLduPrRcRbyK5h0tJAAAAAAAAAAMAAAABAAAAB2ZvcmV2ZXIAAAAAAAAAAAAAAAD//////////wAAAAAAAACCAAAAFXBlcm1pdC1YMTEtZm9yd2FyZGluZwAAAAAAAAAXcGVybWl0LWFnZW50LWZvcndhcmRpbmcAAAAAAAAAFnBlcm1pdC1wb3J0LWZvcndhcmRpbmcAAAAAAAAACnBlcm1pdC1wdHkAAAAAAAAADnBlcm1pdC11c2VyLXJjAAAAAAAAAAAAAAAzAAAAC3NzaC1lZDI1NTE5AAAAIG8rzqz9DpV36sV+CqmpYswAo8VaS0XYceMn9f0xTDiMAAAAUwAAAAtzc2gtZWQyNTUxOQAAAECDYQReh7bmu7hLuj9KuR5RpV8HZIDjbkxhq8l4DemGNm3L7neznuAmb6iqKqohmYz0rbWUpN5agSib3tFPj0IK rsa";
+
+    const CA_FINGERPRINT: &str = "SHA256:EEab23pFS5eaW/nXhhSvlOv6owmF+ac4k05auw84Il0";
+
+    /// 2024-01-01T00:00:00Z and 2025-01-01T00:00:00Z.
+    const AFTER: u64 = 1704067200;
+    const BEFORE: u64 = 1735689600;
+
+    #[test]
+    fn parses_a_user_certificate() {
+        let cert = Certificate::parse(USER).unwrap();
+        assert_eq!(cert.serial, 42);
+        assert!(cert.cert_type == CertType::User);
+        assert_eq!(cert.key_id, "alice@example");
+        assert_eq!(cert.principals, ["alice", "deploy"]);
+        assert_eq!((cert.valid_after, cert.valid_before), (AFTER, BEFORE));
+        assert_eq!(
+            cert.critical_options,
+            [
+                ("force-command".to_owned(), "/bin/true".to_owned()),
+                ("source-address".to_owned(), "10.0.0.0/8".to_owned()),
+            ]
+        );
+        assert_eq!(
+            cert.extensions,
+            [
+                "permit-X11-forwarding",
+                "permit-agent-forwarding",
+                "permit-port-forwarding",
+                "permit-pty",
+                "permit-user-rc",
+            ]
+        );
+        assert_eq!(cert.ca.fingerprint(), CA_FINGERPRINT);
+        assert_eq!(cert.key.kind(), "ED25519-CERT");
+        assert_eq!(
+            cert.window(),
+            "from 2024-01-01T00:00:00Z to 2025-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn parses_host_and_rsa_certificates() {
+        let host = Certificate::parse(HOST).unwrap();
+        assert_eq!(host.serial, 7);
+        assert!(host.cert_type == CertType::Host);
+        assert_eq!(host.principals, ["web01.example.com"]);
+        assert!(host.critical_options.is_empty() && host.extensions.is_empty());
+        assert_eq!(host.ca.fingerprint(), CA_FINGERPRINT);
+        assert_eq!(host.window(), "forever");
+
+        let rsa = Certificate::parse(RSA).unwrap();
+        assert_eq!(rsa.serial, 3);
+        assert_eq!(rsa.key_id, "forever");
+        assert!(rsa.principals.is_empty() && rsa.allows("anyone"));
+        assert_eq!(rsa.ca.fingerprint(), CA_FINGERPRINT);
+    }
+
+    #[test]
+    fn rejects_plain_keys() {
+        let plain =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOdXyM/J7Xku4Rx1528OVqvXiC2a2aSVAAorgwD8jF3o";
+        assert!(Certificate::parse(plain).is_err());
+    }
+
+    #[test]
+    fn validity_at_the_window_edges() {
+        let cert = Certificate::parse(USER).unwrap();
+        assert_eq!(cert.validity(AFTER - 1), Validity::NotYetValid(1));
+        assert_eq!(cert.validity(AFTER), Validity::Valid(BEFORE - AFTER));
+        assert_eq!(cert.validity(BEFORE - 1), Validity::Valid(1));
+        assert_eq!(cert.validity(BEFORE), Validity::Expired(0));
+        assert_eq!(cert.validity(BEFORE + 90000), Validity::Expired(90000));
+        assert_eq!(
+            Certificate::parse(HOST).unwrap().validity(u64::MAX - 1),
+            Validity::Valid(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn problems_of_a_user_certificate() {
+        let cert = Certificate::parse(USER).unwrap();
+        assert!(problems(&cert, "alice", AFTER).is_empty());
+        assert_eq!(
+            problems(&cert, "bob", BEFORE + 86400 * 2 + 3600),
+            ["expired 2d 1h ago", "no principal for user bob"]
+        );
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(AFTER), "2024-01-01T00:00:00Z");
+        // Leap day.
+        assert_eq!(timestamp(1709210096), "2024-02-29T12:34:56Z");
+        assert_eq!(timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(timestamp(4102444799), "2099-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(duration(45), "45s");
+        assert_eq!(duration(600), "10m");
+        assert_eq!(duration(3 * 3600 + 20 * 60), "3h 20m");
+        assert_eq!(duration(2 * 86400 + 4 * 3600), "2d 4h");
+    }
+}
@@ -0,0 +1,320 @@
+use std::path::{Path, PathBuf};
+
+use crate::sshconfig::{Config, Key, ValueKind};
+
+/// Tab-completion state for a value being edited.
+///
+/// `head` is the part of the input that is kept as is, every candidate
+/// replaces whatever follows it. Repeated calls to [`Completion::next`] cycle
+/// through the candidates.
+pub struct Completion {
+    head: String,
+    candidates: Vec<String>,
+    idx: Option<usize>,
+}
+
+impl Completion {
+    /// Build the completions for `input` as a value of `key`. Returns `None`
+    /// when there is nothing to offer.
+    pub fn new(key: Key, input: &str, configs: &[Config]) -> Option<Self> {
+        let completion = match key.value_kind() {
+            ValueKind::Text | ValueKind::Forward | ValueKind::Algorithms => return None,
+            ValueKind::Enum(values) => enum_values(values, input),
+            ValueKind::Path => {
+                let (head, tail) = split_last(input, ' ');
+                Completion {
+                    head: head.to_owned(),
+                    candidates: paths(tail, home::home_dir().as_deref()),
+                    idx: None,
+                }
+            }
+            ValueKind::Host => {
+                let (head, tail) = match key {
+                    Key::ProxyJump => split_last(input, ','),
+                    _ => ("", input),
+                };
+                let (user, tail) = match tail.split_once('@') {
+                    Some((user, rest)) => (&tail[..user.len() + 1], rest),
+                    None => ("", tail),
+                };
+                let mut candidates = hosts(configs, key == Key::HostName);
+                candidates.retain(|h| starts_with_ignore_case(h, tail));
+                Completion {
+                    head: format!("{}{}", head, user),
+                    candidates,
+                    idx: None,
+                }
+            }
+        };
+
+        if completion.candidates.is_empty() {
+            None
+        } else {
+            Some(completion)
+        }
+    }
+
+    /// Move to the next candidate and return the full completed value.
+    pub fn next(&mut self) -> String {
+        let len = self.candidates.len();
+        let idx = self.idx.map_or(0, |i| (i + 1) % len);
+        self.select(idx)
+    }
+
+    /// Move to the previous candidate and return the full completed value.
+    pub fn previous(&mut self) -> String {
+        let len = self.candidates.len();
+        let idx = self.idx.map_or(len - 1, |i| (i + len - 1) % len);
+        self.select(idx)
+    }
+
+    /// One-based position of the current candidate and the candidate count.
+    pub fn position(&self) -> (usize, usize) {
+        (self.idx.map_or(0, |i| i + 1), self.candidates.len())
+    }
+
+    fn select(&mut self, idx: usize) -> String {
+        self.idx = Some(idx);
+        format!("{}{}", self.head, self.candidates[idx])
+    }
+}
+
+/// Legal values of an enum key. If the input is already one of them, cycling
+/// starts from it, otherwise only the values it is a prefix of are offered.
+fn enum_values(values: &[&str], input: &str) -> Completion {
+    let input = input.trim();
+    let current = values.iter().position(|v| v.eq_ignore_ascii_case(input));
+
+    let candidates: Vec<String> = match current {
+        Some(_) => values.iter().map(|v| v.to_string()).collect(),
+        None => values
+            .iter()
+            .filter(|v| starts_with_ignore_case(v, input))
+            .map(|v| v.to_string())
+            .collect(),
+    };
+
+    Completion {
+        head: String::new(),
+        candidates,
+        idx: current,
+    }
+}
+
+/// Entries of the directory part of `input` whose name starts with the file
+/// part. A leading `~` is resolved against `home` but kept in the completed
+/// value, and a name without a directory is completed in `~/.ssh`: the
+/// directory sshe runs from means nothing to ssh. Directories get a trailing
+/// `/` so the next completion descends into them.
+fn paths(input: &str, home: Option<&Path>) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => input.split_at(i + 1),
+        None => ("~/.ssh/", input),
+    };
+
+    let resolved = if let Some(rest) = dir.strip_prefix("~/") {
+        match home {
+            Some(home) => home.join(rest),
+            None => return vec![],
+        }
+    } else {
+        PathBuf::from(dir)
+    };
+
+    let Ok(entries) = std::fs::read_dir(resolved) else {
+        return vec![];
+    };
+
+    let mut result: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+
+    if input == "~" {
+        result.push("~/".to_owned());
+    }
+
+    result.sort();
+    result
+}
+
+/// Host aliases defined in the config, without wildcard patterns. With
+/// `with_host_names`, the values of existing `HostName` keys are included.
+fn hosts(configs: &[Config], with_host_names: bool) -> Vec<String> {
+    let mut result: Vec<String> = configs
+        .iter()
+        .flat_map(|cfg| cfg.host.split_whitespace())
+        .filter(|h| !h.contains(['*', '?', '!']))
+        .map(|h| h.to_owned())
+        .collect();
+
+    if with_host_names {
+        result.extend(
+            configs
+                .iter()
+                .filter_map(|cfg| cfg.columns.get(&Key::HostName))
+                .cloned(),
+        );
+    }
+
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Split `input` after the last `sep`, so that the head keeps the separator.
+fn split_last(input: &str, sep: char) -> (&str, &str) {
+    match input.rfind(sep) {
+        Some(i) => input.split_at(i + 1),
+        None => ("", input),
+    }
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sshconfigfile;
+
+    fn completed(key: Key, input: &str, configs: &[Config]) -> Vec<String> {
+        let Some(mut completion) = Completion::new(key, input, configs) else {
+            return vec![];
+        };
+        (0..completion.position().1)
+            .map(|_| completion.next())
+            .collect()
+    }
+
+    /// A home directory with a few files in `.ssh`.
+    fn home(name: &str) -> PathBuf {
+        let home =
+            std::env::temp_dir().join(format!("sshe-completion-{}-{}", std::process::id(), name));
+        let ssh = home.join(".ssh");
+        std::fs::create_dir_all(ssh.join("keys")).unwrap();
+        for file in [
+            "id_ed25519",
+            "id_ed25519.pub",
+            "id_rsa",
+            "config",
+            ".hidden",
+        ] {
+            std::fs::write(ssh.join(file), "").unwrap();
+        }
+        std::fs::write(home.join("notes"), "").unwrap();
+        home
+    }
+
+    #[test]
+    fn completes_enum_values() {
+        assert_eq!(
+            completed(Key::StrictHostKeyChecking, "a", &[]),
+            ["accept-new", "ask"]
+        );
+        assert_eq!(completed(Key::Compression, "Y", &[]), ["yes"]);
+        assert!(completed(Key::Compression, "maybe", &[]).is_empty());
+        assert!(completed(Key::User, "r", &[]).is_empty());
+    }
+
+    #[test]
+    fn cycles_from_the_current_enum_value() {
+        let mut completion = Completion::new(Key::ControlMaster, " ask ", &[]).unwrap();
+        assert_eq!(completion.position(), (3, 5));
+        assert_eq!(completion.next(), "auto");
+        assert_eq!(completion.next(), "autoask");
+        assert_eq!(completion.next(), "no");
+        assert_eq!(completion.previous(), "autoask");
+    }
+
+    #[test]
+    fn completes_names_in_the_ssh_directory() {
+        let home = home("names");
+        assert_eq!(
+            paths("id_", Some(&home)),
+            [
+                "~/.ssh/id_ed25519",
+                "~/.ssh/id_ed25519.pub",
+                "~/.ssh/id_rsa"
+            ]
+        );
+        assert_eq!(
+            paths("", Some(&home)),
+            [
+                "~/.ssh/config",
+                "~/.ssh/id_ed25519",
+                "~/.ssh/id_ed25519.pub",
+                "~/.ssh/id_rsa",
+                "~/.ssh/keys/"
+            ]
+        );
+        assert_eq!(paths(".h", Some(&home)), ["~/.ssh/.hidden"]);
+        assert!(paths("notes", Some(&home)).is_empty());
+        assert!(paths("id_", None).is_empty());
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn completes_paths_with_a_directory() {
+        let home = home("dirs");
+        assert_eq!(paths("~/n", Some(&home)), ["~/notes"]);
+        assert_eq!(paths("~/.ssh/k", Some(&home)), ["~/.ssh/keys/"]);
+        assert!(paths("~/.ssh/keys/", Some(&home)).is_empty());
+        assert_eq!(paths("~", Some(&home)), ["~/"]);
+
+        let ssh = format!("{}/.ssh/", home.display());
+        assert_eq!(
+            paths(&format!("{}id_r", ssh), Some(&home)),
+            [format!("{}id_rsa", ssh)]
+        );
+        assert!(paths("/nonexistent-sshe-dir/", Some(&home)).is_empty());
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn keeps_earlier_paths_of_the_value() {
+        let home = home("head");
+        let input = format!("~/.ssh/known_hosts {}/.ssh/co", home.display());
+        assert_eq!(
+            completed(Key::UserKnownHostsFile, &input, &[]),
+            [format!("~/.ssh/known_hosts {}/.ssh/config", home.display())]
+        );
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn completes_hosts() {
+        let text = "\
+Host web1 web2
+  HostName web.example
+Host db *.internal !web3
+  HostName 10.0.0.5
+Host Web3
+";
+        let configs = sshconfigfile::parse(text.as_bytes()).unwrap();
+        assert_eq!(
+            completed(Key::ProxyJump, "w", &configs),
+            ["Web3", "web1", "web2"]
+        );
+        assert_eq!(
+            completed(Key::ProxyJump, "db,me@WEB", &configs),
+            ["db,me@Web3", "db,me@web1", "db,me@web2"]
+        );
+        // Only HostName offers the existing host names.
+        assert_eq!(
+            completed(Key::HostName, "", &configs),
+            ["10.0.0.5", "Web3", "db", "web.example", "web1", "web2"]
+        );
+        assert!(completed(Key::ProxyJump, "x", &configs).is_empty());
+    }
+}
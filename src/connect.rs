@@ -0,0 +1,98 @@
+use std::{path::Path, process::Command};
+
+/// Fill in a command template from the `[connect]` settings: `{host}` is
+/// the alias to connect to and `{config}` the config file to read. Words
+/// are split on whitespace outside of double quotes, placeholders are
+/// replaced inside each word so no shell is involved.
+pub fn command_line(template: &str, host: &str, config: &Path) -> Vec<String> {
+    let config = config.to_string_lossy();
+    words(template)
+        .into_iter()
+        .map(|w| w.replace("{host}", host).replace("{config}", &config))
+        .collect()
+}
+
+/// The process for a command line from [`command_line`], `None` when it is
+/// empty.
+pub fn command(line: &[String]) -> Option<Command> {
+    let (program, args) = line.split_first()?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Some(command)
+}
+
+fn words(template: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+    for c in template.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(template: &str) -> Vec<String> {
+        command_line(template, "web1", Path::new("/home/me/.ssh/config"))
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        assert_eq!(
+            line("ssh -F {config} {host}"),
+            ["ssh", "-F", "/home/me/.ssh/config", "web1"]
+        );
+        assert_eq!(
+            line("mosh --ssh=\"ssh -F {config}\" {host}"),
+            ["mosh", "--ssh=ssh -F /home/me/.ssh/config", "web1"]
+        );
+        assert_eq!(line("sftp {host}:{host}"), ["sftp", "web1:web1"]);
+    }
+
+    #[test]
+    fn splits_words_outside_quotes() {
+        assert_eq!(line("  ssh\t -v  {host} "), ["ssh", "-v", "web1"]);
+        assert_eq!(line("a\"b c\"d e"), ["ab cd", "e"]);
+        assert_eq!(line("echo \"\" {host}"), ["echo", "", "web1"]);
+        // An unclosed quote runs to the end.
+        assert_eq!(line("echo \"a  b"), ["echo", "a  b"]);
+        assert!(line("   ").is_empty());
+    }
+
+    #[test]
+    fn keeps_placeholder_values_in_one_word() {
+        let line = command_line("ssh -F {config} {host}", "a b", Path::new("/my dir/config"));
+        assert_eq!(line, ["ssh", "-F", "/my dir/config", "a b"]);
+    }
+
+    #[test]
+    fn builds_the_command() {
+        assert!(command(&[]).is_none());
+        let command = command(&line("ssh -v {host}")).unwrap();
+        assert_eq!(command.get_program(), "ssh");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["-v", "web1"]);
+    }
+}
@@ -0,0 +1,382 @@
+use std::fmt;
+
+use crate::sshconfig::{Config, Key};
+
+/// One side of a forward: a TCP port, with the address it is bound to or
+/// the host it reaches, or a Unix socket.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp { host: Option<String>, port: u16 },
+    Socket(String),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp {
+                host: Some(host),
+                port,
+            } if host.contains(':') => write!(f, "[{}]:{}", host, port),
+            Endpoint::Tcp {
+                host: Some(host),
+                port,
+            } => write!(f, "{}:{}", host, port),
+            Endpoint::Tcp { host: None, port } => write!(f, "{}", port),
+            Endpoint::Socket(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// A `LocalForward`, `RemoteForward` or `DynamicForward` value. Dynamic
+/// forwards, and remote ones acting as a SOCKS proxy, have no target.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Forward {
+    pub listen: Endpoint,
+    pub target: Option<Endpoint>,
+}
+
+impl fmt::Display for Forward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.listen)?;
+        if let Some(target) = &self.target {
+            write!(f, " {}", target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Labels of the form fields used to edit a forward of `key`.
+pub fn field_names(key: Key) -> &'static [&'static str] {
+    match key {
+        Key::DynamicForward => &["Bind address", "Port"],
+        _ => &[
+            "Bind address",
+            "Port or socket",
+            "Target host or socket",
+            "Target port",
+        ],
+    }
+}
+
+impl Forward {
+    /// Parse one line of a forward key, as written in the config.
+    pub fn parse(key: Key, value: &str) -> Result<Self, String> {
+        let args: Vec<&str> = value.split_whitespace().collect();
+        let (listen, target) = match (key, args.as_slice()) {
+            (Key::DynamicForward, [listen]) | (Key::RemoteForward, [listen]) => (*listen, None),
+            (Key::LocalForward | Key::RemoteForward, [listen, target]) => (*listen, Some(*target)),
+            (Key::DynamicForward, _) => return Err("expected [bind_address:]port".to_owned()),
+            _ => return Err("expected a listen port or socket and a target".to_owned()),
+        };
+
+        let forward = Forward {
+            listen: parse_endpoint(listen, false)?,
+            target: target.map(|t| parse_endpoint(t, true)).transpose()?,
+        };
+        forward.validate(key)?;
+        Ok(forward)
+    }
+
+    /// Build a forward from the form fields named by [`field_names`].
+    /// Returns `None` when every field is empty.
+    pub fn from_fields(key: Key, fields: &[String]) -> Result<Option<Self>, String> {
+        let field = |i: usize| fields.get(i).map_or("", |x| x.trim());
+        if (0..fields.len()).all(|i| field(i).is_empty()) {
+            return Ok(None);
+        }
+
+        let listen = if field(1).contains('/') {
+            if !field(0).is_empty() {
+                return Err("a socket has no bind address".to_owned());
+            }
+            Endpoint::Socket(field(1).to_owned())
+        } else {
+            let host = match field(0) {
+                "" => None,
+                host => Some(parse_host(host)?),
+            };
+            Endpoint::Tcp {
+                host,
+                port: parse_port(field(1), true)?,
+            }
+        };
+
+        let target = match (field(2), field(3)) {
+            ("", "") => None,
+            (path, "") if path.contains('/') => Some(Endpoint::Socket(path.to_owned())),
+            (path, _) if path.contains('/') => {
+                return Err("a target socket has no port".to_owned());
+            }
+            ("", _) => return Err("the target host is missing".to_owned()),
+            (host, port) => Some(Endpoint::Tcp {
+                host: Some(parse_host(host)?),
+                port: parse_port(port, false)?,
+            }),
+        };
+
+        let forward = Forward { listen, target };
+        forward.validate(key)?;
+        Ok(Some(forward))
+    }
+
+    /// The form fields named by [`field_names`] holding this forward.
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields = match &self.listen {
+            Endpoint::Tcp { host, port } => {
+                vec![host.clone().unwrap_or_default(), port.to_string()]
+            }
+            Endpoint::Socket(path) => vec![String::new(), path.clone()],
+        };
+        match &self.target {
+            Some(Endpoint::Tcp { host, port }) => {
+                fields.extend([host.clone().unwrap_or_default(), port.to_string()])
+            }
+            Some(Endpoint::Socket(path)) => fields.extend([path.clone(), String::new()]),
+            None => fields.extend([String::new(), String::new()]),
+        }
+        fields
+    }
+
+    fn validate(&self, key: Key) -> Result<(), String> {
+        match (&self.listen, &self.target) {
+            (Endpoint::Tcp { port: 0, .. }, _) if key != Key::RemoteForward => {
+                Err("port 0 is only allowed for RemoteForward".to_owned())
+            }
+            (Endpoint::Socket(_), _) if key == Key::DynamicForward => {
+                Err("DynamicForward cannot listen on a socket".to_owned())
+            }
+            (_, Some(_)) if key == Key::DynamicForward => {
+                Err("DynamicForward has no target".to_owned())
+            }
+            (_, None) if key == Key::LocalForward => Err("the target is missing".to_owned()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The local TCP port this forward listens on, with its bind address.
+    /// Remote forwards listen on the server instead.
+    pub fn local_port(&self, key: Key) -> Option<(Option<&str>, u16)> {
+        match (&self.listen, key) {
+            (_, Key::RemoteForward) => None,
+            (Endpoint::Tcp { host, port }, _) => Some((host.as_deref(), *port)),
+            (Endpoint::Socket(_), _) => None,
+        }
+    }
+}
+
+/// Parse `[host:]port`, `[ipv6]:port` or a socket path. A target always has
+/// a host.
+fn parse_endpoint(text: &str, is_target: bool) -> Result<Endpoint, String> {
+    if text.contains('/') {
+        return Ok(Endpoint::Socket(text.to_owned()));
+    }
+
+    let (host, port) = if let Some(bracketed) = text.strip_prefix('[') {
+        let (host, after) = bracketed
+            .split_once(']')
+            .ok_or(format!("`{}` has an unclosed `[`", text))?;
+        let port = after
+            .strip_prefix(':')
+            .ok_or(format!("`{}` has no port after the address", text))?;
+        (Some(host), port)
+    } else {
+        match text.rsplit_once(':') {
+            Some((host, _)) if host.contains(':') => {
+                return Err(format!("IPv6 address in `{}` needs brackets", text));
+            }
+            Some((host, port)) => (Some(host), port),
+            None => (None, text),
+        }
+    };
+
+    if is_target && host.is_none() {
+        return Err(format!("`{}` has no target host", text));
+    }
+    Ok(Endpoint::Tcp {
+        host: host.map(parse_host).transpose()?,
+        // Port 0 lets the server pick the listen port of a RemoteForward.
+        port: parse_port(port, !is_target)?,
+    })
+}
+
+/// Check a host name or address typed without brackets, or with them for
+/// IPv6.
+fn parse_host(text: &str) -> Result<String, String> {
+    let host = match text.strip_prefix('[') {
+        Some(rest) => rest
+            .strip_suffix(']')
+            .ok_or(format!("`{}` has an unclosed `[`", text))?,
+        None if text.contains(']') => return Err(format!("`{}` has an unopened `]`", text)),
+        None => text,
+    };
+
+    let valid = |c: char| c.is_ascii_alphanumeric() || "-._*%".contains(c);
+    if host.contains(':') {
+        host.parse::<std::net::Ipv6Addr>()
+            .map_err(|_| format!("`{}` is not a valid IPv6 address", host))?;
+    } else if host.is_empty() || !host.chars().all(valid) {
+        return Err(format!("`{}` is not a valid host", host));
+    }
+    Ok(host.to_owned())
+}
+
+/// Parse a port, 0 only being valid with `allow_zero`.
+fn parse_port(text: &str, allow_zero: bool) -> Result<u16, String> {
+    if text.is_empty() {
+        return Err("the port is missing".to_owned());
+    }
+    match text.parse::<u32>() {
+        Ok(0) if allow_zero => Ok(0),
+        Ok(port @ 1..=65535) => Ok(port as u16),
+        Ok(_) => Err(format!("port {} is out of range (1-65535)", text)),
+        Err(_) => Err(format!("`{}` is not a port number", text)),
+    }
+}
+
+/// The forwards of `key` in a block, skipping lines that do not parse.
+pub fn forwards(cfg: &Config, key: Key) -> Vec<Forward> {
+    cfg.columns
+        .get(&key)
+        .into_iter()
+        .flat_map(|v| v.lines())
+        .filter_map(|line| Forward::parse(key, line).ok())
+        .collect()
+}
+
+/// Other blocks binding the same local port as `forward`, on an address
+/// that overlaps with its own.
+pub fn conflicts(configs: &[Config], config_idx: usize, key: Key, forward: &Forward) -> Vec<usize> {
+    let Some((address, port)) = forward.local_port(key) else {
+        return vec![];
+    };
+
+    let mut result = vec![];
+    for (i, cfg) in configs.iter().enumerate().filter(|(i, _)| *i != config_idx) {
+        let clash = [Key::LocalForward, Key::DynamicForward]
+            .into_iter()
+            .any(|k| {
+                forwards(cfg, k).iter().any(|other| {
+                    other
+                        .local_port(k)
+                        .is_some_and(|(a, p)| p == port && overlaps(a, address))
+                })
+            });
+        if clash {
+            result.push(i);
+        }
+    }
+    result
+}
+
+/// Whether two bind addresses can clash. Without one, ssh binds the
+/// loopback address.
+fn overlaps(a: Option<&str>, b: Option<&str>) -> bool {
+    fn normalize(x: Option<&str>) -> Option<&str> {
+        match x {
+            None | Some("localhost") | Some("127.0.0.1") | Some("::1") => Some("localhost"),
+            Some("*") | Some("") | Some("0.0.0.0") | Some("::") => None,
+            Some(other) => Some(other),
+        }
+    }
+    match (normalize(a), normalize(b)) {
+        (None, _) | (_, None) => true,
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(key: Key, value: &str) -> Result<String, String> {
+        Forward::parse(key, value).map(|f| f.to_string())
+    }
+
+    #[test]
+    fn parses_forwards() {
+        let local = |v| parse(Key::LocalForward, v);
+        assert_eq!(
+            local("8080 localhost:80"),
+            Ok("8080 localhost:80".to_owned())
+        );
+        assert_eq!(
+            local("127.0.0.1:8080 db:5432"),
+            Ok("127.0.0.1:8080 db:5432".to_owned())
+        );
+        assert_eq!(
+            local("/tmp/app.sock /run/app.sock"),
+            Ok("/tmp/app.sock /run/app.sock".to_owned())
+        );
+        assert_eq!(parse(Key::DynamicForward, "1080"), Ok("1080".to_owned()));
+        assert_eq!(parse(Key::RemoteForward, "9000"), Ok("9000".to_owned()));
+        assert!(local("8080").is_err());
+        assert!(parse(Key::DynamicForward, "1080 host:80").is_err());
+    }
+
+    #[test]
+    fn checks_ipv6_brackets() {
+        let local = |v| parse(Key::LocalForward, v);
+        assert_eq!(
+            local("[::1]:8080 [fe80::1]:80"),
+            Ok("[::1]:8080 [fe80::1]:80".to_owned())
+        );
+        assert_eq!(
+            local("::1:8080 host:80"),
+            Err("IPv6 address in `::1:8080` needs brackets".to_owned())
+        );
+        assert_eq!(
+            local("[::1:8080 host:80"),
+            Err("`[::1:8080` has an unclosed `[`".to_owned())
+        );
+        assert_eq!(
+            local("[::1] host:80"),
+            Err("`[::1]` has no port after the address".to_owned())
+        );
+        assert_eq!(
+            local("[::g]:8080 host:80"),
+            Err("`::g` is not a valid IPv6 address".to_owned())
+        );
+    }
+
+    #[test]
+    fn checks_port_ranges() {
+        let local = |v| parse(Key::LocalForward, v);
+        assert!(local("1 host:65535").is_ok());
+        assert_eq!(
+            local("65536 host:80"),
+            Err("port 65536 is out of range (1-65535)".to_owned())
+        );
+        assert_eq!(
+            local("8080 host:0"),
+            Err("port 0 is out of range (1-65535)".to_owned())
+        );
+        assert_eq!(
+            local("0 host:80"),
+            Err("port 0 is only allowed for RemoteForward".to_owned())
+        );
+        assert_eq!(
+            local("8080 host:http"),
+            Err("`http` is not a port number".to_owned())
+        );
+        assert_eq!(
+            parse(Key::RemoteForward, "0 localhost:22"),
+            Ok("0 localhost:22".to_owned())
+        );
+    }
+
+    #[test]
+    fn builds_forwards_from_fields() {
+        let fields = |f: &[&str]| f.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        let forward = Forward::from_fields(Key::LocalForward, &fields(&["", "8080", "::1", "80"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(forward.to_string(), "8080 [::1]:80");
+        assert_eq!(forward.fields(), fields(&["", "8080", "::1", "80"]));
+        assert!(Forward::from_fields(Key::LocalForward, &fields(&["", "8080", "h", "0"])).is_err());
+        assert!(
+            Forward::from_fields(Key::LocalForward, &fields(&["", "", "", ""]))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
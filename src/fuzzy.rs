@@ -0,0 +1,104 @@
+/// Score `candidate` against `query` as a case-insensitive subsequence match.
+///
+/// Returns `None` if not every character of `query` appears in `candidate` in
+/// order. Higher scores are better: consecutive characters, matches at the
+/// start and matches on a word boundary (an uppercase letter in CamelCase
+/// keywords, or the character after `-`, `_`, `.` or a space) are rewarded.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev_match: Option<usize> = None;
+
+    for q in query.chars() {
+        let q = q.to_ascii_lowercase();
+        let found = (pos..chars.len()).find(|&i| chars[i].to_ascii_lowercase() == q)?;
+
+        score += 1;
+        if found == 0 {
+            score += 8;
+        } else if chars[found].is_uppercase() || matches!(chars[found - 1], '-' | '_' | '.' | ' ') {
+            score += 5;
+        }
+        if prev_match.is_some_and(|p| p + 1 == found) {
+            score += 4;
+        }
+        score -= (found - pos) as i64;
+
+        prev_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
+
+/// Filter `items` by `query` and return their indices, best match first.
+/// Ties keep the original order.
+pub fn filter<'a, I>(query: &str, items: I) -> Vec<usize>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut scored: Vec<(usize, i64)> = items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, s)| score(query, s).map(|x| (i, x)))
+        .collect();
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert_eq!(score("", "HostName"), Some(0));
+        assert!(score("hn", "HostName").is_some());
+        assert!(score("HOSTNAME", "hostname").is_some());
+        assert_eq!(score("nh", "HostName"), None);
+        assert_eq!(score("hostnames", "HostName"), None);
+    }
+
+    #[test]
+    fn rewards_consecutive_characters() {
+        assert!(score("ab", "xaby") > score("ab", "xaxb"));
+    }
+
+    #[test]
+    fn rewards_word_boundaries() {
+        assert!(score("a", "x-a") > score("a", "xxa"));
+        assert!(score("a", "x_a") > score("a", "xxa"));
+        assert!(score("a", "xA") > score("a", "xa"));
+        assert!(score("ka", "Key Agent") > score("ka", "Keyagent"));
+    }
+
+    #[test]
+    fn penalizes_gaps() {
+        assert!(score("a", "xa") > score("a", "xxxa"));
+        assert!(score("a", "a") > score("a", "xa"));
+    }
+
+    #[test]
+    fn ranks_prefixes_and_runs_first() {
+        let keys = [
+            "PubkeyAuthentication",
+            "ProxyUseFdpass",
+            "PasswordAuthentication",
+        ];
+        assert_eq!(filter("pa", keys), [2, 0, 1]);
+
+        let keys = ["CheckHostIP", "HostName", "HostbasedAuthentication"];
+        assert_eq!(filter("host", keys), [1, 2, 0]);
+    }
+
+    #[test]
+    fn keeps_the_order_of_ties_and_drops_misses() {
+        assert_eq!(filter("", ["b", "a", "c"]), [0, 1, 2]);
+        assert_eq!(filter("x", ["ax", "c", "bx"]), [0, 2]);
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+
+use crate::sshconfig::{Config, Key};
+
+/// How the host list is organised.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Grouping {
+    #[default]
+    None,
+    Tag,
+    Domain,
+}
+
+/// A line of the host list: either a section header or a host, given by its
+/// index in the config. With tag grouping a host shows up once per tag.
+pub enum Row {
+    Group {
+        name: String,
+        count: usize,
+        collapsed: bool,
+    },
+    Host(usize),
+}
+
+impl Grouping {
+    pub fn next(self) -> Self {
+        match self {
+            Grouping::None => Grouping::Tag,
+            Grouping::Tag => Grouping::Domain,
+            Grouping::Domain => Grouping::None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Grouping::None => "none",
+            Grouping::Tag => "tag",
+            Grouping::Domain => "domain",
+        }
+    }
+
+    /// The sections `cfg` belongs to.
+    fn groups(&self, cfg: &Config) -> Vec<String> {
+        match self {
+            Grouping::None => vec![],
+            Grouping::Tag => {
+                let tags = cfg.tags();
+                if tags.is_empty() {
+                    vec!["(untagged)".to_owned()]
+                } else {
+                    tags
+                }
+            }
+            Grouping::Domain => vec![domain(cfg)],
+        }
+    }
+}
+
+/// Build the rows of the host list. Only hosts carrying every tag of
+/// `filter` are kept, and hosts of `collapsed` sections are hidden.
+pub fn rows(
+    configs: &[Config],
+    grouping: Grouping,
+    collapsed: &HashSet<String>,
+    filter: &[String],
+) -> Vec<Row> {
+    let visible: Vec<usize> = configs
+        .iter()
+        .enumerate()
+        .filter(|(_, cfg)| {
+            let tags = cfg.tags();
+            filter.iter().all(|f| tags.iter().any(|t| t == f))
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    if grouping == Grouping::None {
+        return visible.into_iter().map(Row::Host).collect();
+    }
+
+    let mut names: Vec<String> = visible
+        .iter()
+        .flat_map(|i| grouping.groups(&configs[*i]))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let mut result = vec![];
+    for name in names {
+        let members: Vec<usize> = visible
+            .iter()
+            .copied()
+            .filter(|i| grouping.groups(&configs[*i]).contains(&name))
+            .collect();
+        let is_collapsed = collapsed.contains(&name);
+        result.push(Row::Group {
+            count: members.len(),
+            collapsed: is_collapsed,
+            name,
+        });
+        if !is_collapsed {
+            result.extend(members.into_iter().map(Row::Host));
+        }
+    }
+    result
+}
+
+/// Domain of a host: its `HostName`, or the alias when there is none,
+/// without the first label. Addresses and single-label names have their own
+/// sections.
+fn domain(cfg: &Config) -> String {
+    let name = cfg
+        .columns
+        .get(&Key::HostName)
+        .map(|x| x.as_str())
+        .unwrap_or_else(|| cfg.host.split_whitespace().next().unwrap_or_default());
+
+    if name.contains(['*', '?']) {
+        return "(patterns)".to_owned();
+    }
+    if name.parse::<std::net::IpAddr>().is_ok() {
+        return "(addresses)".to_owned();
+    }
+    match name.split_once('.') {
+        Some((_, rest)) if !rest.is_empty() => rest.to_ascii_lowercase(),
+        _ => "(no domain)".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sshconfigfile;
+
+    const CONFIG: &str = "\
+# sshe: tags=prod,db
+Host db1
+  HostName db1.example.com
+# sshe: tags=prod
+Host web1
+  HostName 10.0.0.1
+Host laptop
+Host *.example.org
+";
+
+    /// The rows as `[name count]` headers, with `+` when collapsed, and host
+    /// names.
+    fn rendered(grouping: Grouping, collapsed: &[&str], filter: &[&str]) -> Vec<String> {
+        let configs = sshconfigfile::parse(CONFIG.as_bytes()).unwrap();
+        let collapsed: HashSet<String> = collapsed.iter().map(|x| x.to_string()).collect();
+        let filter: Vec<String> = filter.iter().map(|x| x.to_string()).collect();
+        rows(&configs, grouping, &collapsed, &filter)
+            .iter()
+            .map(|row| match row {
+                Row::Group {
+                    name,
+                    count,
+                    collapsed,
+                } => format!("[{} {}{}]", name, count, if *collapsed { " +" } else { "" }),
+                Row::Host(i) => configs[*i].host.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lists_hosts_without_grouping() {
+        assert_eq!(
+            rendered(Grouping::None, &[], &[]),
+            ["db1", "web1", "laptop", "*.example.org"]
+        );
+    }
+
+    #[test]
+    fn groups_by_tag() {
+        assert_eq!(
+            rendered(Grouping::Tag, &[], &[]),
+            [
+                "[(untagged) 2]",
+                "laptop",
+                "*.example.org",
+                "[db 1]",
+                "db1",
+                "[prod 2]",
+                "db1",
+                "web1",
+            ]
+        );
+    }
+
+    #[test]
+    fn hides_collapsed_sections() {
+        assert_eq!(
+            rendered(Grouping::Tag, &["prod", "(untagged)"], &[]),
+            ["[(untagged) 2 +]", "[db 1]", "db1", "[prod 2 +]"]
+        );
+    }
+
+    #[test]
+    fn filters_by_every_tag() {
+        assert_eq!(rendered(Grouping::None, &[], &["prod"]), ["db1", "web1"]);
+        assert_eq!(rendered(Grouping::None, &[], &["prod", "db"]), ["db1"]);
+        assert_eq!(
+            rendered(Grouping::Tag, &[], &["db"]),
+            ["[db 1]", "db1", "[prod 1]", "db1"]
+        );
+    }
+
+    #[test]
+    fn groups_by_domain() {
+        assert_eq!(
+            rendered(Grouping::Domain, &[], &[]),
+            [
+                "[(addresses) 1]",
+                "web1",
+                "[(no domain) 1]",
+                "laptop",
+                "[(patterns) 1]",
+                "*.example.org",
+                "[example.com 1]",
+                "db1",
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_through_groupings() {
+        let mut grouping = Grouping::default();
+        let mut names = vec![];
+        for _ in 0..4 {
+            names.push(grouping.name());
+            grouping = grouping.next();
+        }
+        assert_eq!(names, ["none", "tag", "domain", "none"]);
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    fs, io,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::Path,
+};
+
+/// Key types offered for a new key, the first one being the default.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Ed25519,
+    Ecdsa,
+    Rsa,
+}
+
+impl KeyType {
+    pub const ALL: &'static [KeyType] = &[KeyType::Ed25519, KeyType::Ecdsa, KeyType::Rsa];
+
+    /// The name `ssh-keygen -t` takes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "ed25519",
+            KeyType::Ecdsa => "ecdsa",
+            KeyType::Rsa => "rsa",
+        }
+    }
+}
+
+/// Where a new key for `alias` goes by default.
+pub fn default_file(alias: &str, key_type: KeyType) -> String {
+    format!("~/.ssh/id_{}_{}", key_type.name(), alias)
+}
+
+/// `user@machine`, as ssh-keygen comments keys by default.
+pub fn default_comment() -> String {
+    let user = std::env::var("USER").unwrap_or_default();
+    let machine = fs::read_to_string("/etc/hostname").unwrap_or_default();
+    match (user.as_str(), machine.trim()) {
+        ("", _) | (_, "") => String::new(),
+        (user, machine) => format!("{}@{}", user, machine),
+    }
+}
+
+/// The ssh-keygen command creating the key. It asks for the passphrase
+/// itself, so sshe never sees it.
+pub fn command_line(key_type: KeyType, file: &Path, comment: &str) -> Vec<String> {
+    let mut line = vec![
+        "ssh-keygen".to_owned(),
+        "-t".to_owned(),
+        key_type.name().to_owned(),
+    ];
+    if key_type == KeyType::Rsa {
+        line.extend(["-b".to_owned(), "4096".to_owned()]);
+    }
+    line.extend([
+        "-f".to_owned(),
+        file.to_string_lossy().into_owned(),
+        "-C".to_owned(),
+        comment.to_owned(),
+    ]);
+    line
+}
+
+/// Make sure the key can be written to `file` without replacing another
+/// one, creating its directory private to the user when missing.
+pub fn prepare(file: &Path) -> Result<(), String> {
+    if file.exists() {
+        return Err(format!("{} already exists", file.display()));
+    }
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.exists() => fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .map_err(|e| format!("Unable to create {}: {}", dir.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+/// Restrict the private key to the user, ssh refuses it otherwise, and
+/// let anyone read the public key.
+pub fn fix_permissions(file: &Path) -> io::Result<()> {
+    fs::set_permissions(file, fs::Permissions::from_mode(0o600))?;
+    let mut public = file.as_os_str().to_owned();
+    public.push(".pub");
+    fs::set_permissions(public, fs::Permissions::from_mode(0o644))
+}
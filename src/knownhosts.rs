@@ -0,0 +1,380 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::{
+    reachability,
+    settings::{contract_tilde, expand_tilde},
+    sshconfig::{self, Config, Key},
+    topology,
+};
+
+/// The `@` marker at the start of a known_hosts line.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    CertAuthority,
+    Revoked,
+}
+
+/// Host names of a known_hosts line.
+#[derive(Clone)]
+pub enum Hosts {
+    /// Comma separated patterns, `[host]:port` when not on port 22.
+    Patterns(Vec<String>),
+    /// `|1|salt|hash`: the HMAC-SHA1 of the name keyed with the salt.
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// A host key line of a known_hosts file.
+#[derive(Clone)]
+pub struct Entry {
+    pub file: PathBuf,
+    /// 1-based line number in `file`.
+    pub line: usize,
+    /// The line as read, to find it again if the file changed.
+    pub text: String,
+    pub marker: Option<Marker>,
+    pub hosts: Hosts,
+    pub key_type: String,
+}
+
+impl Entry {
+    /// Whether ssh uses this line for `host` on `port`. Negated patterns
+    /// win over the others, as with ssh.
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        let host = host.to_ascii_lowercase();
+        let name = if port == 22 {
+            host
+        } else {
+            format!("[{}]:{}", host, port)
+        };
+        match &self.hosts {
+            Hosts::Hashed { salt, hash } => {
+                let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(salt) else {
+                    return false;
+                };
+                mac.update(name.as_bytes());
+                mac.verify_slice(hash).is_ok()
+            }
+            Hosts::Patterns(patterns) => {
+                let mut matched = false;
+                for pattern in patterns {
+                    match pattern.strip_prefix('!') {
+                        Some(p) if sshconfig::glob_match(p, &name) => return false,
+                        Some(_) => {}
+                        None => matched |= sshconfig::glob_match(pattern, &name),
+                    }
+                }
+                matched
+            }
+        }
+    }
+
+    /// `file:line`, with the home directory as `~`.
+    pub fn location(&self) -> String {
+        format!("{}:{}", contract_tilde(&self.file), self.line)
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.marker {
+            Some(Marker::CertAuthority) => write!(f, "@cert-authority ")?,
+            Some(Marker::Revoked) => write!(f, "@revoked ")?,
+            None => {}
+        }
+        match &self.hosts {
+            Hosts::Patterns(patterns) => write!(f, "{}", patterns.join(","))?,
+            Hosts::Hashed { .. } => write!(f, "(hashed)")?,
+        }
+        write!(f, " {}", self.key_type)
+    }
+}
+
+/// Parse the host key lines of a known_hosts file. Comments, blank lines
+/// and lines that are not understood are skipped.
+pub fn parse(file: &Path, text: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let Some(mut first) = fields.next() else {
+            continue;
+        };
+        if first.starts_with('#') {
+            continue;
+        }
+
+        let marker = match first {
+            "@cert-authority" => Some(Marker::CertAuthority),
+            "@revoked" => Some(Marker::Revoked),
+            _ => None,
+        };
+        if marker.is_some() {
+            let Some(next) = fields.next() else {
+                continue;
+            };
+            first = next;
+        }
+        // The key itself follows the type.
+        let (Some(key_type), Some(_)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(hosts) = parse_hosts(first) else {
+            continue;
+        };
+
+        entries.push(Entry {
+            file: file.to_owned(),
+            line: i + 1,
+            text: line.trim_end().to_owned(),
+            marker,
+            hosts,
+            key_type: key_type.to_owned(),
+        });
+    }
+    entries
+}
+
+fn parse_hosts(field: &str) -> Option<Hosts> {
+    match field.strip_prefix("|1|") {
+        Some(hashed) => {
+            let (salt, hash) = hashed.split_once('|')?;
+            Some(Hosts::Hashed {
+                salt: STANDARD.decode(salt).ok()?,
+                hash: STANDARD.decode(hash).ok()?,
+            })
+        }
+        None => Some(Hosts::Patterns(
+            field.split(',').map(|p| p.to_ascii_lowercase()).collect(),
+        )),
+    }
+}
+
+/// The user known_hosts files: the default ones and every one set with
+/// `UserKnownHostsFile` in the config.
+pub fn files(configs: &[Config]) -> Vec<PathBuf> {
+    let default = Key::UserKnownHostsFile.default_value().unwrap_or("");
+    let values = configs
+        .iter()
+        .filter_map(|c| c.columns.get(&Key::UserKnownHostsFile))
+        .map(|v| v.as_str());
+
+    let mut files: Vec<PathBuf> = vec![];
+    for file in [default]
+        .into_iter()
+        .chain(values)
+        .flat_map(str::split_whitespace)
+    {
+        let file = expand_tilde(file);
+        if file != Path::new("/dev/null") && !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+/// The entries of every file of `files`. Missing files are skipped.
+pub fn load(files: &[PathBuf]) -> io::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for file in files {
+        match fs::read_to_string(file) {
+            Ok(text) => entries.extend(parse(file, &text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(entries)
+}
+
+/// The name ssh looks up for `alias`: its `HostKeyAlias`, or else its
+/// `HostName`, with its port.
+pub fn lookup(configs: &[Config], alias: &str) -> Option<(String, u16)> {
+    let target = reachability::target(configs, alias).ok()?;
+    let host_key_alias = sshconfig::effective(configs, alias)
+        .into_iter()
+        .find(|(k, _, _)| *k == Key::HostKeyAlias)
+        .map(|(_, v, _)| v.trim().to_owned());
+    Some((host_key_alias.unwrap_or(target.host), target.port))
+}
+
+/// The entries ssh uses for `alias`.
+pub fn matching<'a>(entries: &'a [Entry], configs: &[Config], alias: &str) -> Vec<&'a Entry> {
+    let Some((host, port)) = lookup(configs, alias) else {
+        return vec![];
+    };
+    entries.iter().filter(|e| e.matches(&host, port)).collect()
+}
+
+/// Host key lines no host of the config uses. `@cert-authority` and
+/// `@revoked` lines are not tied to a host and never orphaned.
+pub fn orphans<'a>(entries: &'a [Entry], configs: &[Config]) -> Vec<&'a Entry> {
+    let names: Vec<(String, u16)> = topology::aliases(configs)
+        .into_iter()
+        .filter_map(|alias| lookup(configs, alias))
+        .collect();
+    entries
+        .iter()
+        .filter(|e| e.marker.is_none())
+        .filter(|e| !names.iter().any(|(host, port)| e.matches(host, *port)))
+        .collect()
+}
+
+/// Remove `entries` from their files. Each file changed is first copied to
+/// `<file>.old`, like `ssh-keygen -R` does.
+///
+/// The files may have changed since the entries were read, by `ssh-keygen
+/// -R` for instance. An entry no longer at its line is looked for by its
+/// text, and nothing is removed when one is gone.
+pub fn remove(entries: &[&Entry]) -> io::Result<()> {
+    let mut files: Vec<&Path> = vec![];
+    for entry in entries {
+        if !files.contains(&entry.file.as_path()) {
+            files.push(&entry.file);
+        }
+    }
+
+    let mut changes = vec![];
+    for file in files {
+        let text = fs::read_to_string(file)?;
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut dropped: Vec<usize> = vec![];
+        for entry in entries.iter().filter(|e| e.file == file) {
+            let is_entry = |i: &usize| {
+                !dropped.contains(i) && lines.get(*i).is_some_and(|l| l.trim_end() == entry.text)
+            };
+            let found = Some(entry.line - 1)
+                .filter(is_entry)
+                .or_else(|| (0..lines.len()).find(is_entry));
+            match found {
+                Some(i) => dropped.push(i),
+                None => {
+                    return Err(io::Error::other(format!(
+                        "{} is no longer in the file, it changed",
+                        entry.location()
+                    )))
+                }
+            }
+        }
+        let kept: String = lines
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !dropped.contains(i))
+            .map(|(_, line)| *line)
+            .collect();
+        changes.push((file, kept));
+    }
+
+    for (file, kept) in changes {
+        let mut old = file.as_os_str().to_owned();
+        old.push(".old");
+        fs::copy(file, old)?;
+        fs::write(file, kept)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOdXyM/J7Xku4Rx1528OVqvXiC2a2aSVAAorgwD8jF3o";
+
+    /// A known_hosts file with `text` in a fresh directory.
+    fn file(name: &str, text: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sshe-knownhosts-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("known_hosts");
+        fs::write(&file, text).unwrap();
+        file
+    }
+
+    /// `ssh-keygen -H` of `web.example.com` and `[db.example.com]:2222`.
+    const HASHED: &str = "\
+|1|57Zplu3+JBqPAjAfn5vhFzhINb8=|Xjob6ZctuMuo15DmpTR4Jd0eBt0= ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOdXyM/J7Xku4Rx1528OVqvXiC2a2aSVAAorgwD8jF3o
+|1|bfvaSpAevuOTg/XN0rWOaiwZX/Q=|U2oH32k9aKZDkEiBZMdf4udkdtg= ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOdXyM/J7Xku4Rx1528OVqvXiC2a2aSVAAorgwD8jF3o
+";
+
+    #[test]
+    fn matches_hashed_entries() {
+        let entries = parse(Path::new("known_hosts"), HASHED);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].matches("web.example.com", 22));
+        assert!(entries[0].matches("WEB.example.com", 22));
+        assert!(!entries[0].matches("web.example.com", 2222));
+        assert!(!entries[0].matches("db.example.com", 22));
+        assert!(entries[1].matches("db.example.com", 2222));
+        assert!(!entries[1].matches("db.example.com", 22));
+        assert_eq!(entries[0].to_string(), "(hashed) ssh-ed25519");
+
+        let text = "Host db\n  HostName db.example.com\n  Port 2222\n";
+        let configs = crate::sshconfigfile::parse(text.as_bytes()).unwrap();
+        let matched = matching(&entries, &configs, "db");
+        assert!(matched.len() == 1 && matched[0].line == 2);
+        let orphans = orphans(&entries, &configs);
+        assert!(orphans.len() == 1 && orphans[0].line == 1);
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let text = format!(
+            "*.example.com,!bad.example.com {0}\n\
+             [git.example.com]:2222 {0}\n\
+             @cert-authority *.example.com {0}\n\
+             @revoked * {0}\n\
+             # comment\n\
+             broken-line\n",
+            KEY
+        );
+        let entries = parse(Path::new("known_hosts"), &text);
+        assert_eq!(entries.len(), 4);
+
+        let wildcard = &entries[0];
+        assert!(wildcard.matches("web.example.com", 22));
+        assert!(!wildcard.matches("bad.example.com", 22));
+        assert!(!wildcard.matches("web.example.com", 2200));
+
+        let ported = &entries[1];
+        assert!(ported.matches("git.example.com", 2222));
+        assert!(!ported.matches("git.example.com", 22));
+
+        assert!(entries[2].marker == Some(Marker::CertAuthority));
+        assert!(entries[3].marker == Some(Marker::Revoked));
+        assert_eq!(
+            entries[2].to_string(),
+            "@cert-authority *.example.com ssh-ed25519"
+        );
+        assert_eq!(entries[3].line, 4);
+    }
+
+    #[test]
+    fn removes_moved_entries() {
+        let text = format!("a {0}\nb {0}\nc {0}\n", KEY);
+        let file = file("moved", &text);
+        let entries = parse(&file, &text);
+
+        // Another tool removed the first line since the file was read.
+        fs::write(&file, format!("b {0}\nc {0}\n", KEY)).unwrap();
+        remove(&[&entries[1]]).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), format!("c {}\n", KEY));
+    }
+
+    #[test]
+    fn keeps_files_whose_entry_is_gone() {
+        let text = format!("a {0}\nb {0}\n", KEY);
+        let file = file("gone", &text);
+        let entries = parse(&file, &text);
+
+        let changed = format!("a {0}\nc {0}\n", KEY);
+        fs::write(&file, &changed).unwrap();
+        assert!(remove(&[&entries[0], &entries[1]]).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), changed);
+    }
+}
@@ -0,0 +1,566 @@
+use std::path::PathBuf;
+
+use crate::{
+    sshconfig::{self, Config, Key},
+    topology::{self, Problem},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+macro_rules! rules {
+    ($($rule:ident => $id:literal, $severity:ident, $summary:literal),* $(,)?) => {
+        /// A check run by [`lint`]. The ID is what `# sshe: ignore=` takes and
+        /// must not change.
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        pub enum Rule {
+            $($rule,)*
+        }
+
+        pub const ALL_RULES: &[Rule] = &[$(Rule::$rule,)*];
+
+        impl Rule {
+            pub fn id(&self) -> &'static str {
+                match self {
+                    $(Rule::$rule => $id,)*
+                }
+            }
+
+            pub fn severity(&self) -> Severity {
+                match self {
+                    $(Rule::$rule => Severity::$severity,)*
+                }
+            }
+
+            pub fn summary(&self) -> &'static str {
+                match self {
+                    $(Rule::$rule => $summary,)*
+                }
+            }
+        }
+    };
+}
+
+rules!(
+    DuplicateHost => "duplicate-host", Warning,
+        "A host pattern already used by an earlier block",
+    ShadowedKey => "shadowed-key", Warning,
+        "A key that an earlier block matching the same hosts already sets",
+    WildcardFirst => "wildcard-first", Warning,
+        "`Host *` placed before more specific blocks",
+    MissingIdentity => "missing-identity", Error,
+        "An IdentityFile that does not exist",
+    IdentityPermissions => "identity-permissions", Error,
+        "An IdentityFile readable by other users, which ssh refuses",
+    DeprecatedKey => "deprecated-key", Warning,
+        "A keyword that OpenSSH renamed or removed",
+    Contradiction => "contradiction", Warning,
+        "Keys that contradict each other",
+    JumpHost => "jump-host", Error,
+        "A ProxyJump or ProxyCommand chain that cannot work",
+);
+
+/// Something [`lint`] found in the block at `config_idx`, about `key` when
+/// it comes from a single key.
+pub struct Finding {
+    pub rule: Rule,
+    pub config_idx: usize,
+    pub key: Option<Key>,
+    pub message: String,
+}
+
+impl Finding {
+    /// Where the finding is: its block, and its key if any.
+    pub fn location(&self, configs: &[Config]) -> String {
+        let host = &configs[self.config_idx].host;
+        match self.key {
+            Some(key) => format!("Host {}, {}", host, key.str()),
+            None => format!("Host {}", host),
+        }
+    }
+}
+
+/// Run every rule on the config. Findings silenced by an `ignore=`
+/// directive are left out, the others are sorted by block.
+pub fn lint(configs: &[Config]) -> Vec<Finding> {
+    let mut findings = vec![];
+    duplicate_hosts(configs, &mut findings);
+    shadowed_keys(configs, &mut findings);
+    wildcard_first(configs, &mut findings);
+    identity_files(configs, &mut findings);
+    deprecated_keys(configs, &mut findings);
+    contradictions(configs, &mut findings);
+    jump_hosts(configs, &mut findings);
+
+    findings.retain(|f| {
+        let ignored = configs[f.config_idx].ignored(f.key);
+        !ignored.iter().any(|x| *x == f.rule.id() || *x == "all")
+    });
+    findings.sort_by_key(|f| f.config_idx);
+    findings
+}
+
+fn finding(rule: Rule, config_idx: usize, key: Option<Key>, message: String) -> Finding {
+    Finding {
+        rule,
+        config_idx,
+        key,
+        message,
+    }
+}
+
+fn duplicate_hosts(configs: &[Config], findings: &mut Vec<Finding>) {
+    for (i, cfg) in configs.iter().enumerate() {
+        for pattern in cfg.host.split_whitespace() {
+            let earlier = configs[..i].iter().find(|c| {
+                c.host
+                    .split_whitespace()
+                    .any(|p| p.eq_ignore_ascii_case(pattern))
+            });
+            if let Some(earlier) = earlier {
+                let message = format!("`{}` is already used by Host {}", pattern, earlier.host);
+                findings.push(finding(Rule::DuplicateHost, i, None, message));
+            }
+        }
+    }
+}
+
+/// Whether every host `inner` applies to is matched by `outer` too.
+fn covers(outer: &Config, inner: &Config) -> bool {
+    let outer_patterns: Vec<&str> = outer.host.split_whitespace().collect();
+    if outer_patterns.iter().any(|p| p.starts_with('!')) {
+        return false;
+    }
+    inner
+        .host
+        .split_whitespace()
+        .filter(|p| !p.starts_with('!'))
+        .all(|p| outer_patterns.iter().any(|o| sshconfig::glob_match(o, p)))
+}
+
+fn shadowed_keys(configs: &[Config], findings: &mut Vec<Finding>) {
+    for (i, cfg) in configs.iter().enumerate() {
+        let mut shadowed = vec![];
+        for key in sshconfig::ALL_KEYS
+            .iter()
+            .filter(|k| cfg.columns.contains_key(k))
+        {
+            // Their values add up instead of the first one winning.
+            if key.is_repeatable() {
+                continue;
+            }
+            let earlier = configs[..i]
+                .iter()
+                .find(|c| c.columns.contains_key(key) && covers(c, cfg));
+            if let Some(earlier) = earlier {
+                shadowed.push((*key, earlier));
+            }
+        }
+
+        let all = shadowed.len() == cfg.columns.len();
+        for (key, earlier) in shadowed {
+            let message = if all {
+                format!(
+                    "never used, Host {} above already sets every key of this block",
+                    earlier.host
+                )
+            } else {
+                format!("never used, Host {} above already sets it", earlier.host)
+            };
+            findings.push(finding(Rule::ShadowedKey, i, Some(key), message));
+        }
+    }
+}
+
+fn wildcard_first(configs: &[Config], findings: &mut Vec<Finding>) {
+    let last = configs.len().saturating_sub(1);
+    for (i, cfg) in configs.iter().enumerate().take(last) {
+        if cfg.host.split_whitespace().any(|p| p == "*") {
+            let message =
+                "comes before other blocks, which cannot override the keys it sets".to_owned();
+            findings.push(finding(Rule::WildcardFirst, i, None, message));
+        }
+    }
+}
+
+/// Expand a leading `~` and the `%d` token. Paths with other tokens
+/// depend on the connection and cannot be checked.
+fn resolve_path(path: &str) -> Option<PathBuf> {
+    let home = home::home_dir()?;
+    let path = path.replace("%d", &home.to_string_lossy());
+    if path.contains('%') || path.contains("${") {
+        return None;
+    }
+    match path.strip_prefix("~/") {
+        Some(rest) => Some(home.join(rest)),
+        None => Some(PathBuf::from(path)),
+    }
+}
+
+fn identity_files(configs: &[Config], findings: &mut Vec<Finding>) {
+    for (i, cfg) in configs.iter().enumerate() {
+        let Some(values) = cfg.columns.get(&Key::IdentityFile) else {
+            continue;
+        };
+        for value in values.lines() {
+            identity_file(i, value, findings);
+        }
+    }
+}
+
+fn identity_file(i: usize, value: &str, findings: &mut Vec<Finding>) {
+    let value = value.trim().trim_matches('"');
+    if value.eq_ignore_ascii_case("none") {
+        return;
+    }
+    let Some(path) = resolve_path(value) else {
+        return;
+    };
+
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        let message = format!("{} does not exist", path.display());
+        findings.push(finding(
+            Rule::MissingIdentity,
+            i,
+            Some(Key::IdentityFile),
+            message,
+        ));
+        return;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            let message = format!(
+                "permissions {:04o} of {} are too open, run `chmod 600` on it",
+                mode,
+                path.display()
+            );
+            findings.push(finding(
+                Rule::IdentityPermissions,
+                i,
+                Some(Key::IdentityFile),
+                message,
+            ));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+}
+
+fn deprecated_keys(configs: &[Config], findings: &mut Vec<Finding>) {
+    for (i, cfg) in configs.iter().enumerate() {
+        for key in sshconfig::ALL_KEYS
+            .iter()
+            .filter(|k| cfg.columns.contains_key(k))
+        {
+            let Some(deprecation) = key.deprecation() else {
+                continue;
+            };
+            let reason = deprecation.reason;
+            let message = match deprecation.replacement {
+                Some(new) => format!("{}, use {}", reason, new.str()),
+                None => format!("{}, remove it", reason),
+            };
+            findings.push(finding(Rule::DeprecatedKey, i, Some(*key), message));
+        }
+    }
+}
+
+fn contradictions(configs: &[Config], findings: &mut Vec<Finding>) {
+    for (i, cfg) in configs.iter().enumerate() {
+        if cfg.columns.contains_key(&Key::ProxyJump) && cfg.columns.contains_key(&Key::ProxyCommand)
+        {
+            let message = "ProxyCommand is set too, only the first of the two is used".to_owned();
+            findings.push(finding(
+                Rule::Contradiction,
+                i,
+                Some(Key::ProxyJump),
+                message,
+            ));
+        }
+
+        // Judge on what ssh uses for the first concrete alias of the block,
+        // since the IdentityFile may come from another block.
+        let Some(alias) = topology::aliases(std::slice::from_ref(cfg))
+            .first()
+            .copied()
+        else {
+            continue;
+        };
+        let values = sshconfig::effective(configs, alias);
+        let get = |key: Key| {
+            values
+                .iter()
+                .find(|(k, _, _)| *k == key)
+                .map(|(_, v, from)| (v.as_str(), *from))
+        };
+        if let (Some((only, from)), None) = (get(Key::IdentitiesOnly), get(Key::IdentityFile)) {
+            if only.eq_ignore_ascii_case("yes") {
+                let key = (from == i).then_some(Key::IdentitiesOnly);
+                let message =
+                    "IdentitiesOnly yes without an IdentityFile ignores every agent key".to_owned();
+                findings.push(finding(Rule::Contradiction, i, key, message));
+            }
+        }
+    }
+}
+
+fn jump_hosts(configs: &[Config], findings: &mut Vec<Finding>) {
+    let mut reported: Vec<Vec<String>> = vec![];
+    for (i, cfg) in configs.iter().enumerate() {
+        for alias in topology::aliases(std::slice::from_ref(cfg)) {
+            for problem in topology::route(configs, alias).problems {
+                match &problem {
+                    // A cycle shows up from every host on it, report it once.
+                    Problem::Cycle(hosts) => {
+                        let mut members = hosts[1..].to_vec();
+                        members.sort();
+                        if reported.contains(&members) {
+                            continue;
+                        }
+                        reported.push(members);
+                    }
+                    // Reported on the jump host itself.
+                    Problem::Invalid { host, .. } | Problem::Undefined { host, .. }
+                        if host != alias =>
+                    {
+                        continue;
+                    }
+                    _ => {}
+                }
+                let key = jump_key(cfg);
+                findings.push(finding(Rule::JumpHost, i, key, problem.to_string()));
+            }
+        }
+    }
+}
+
+fn jump_key(cfg: &Config) -> Option<Key> {
+    [Key::ProxyJump, Key::ProxyCommand]
+        .into_iter()
+        .find(|k| cfg.columns.contains_key(k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sshconfigfile;
+
+    /// Locations of the findings of `rule` for `text`.
+    fn found(rule: Rule, text: &str) -> Vec<String> {
+        let configs = sshconfigfile::parse(text.as_bytes()).unwrap();
+        lint(&configs)
+            .iter()
+            .filter(|f| f.rule == rule)
+            .map(|f| f.location(&configs))
+            .collect()
+    }
+
+    fn messages(rule: Rule, text: &str) -> Vec<String> {
+        let configs = sshconfigfile::parse(text.as_bytes()).unwrap();
+        lint(&configs)
+            .into_iter()
+            .filter(|f| f.rule == rule)
+            .map(|f| f.message)
+            .collect()
+    }
+
+    #[test]
+    fn flags_duplicate_hosts() {
+        let text = "Host a b\n  User x\nHost B c\n  Port 22\nHost c\n  Port 23\n";
+        assert_eq!(found(Rule::DuplicateHost, text), ["Host B c", "Host c"]);
+        assert_eq!(
+            messages(Rule::DuplicateHost, text)[0],
+            "`B` is already used by Host a b"
+        );
+    }
+
+    #[test]
+    fn flags_shadowed_keys() {
+        let text = "\
+Host web*
+  User a
+  SendEnv LANG
+Host web1
+  User b
+  Port 22
+  SendEnv LC_*
+Host web2
+  User c
+";
+        // Repeated keys add up, so SendEnv is not shadowed.
+        assert_eq!(
+            found(Rule::ShadowedKey, text),
+            ["Host web1, User", "Host web2, User"]
+        );
+        assert_eq!(
+            messages(Rule::ShadowedKey, text),
+            [
+                "never used, Host web* above already sets it",
+                "never used, Host web* above already sets every key of this block",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_negated_blocks_when_shadowing() {
+        // A negated pattern may leave out the later block's hosts.
+        let text = "Host * !bastion\n  User a\nHost bastion\n  User b\n";
+        assert!(found(Rule::ShadowedKey, text).is_empty());
+        // Negations of the later block only narrow it.
+        let text = "Host web*\n  User a\nHost web1 !web2\n  User b\n";
+        assert_eq!(found(Rule::ShadowedKey, text), ["Host web1 !web2, User"]);
+        // The earlier block must match every host of the later one.
+        let text = "Host web*\n  User a\nHost web1 db1\n  User b\n";
+        assert!(found(Rule::ShadowedKey, text).is_empty());
+    }
+
+    #[test]
+    fn flags_wildcard_first() {
+        assert_eq!(
+            found(Rule::WildcardFirst, "Host *\n  User a\nHost b\n  Port 22\n"),
+            ["Host *"]
+        );
+        assert!(found(Rule::WildcardFirst, "Host b\n  Port 22\nHost *\n  User a\n").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checks_every_identity_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("sshe-lint-{}-identity", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let private = dir.join("private");
+        let open = dir.join("open");
+        for (path, mode) in [(&private, 0o600), (&open, 0o644)] {
+            std::fs::write(path, "key").unwrap();
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let text = format!(
+            "Host a\n  IdentityFile {}\n  IdentityFile \"{}\"\n  IdentityFile {}\n  \
+             IdentityFile none\n  IdentityFile ~/.ssh/%r\n",
+            private.display(),
+            open.display(),
+            dir.join("missing").display()
+        );
+        assert_eq!(
+            messages(Rule::MissingIdentity, &text),
+            [format!("{} does not exist", dir.join("missing").display())]
+        );
+        assert_eq!(
+            messages(Rule::IdentityPermissions, &text),
+            [format!(
+                "permissions 0644 of {} are too open, run `chmod 600` on it",
+                open.display()
+            )]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flags_deprecated_keys() {
+        let text = "Host a\n  PubkeyAcceptedKeyTypes +ssh-rsa\n  UseRoaming no\n";
+        assert_eq!(
+            found(Rule::DeprecatedKey, text),
+            ["Host a, PubkeyAcceptedKeyTypes", "Host a, UseRoaming"]
+        );
+        assert_eq!(
+            messages(Rule::DeprecatedKey, text),
+            [
+                "renamed to PubkeyAcceptedAlgorithms in OpenSSH 8.5, use PubkeyAcceptedAlgorithms",
+                "the roaming extension was removed in OpenSSH 7.2, remove it",
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_contradictions() {
+        let text = "Host a\n  ProxyJump b.example\n  ProxyCommand ssh -W %h:%p c.example\n";
+        assert_eq!(found(Rule::Contradiction, text), ["Host a, ProxyJump"]);
+
+        let text = "Host a\n  IdentitiesOnly yes\n";
+        assert_eq!(found(Rule::Contradiction, text), ["Host a, IdentitiesOnly"]);
+        // Set by another block, the finding is on the whole block.
+        let text = "Host a\n  User me\nHost *\n  IdentitiesOnly yes\n";
+        assert_eq!(found(Rule::Contradiction, text), ["Host a"]);
+        let text = "Host a\n  IdentitiesOnly yes\nHost *\n  IdentityFile ~/.ssh/%r\n";
+        assert!(found(Rule::Contradiction, text).is_empty());
+        assert!(found(Rule::Contradiction, "Host a\n  IdentitiesOnly no\n").is_empty());
+    }
+
+    #[test]
+    fn reports_a_jump_cycle_once() {
+        let text = "\
+Host a
+  ProxyJump b
+Host b
+  ProxyJump c
+Host c
+  ProxyJump a
+";
+        assert_eq!(
+            messages(Rule::JumpHost, text),
+            ["jump cycle: a -> b -> c -> a"]
+        );
+        assert_eq!(found(Rule::JumpHost, text), ["Host a, ProxyJump"]);
+    }
+
+    #[test]
+    fn reports_bad_jump_hosts_on_their_block() {
+        let text = "Host a\n  ProxyJump b\nHost b\n  ProxyJump nowhere\nHost c\n  ProxyJump c1:0\n";
+        assert_eq!(
+            messages(Rule::JumpHost, text),
+            [
+                "b: jump host `nowhere` is not defined",
+                "c: `0` is not a valid port",
+            ]
+        );
+        assert_eq!(
+            found(Rule::JumpHost, text),
+            ["Host b, ProxyJump", "Host c, ProxyJump"]
+        );
+    }
+
+    #[test]
+    fn skips_ignored_rules() {
+        let text = "\
+# sshe: ignore=wildcard-first
+Host *
+  User a
+Host b
+  # sshe: ignore=deprecated-key
+  UseRoaming no
+  Protocol 2
+";
+        assert!(found(Rule::WildcardFirst, text).is_empty());
+        assert_eq!(found(Rule::DeprecatedKey, text), ["Host b, Protocol"]);
+
+        let text = "Host a\n  UseRoaming no\n# sshe: ignore=all\nHost a\n  Protocol 2\n";
+        assert!(found(Rule::DuplicateHost, text).is_empty());
+        assert_eq!(found(Rule::DeprecatedKey, text), ["Host a, UseRoaming"]);
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        for (i, rule) in ALL_RULES.iter().enumerate() {
+            assert!(ALL_RULES[..i].iter().all(|r| r.id() != rule.id()));
+        }
+    }
+}
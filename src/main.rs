@@ -13,65 +13,752 @@
 //! [examples]: https://github.com/ratatui-org/ratatui/blob/main/examples
 //! [examples readme]: https://github.com/ratatui-org/ratatui/blob/main/examples/README.md
 
+mod agent;
+mod algorithms;
 mod app;
+mod audit;
+mod certs;
+mod completion;
+mod connect;
+mod forward;
+mod fuzzy;
+mod grouping;
+mod keygen;
+mod keys;
+mod knownhosts;
+mod lint;
+mod migrate;
+mod mux;
+mod picker;
+mod reachability;
+mod settings;
 mod sshconfig;
 mod sshconfigfile;
+mod topology;
 mod tui;
+mod tunnels;
+mod wire;
 
-use clap::Parser;
-use sshconfigfile::{parse, save_config};
-use std::{error::Error, io::{BufReader, BufWriter}, path::PathBuf};
+use clap::{Parser, Subcommand};
+use settings::Settings;
+use sshconfigfile::{lost_lines, parse, save_config, Casing, Order, Quoting, Format};
+use sshconfig::Config;
+use std::{collections::HashMap, error::Error, io::{BufReader, BufWriter}, path::{Path, PathBuf}, time::Duration};
 
-fn default_in_file() -> PathBuf {
-    if let Some(x) = home::home_dir() {
-        x.join(".ssh").join("config")
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Input config file [default: ~/.ssh/config]
+    #[arg(short, long, global = true)]
+    in_file: Option<PathBuf>,
+
+    /// Out file [default: ~/.ssh/config.new]
+    #[arg(short, long, global = true)]
+    out_file: Option<PathBuf>,
+
+    /// Leave the mouse to the terminal instead of capturing it
+    #[arg(long)]
+    no_mouse: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Validate the sshe settings file (~/.config/sshe/config.toml)
+    Config {
+        /// Print the default settings instead
+        #[arg(long)]
+        print_default: bool,
+    },
+    /// Check the config for common mistakes
+    Lint {
+        /// List the rules instead, with the IDs to use in `# sshe: ignore=`
+        #[arg(long)]
+        rules: bool,
+    },
+    /// Flag risky client settings, with a safer value for each
+    Audit,
+    /// Rename or remove keywords deprecated by OpenSSH
+    Migrate {
+        /// OpenSSH version to migrate to [default: the installed one]
+        #[arg(long, value_parser = migrate::parse_version)]
+        target: Option<sshconfig::Version>,
+        /// Write the migrated config to the out file instead of only showing the changes
+        #[arg(long)]
+        write: bool,
+    },
+    /// Rewrite the config in a consistent style, in place unless --out-file is given
+    Fmt {
+        /// Exit with an error if the config is not formatted, without writing it
+        #[arg(long)]
+        check: bool,
+        /// Keyword casing [default: from the settings, canonical]
+        #[arg(long)]
+        casing: Option<Casing>,
+        /// Spaces before each key [default: from the settings, 2]
+        #[arg(long)]
+        indent: Option<usize>,
+        /// Empty lines between blocks [default: from the settings, 1]
+        #[arg(long)]
+        blank_lines: Option<usize>,
+        /// Order of the keys in a block [default: from the settings, file]
+        #[arg(long)]
+        order: Option<Order>,
+        /// Quoting of arguments [default: from the settings, keep]
+        #[arg(long)]
+        quoting: Option<Quoting>,
+    },
+    /// Choose a host on the terminal and print its alias, e.g. `ssh $(sshe pick)`
+    Pick {
+        /// Print this key of the chosen host instead, e.g. HostName
+        #[arg(long)]
+        field: Option<String>,
+        /// Start with this filter
+        #[arg(long, default_value = "")]
+        query: String,
+        /// Print a snippet binding Alt-S to the picker, for your shell's rc file
+        #[arg(long, value_enum)]
+        init: Option<picker::Shell>,
+    },
+    /// Try a TCP connection to each host's HostName and Port and read its SSH banner
+    Check {
+        /// Hosts to check [default: every host alias]
+        hosts: Vec<String>,
+        /// Seconds to wait for each host
+        #[arg(long, default_value_t = reachability::TIMEOUT.as_secs())]
+        timeout: u64,
+    },
+    /// List the known_hosts lines ssh checks a host's key against
+    KnownHosts {
+        /// Host to list the lines of [default: every line]
+        host: Option<String>,
+        /// List the lines no host of the config uses instead
+        #[arg(long, conflicts_with = "host")]
+        orphans: bool,
+        /// Remove the listed lines, keeping each file changed as <file>.old
+        #[arg(long)]
+        remove: bool,
+    },
+    /// List the keys of ~/.ssh with their fingerprints and the hosts using them
+    Keys,
+    /// List the keys loaded in ssh-agent and the hosts whose key is not loaded
+    Agent {
+        /// Load this private key in the agent instead
+        #[arg(long)]
+        add: Option<PathBuf>,
+        /// Seconds the added key stays loaded, 0 for no limit [default: from
+        /// the settings, 0]
+        #[arg(long, requires = "add")]
+        lifetime: Option<u32>,
+    },
+    /// Show the ControlMaster socket of each host and whether a master runs
+    Mux {
+        /// Hosts to show [default: every host alias with a ControlPath]
+        hosts: Vec<String>,
+    },
+    /// List the hosts with local forwards, and start or stop `ssh -N` for them
+    Tunnels {
+        /// Start `ssh -N` in the background for this host
+        #[arg(long, conflicts_with = "stop")]
+        start: Option<String>,
+        /// Stop the tunnel started for this host
+        #[arg(long)]
+        stop: Option<String>,
+    },
+    /// Show the certificates each host uses, and whether they are valid now
+    Certs {
+        /// Hosts to show [default: every host alias with a certificate]
+        hosts: Vec<String>,
+    },
+    /// Show how each host is reached through ProxyJump and ProxyCommand
+    Graph {
+        /// Print the whole jump topology in Graphviz format
+        #[arg(long)]
+        dot: bool,
+    },
+}
+
+fn config_command(print_default: bool) -> Result<(), Box<dyn Error>> {
+    if print_default {
+        print!("{}", Settings::default_toml());
+        return Ok(());
+    }
+
+    let path = Settings::path().ok_or("Unable to get home directory")?;
+    if path.exists() {
+        Settings::load(&path)?;
+        println!("{}: ok", path.display());
     } else {
-        panic!("Unable to get home directory")
+        println!("{}: not found, using defaults", path.display());
+    }
+
+    Ok(())
+}
+
+fn graph_command(config: &[Config], dot: bool) -> Result<(), Box<dyn Error>> {
+    if dot {
+        print!("{}", topology::dot(config));
+        return Ok(());
+    }
+
+    let mut failed = false;
+    for alias in topology::aliases(config) {
+        let route = topology::route(config, alias);
+        if !route.hops.is_empty() {
+            let hops: Vec<String> = route.hops.iter().map(|h| h.to_string()).collect();
+            println!("{}: {} -> {}", alias, hops.join(" -> "), alias);
+        }
+        for problem in route.problems {
+            eprintln!("{}", problem);
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn lint_command(config: &[Config]) -> Result<(), Box<dyn Error>> {
+    let findings = lint::lint(config);
+    for f in findings.iter() {
+        let rule = f.rule;
+        println!(
+            "{}[{}] {}: {}",
+            rule.severity().name(),
+            rule.id(),
+            f.location(config),
+            f.message
+        );
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.rule.severity() == lint::Severity::Error)
+        .count();
+    println!("{} errors, {} warnings", errors, findings.len() - errors);
+
+    if !findings.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn audit_command(config: &[Config]) -> Result<(), Box<dyn Error>> {
+    let risks = audit::audit(config);
+    for r in risks.iter() {
+        println!("risk[{}] {}: {}", r.check.id(), r.location(config), r.explanation);
+        println!("    safer: {}", r.fix());
+    }
+    println!("{} risky settings", risks.len());
+
+    if !risks.is_empty() {
+        std::process::exit(1);
     }
+    Ok(())
 }
 
-fn default_out_file() -> PathBuf {
-    if let Some(x) = home::home_dir() {
-        x.join(".ssh").join("config.new")
+fn migrate_command(
+    mut config: Vec<Config>,
+    target: sshconfig::Version,
+    write: bool,
+    out_file: &Path,
+    format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    let changes = migrate::changes(&config, target);
+    let mut last_idx = None;
+    for change in changes.iter() {
+        if last_idx != Some(change.config_idx) {
+            if last_idx.is_some() {
+                println!();
+            }
+            println!("Host {}", config[change.config_idx].host);
+            last_idx = Some(change.config_idx);
+        }
+        for line in change.diff(&config) {
+            println!("{}", line);
+        }
+        println!("  # {}", change.explanation);
+    }
+
+    let (major, minor) = target;
+    if changes.is_empty() {
+        println!("Nothing to migrate for OpenSSH {}.{}", major, minor);
+    } else if write {
+        for change in changes.iter() {
+            change.apply(&mut config);
+        }
+        let mut buf_writer = BufWriter::new(std::fs::File::create(out_file)?);
+        save_config(&mut buf_writer, &config, format)?;
+        println!("{} written to {}", count(changes.len(), "change"), out_file.display());
     } else {
-        panic!("Unable to get home directory")
+        println!(
+            "{} for OpenSSH {}.{}, run with --write to save them to {}",
+            count(changes.len(), "change"),
+            major,
+            minor,
+            out_file.display()
+        );
     }
+    Ok(())
 }
 
-#[derive(Parser)]
-#[command(version)]
-struct Args {
-    /// Input config file
-    #[arg(short, long, default_value_os_t = default_in_file())]
-    in_file: PathBuf,
+/// `n` followed by `noun`, in the plural unless `n` is 1.
+fn count(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", n, noun),
+    }
+}
+
+/// Rewrite `in_file` with `format`, or with `check` only report whether it
+/// would change.
+fn fmt_command(
+    config: &[Config],
+    in_file: &Path,
+    out_file: &Path,
+    format: &Format,
+    check: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut formatted = vec![];
+    save_config(&mut BufWriter::new(&mut formatted), config, format)?;
+    let formatted = String::from_utf8(formatted)?;
+    let original = std::fs::read_to_string(in_file)?;
+
+    // Better leave the file alone than drop what sshe cannot write back.
+    let lost = lost_lines(&original, &formatted);
+    if !lost.is_empty() {
+        eprintln!("{}: formatting would drop these lines, not writing:", in_file.display());
+        for line in lost {
+            eprintln!("  {}", line);
+        }
+        std::process::exit(1);
+    }
+
+    if !check {
+        std::fs::write(out_file, formatted)?;
+        return Ok(());
+    }
 
-    /// Out file
-    #[arg(short, long, default_value_os_t = default_out_file())]
-    out_file: PathBuf,
+    let original: Vec<&str> = original.lines().collect();
+    let formatted: Vec<&str> = formatted.lines().collect();
+    let mismatch = (0..original.len().max(formatted.len())).find(|&i| original.get(i) != formatted.get(i));
+    match mismatch {
+        Some(line) => {
+            println!("{}:{}: not formatted", in_file.display(), line + 1);
+            std::process::exit(1);
+        }
+        None => println!("{}: formatted", in_file.display()),
+    }
+    Ok(())
+}
+
+fn pick_command(
+    config: &[Config],
+    settings: &Settings,
+    field: Option<String>,
+    query: &str,
+) -> Result<(), Box<dyn Error>> {
+    let key = field
+        .map(|f| {
+            sshconfig::Key::try_from(f.as_str()).map_err(|_| format!("Unknown key: `{}`", f))
+        })
+        .transpose()?;
+
+    let Some(alias) = picker::pick(config, settings, query)? else {
+        std::process::exit(130);
+    };
+    match key {
+        None => println!("{}", alias),
+        Some(key) => match picker::field(config, alias, key) {
+            Some(value) => println!("{}", value),
+            // ssh connects to the alias itself without a HostName.
+            None if key == sshconfig::Key::HostName => println!("{}", alias),
+            None => return Err(format!("{} is not set for {}", key.str(), alias).into()),
+        },
+    }
+    Ok(())
+}
+
+fn check_command(
+    config: &[Config],
+    hosts: Vec<String>,
+    timeout: u64,
+) -> Result<(), Box<dyn Error>> {
+    let hosts = if hosts.is_empty() {
+        topology::aliases(config).into_iter().map(str::to_owned).collect()
+    } else {
+        // A host named twice is checked once.
+        let mut unique: Vec<String> = vec![];
+        for host in hosts {
+            if !unique.contains(&host) {
+                unique.push(host);
+            }
+        }
+        unique
+    };
+    let mut results: HashMap<String, reachability::Status> =
+        reachability::check_all(config, &hosts, Duration::from_secs(timeout))
+            .iter()
+            .collect();
+
+    let width = hosts.iter().map(|h| h.len()).max().unwrap_or(0);
+    let mut failed = 0;
+    for host in hosts.iter() {
+        let status = results.remove(host).ok_or("A check did not finish")?;
+        let target = match reachability::target(config, host) {
+            Ok(target) => target.to_string(),
+            Err(_) => String::new(),
+        };
+        println!("{:<width$}  {:<24}  {}", host, target, status);
+        if !status.is_reachable() && status != reachability::Status::Proxied {
+            failed += 1;
+        }
+    }
+    println!("{} of {} hosts unreachable", failed, hosts.len());
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn known_hosts_command(
+    config: &[Config],
+    host: Option<String>,
+    orphans: bool,
+    remove: bool,
+) -> Result<(), Box<dyn Error>> {
+    let entries = knownhosts::load(&knownhosts::files(config))?;
+    let listed = match &host {
+        _ if orphans => knownhosts::orphans(&entries, config),
+        Some(host) => knownhosts::matching(&entries, config, host),
+        None if remove => return Err("--remove needs a host or --orphans".into()),
+        None => entries.iter().collect(),
+    };
+
+    for entry in listed.iter() {
+        println!("{}  {}", entry.location(), entry);
+    }
+    if remove {
+        knownhosts::remove(&listed)?;
+        println!("Removed {} lines", listed.len());
+    } else {
+        println!("{} lines", listed.len());
+    }
+    Ok(())
+}
+
+fn keys_command(config: &[Config]) -> Result<(), Box<dyn Error>> {
+    let inventory = keys::inventory(config)?;
+    let references = keys::references(config);
+
+    let width = inventory
+        .iter()
+        .map(|k| settings::contract_tilde(&k.path).len())
+        .max()
+        .unwrap_or(0);
+    for key in inventory.iter() {
+        let users = keys::users(&references, key);
+        let path = settings::contract_tilde(&key.path);
+        let usage = keys::usage(key, &users, config).unwrap_or("unused".to_owned());
+        println!("{:<width$}  {}  {}", path, keys::summary(key), usage);
+    }
+
+    for reference in references.iter().filter(|r| r.is_missing()) {
+        println!(
+            "missing {}, IdentityFile of Host {}",
+            settings::contract_tilde(&reference.path),
+            config[reference.config_idx].host
+        );
+    }
+    Ok(())
+}
+
+fn tunnels_command(
+    config: &[Config],
+    config_file: &Path,
+    start: Option<String>,
+    stop: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let dir = tunnels::state_dir();
+    if let Some(alias) = start {
+        let child = tunnels::start("ssh", &dir, config_file, &alias)?;
+        println!("Started a tunnel for {}, pid {}", alias, child.id());
+        return Ok(());
+    }
+    if let Some(alias) = stop {
+        tunnels::stop(&dir, &alias)?;
+        println!("Stopped the tunnel of {}", alias);
+        return Ok(());
+    }
+
+    let running = tunnels::load(&dir);
+    let listening = tunnels::listening();
+    let hosts = tunnels::hosts(config);
+    let width = hosts.iter().map(|h| h.len()).max().unwrap_or(0);
+    for host in hosts.iter() {
+        let state = match running.iter().find(|t| t.alias == *host) {
+            Some(tunnel) => format!("running, pid {}", tunnel.pid),
+            None => "stopped".to_owned(),
+        };
+        let ports = tunnels::ports(config, host);
+        println!(
+            "{:<width$}  {:<20}  {}",
+            host,
+            state,
+            tunnels::port_summary(&ports, &listening)
+        );
+    }
+    Ok(())
+}
+
+fn agent_command(
+    config: &[Config],
+    add: Option<PathBuf>,
+    lifetime: u32,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(file) = add {
+        if !agent::add_file(&file, lifetime)? {
+            // ssh-add asks for the passphrase.
+            let line = agent::add_command(&file, lifetime);
+            let status = std::process::Command::new(&line[0])
+                .args(&line[1..])
+                .status()?;
+            if !status.success() {
+                return Err(format!("ssh-add ended with {}", status).into());
+            }
+        }
+        println!("Added {}", file.display());
+        return Ok(());
+    }
+
+    let identities = agent::list()?;
+    let inventory = keys::inventory(config)?;
+    let references = keys::references(config);
+    for identity in identities.iter() {
+        let hosts: Vec<&str> = inventory
+            .iter()
+            .filter(|k| agent::is_loaded(std::slice::from_ref(identity), k))
+            .flat_map(|k| keys::users(&references, k))
+            .map(|i| config[i].host.as_str())
+            .collect();
+        let usage = if hosts.is_empty() {
+            "no host names it".to_owned()
+        } else {
+            format!("used by {}", hosts.join(", "))
+        };
+        println!(
+            "{} {}  {}  {}  {}",
+            identity.kind(),
+            identity.bits,
+            identity.fingerprint(),
+            identity.comment,
+            usage
+        );
+    }
+    println!("{} keys loaded", identities.len());
+
+    for reference in references.iter().filter(|r| !r.is_missing()) {
+        let loaded = keys::find(&inventory, &reference.path)
+            .is_some_and(|k| agent::is_loaded(&identities, k));
+        if !loaded {
+            println!(
+                "not loaded {}, IdentityFile of Host {}",
+                settings::contract_tilde(&reference.path),
+                config[reference.config_idx].host
+            );
+        }
+    }
+    Ok(())
+}
+
+fn mux_command(config: &[Config], hosts: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let explicit = !hosts.is_empty();
+    let hosts = if explicit {
+        hosts
+    } else {
+        topology::aliases(config).into_iter().map(str::to_owned).collect()
+    };
+
+    let width = hosts.iter().map(|h| h.len()).max().unwrap_or(0);
+    for host in hosts.iter() {
+        match mux::control_path(config, host) {
+            Some(path) => println!(
+                "{:<width$}  {}  {}",
+                host,
+                settings::contract_tilde(&path),
+                mux::status(&path)
+            ),
+            None if explicit => println!("{:<width$}  no ControlPath", host),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn certs_command(config: &[Config], hosts: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let inventory = certs::inventory(config);
+    let explicit = !hosts.is_empty();
+    let hosts = if explicit {
+        hosts
+    } else {
+        topology::aliases(config).into_iter().map(str::to_owned).collect()
+    };
+
+    let now = certs::now();
+    let mut failed = 0;
+    for host in hosts.iter() {
+        let files = certs::of(&inventory, config, host);
+        if files.is_empty() {
+            if explicit {
+                println!("{}  no certificate", host);
+            }
+            continue;
+        }
+        for file in files {
+            println!("{}  {}", host, settings::contract_tilde(&file.path));
+            let problems = match &file.certificate {
+                Ok(cert) => {
+                    for line in certs::details(cert, now) {
+                        println!("    {}", line);
+                    }
+                    certs::problems(cert, &certs::user(config, host), now)
+                }
+                Err(e) => vec![format!("unreadable: {}", e)],
+            };
+            for problem in problems.iter() {
+                println!("    ✗ {}", problem);
+            }
+            if !problems.is_empty() {
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        println!("{} certificates unusable", failed);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn lint_rules() {
+    for rule in lint::ALL_RULES {
+        println!("{:<22}{:<9}{}", rule.id(), rule.severity().name(), rule.summary());
+    }
+}
+
+fn read_config(path: &Path) -> Result<Vec<Config>, Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+
+    let buf_reader = BufReader::new(file);
+
+    parse(buf_reader)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let opts: Args = Args::parse();
 
-    let file = std::fs::File::open(opts.in_file)?;
+    match opts.command {
+        Some(Command::Config { print_default }) => return config_command(print_default),
+        Some(Command::Lint { rules: true }) => {
+            lint_rules();
+            return Ok(());
+        }
+        Some(Command::Pick { init: Some(shell), .. }) => {
+            print!("{}", shell.snippet());
+            return Ok(());
+        }
+        _ => {}
+    }
 
-    let buf_reader = BufReader::new(file);
+    let settings = match Settings::path() {
+        Some(path) => Settings::load(&path)?,
+        None => Settings::default(),
+    };
+    let in_file = opts.in_file.unwrap_or_else(|| settings.files.in_file());
+    // fmt rewrites the config in place by default.
+    let fmt_file = opts.out_file.clone().unwrap_or_else(|| in_file.clone());
+    let out_file = opts.out_file.unwrap_or_else(|| settings.files.out_file());
+    let format = settings.format.clone();
 
-    let config = parse(buf_reader)?;
+    let config = read_config(&in_file)?;
+
+    match opts.command {
+        Some(Command::Graph { dot }) => return graph_command(&config, dot),
+        Some(Command::Check { hosts, timeout }) => return check_command(&config, hosts, timeout),
+        Some(Command::Keys) => return keys_command(&config),
+        Some(Command::Certs { hosts }) => return certs_command(&config, hosts),
+        Some(Command::Mux { hosts }) => return mux_command(&config, hosts),
+        Some(Command::Tunnels { start, stop }) => {
+            return tunnels_command(&config, &in_file, start, stop)
+        }
+        Some(Command::Agent { add, lifetime }) => {
+            let lifetime = lifetime.unwrap_or(settings.agent.lifetime);
+            return agent_command(&config, add, lifetime);
+        }
+        Some(Command::KnownHosts {
+            host,
+            orphans,
+            remove,
+        }) => return known_hosts_command(&config, host, orphans, remove),
+        Some(Command::Pick { field, query, .. }) => {
+            return pick_command(&config, &settings, field, &query)
+        }
+        Some(Command::Fmt {
+            check,
+            casing,
+            indent,
+            blank_lines,
+            order,
+            quoting,
+        }) => {
+            let format = Format {
+                casing: casing.unwrap_or(format.casing),
+                indent: indent.unwrap_or(format.indent),
+                blank_lines: blank_lines.unwrap_or(format.blank_lines),
+                order: order.unwrap_or(format.order),
+                quoting: quoting.unwrap_or(format.quoting),
+            };
+            return fmt_command(&config, &in_file, &fmt_file, &format, check);
+        }
+        Some(Command::Lint { .. }) => return lint_command(&config),
+        Some(Command::Audit) => return audit_command(&config),
+        Some(Command::Migrate { target, write }) => {
+            let target = target.or_else(migrate::installed_version).unwrap_or(migrate::LATEST);
+            return migrate_command(config, target, write, &out_file, &format);
+        }
+        Some(Command::Config { .. }) | None => {}
+    }
 
     tui::init_error_hooks()?;
-    let terminal = tui::init_terminal()?;
+    let mut settings = settings;
+    settings.ui.mouse &= !opts.no_mouse;
+    let terminal = tui::init_terminal(settings.ui.mouse)?;
 
-    let mut app = app::App::with_config(config);
+    let openssh_version = migrate::installed_version().unwrap_or(migrate::LATEST);
+    // The host keys are only shown, the editor works without them.
+    let known_hosts = knownhosts::load(&knownhosts::files(&config)).unwrap_or_default();
+    let keys = keys::inventory(&config).unwrap_or_default();
+    let certificates = certs::inventory(&config);
+    let mut app = app::App::with_config(config)
+        .with_settings(settings)
+        .with_files(in_file, out_file)
+        .with_known_hosts(known_hosts)
+        .with_keys(keys)
+        .with_certificates(certificates)
+        .with_agent(agent::list())
+        .with_openssh_version(openssh_version);
 
     app.run(terminal)?;
 
     tui::restore_terminal()?;
 
-    let writer = std::fs::File::create(opts.out_file)?;
-    let mut buf_writer = BufWriter::new(writer);
-
-    save_config(&mut buf_writer, app.config())?;
+    app.save()?;
 
     Ok(())
 }
@@ -0,0 +1,231 @@
+use crate::sshconfig::{self, Config, Key, Version};
+
+/// The newest OpenSSH version sshe knows about, used when the installed
+/// one cannot be found.
+pub const LATEST: Version = (10, 0);
+
+/// Parse `9.6`, or a version as printed by `ssh -V` such as
+/// `OpenSSH_9.6p1`.
+pub fn parse_version(text: &str) -> Result<Version, String> {
+    let number = text.trim().strip_prefix("OpenSSH_").unwrap_or(text.trim());
+    let invalid = || format!("`{}` is not an OpenSSH version such as 9.6", text);
+    let (major, rest) = number.split_once('.').ok_or_else(invalid)?;
+    let minor: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    Ok((
+        major.parse().map_err(|_| invalid())?,
+        minor.parse().map_err(|_| invalid())?,
+    ))
+}
+
+/// The version of the `ssh` found in `PATH`.
+pub fn installed_version() -> Option<Version> {
+    let output = std::process::Command::new("ssh").arg("-V").output().ok()?;
+    // Printed on stderr, followed by the OpenSSL version.
+    let text = String::from_utf8_lossy(&output.stderr);
+    parse_version(text.split([' ', ',']).next()?).ok()
+}
+
+/// What a migration does to a deprecated key.
+#[derive(Clone, Copy)]
+pub enum Action {
+    Rename(Key),
+    Remove,
+}
+
+/// A deprecated key of the block at `config_idx` to rename or remove.
+pub struct Change {
+    pub config_idx: usize,
+    pub key: Key,
+    pub action: Action,
+    pub explanation: String,
+}
+
+impl Change {
+    /// The config lines removed and added, prefixed with `-` and `+`.
+    pub fn diff(&self, configs: &[Config]) -> Vec<String> {
+        let value = configs[self.config_idx]
+            .columns
+            .get(&self.key)
+            .map_or("", |v| v.as_str());
+        let mut lines: Vec<String> = value
+            .lines()
+            .map(|v| format!("- {} {}", self.key.str(), v))
+            .collect();
+        if let Action::Rename(new) = self.action {
+            lines.extend(value.lines().map(|v| format!("+ {} {}", new.str(), v)));
+        }
+        lines
+    }
+
+    /// Rename or remove the key, keeping its lines where they are.
+    pub fn apply(&self, configs: &mut [Config]) {
+        let cfg = &mut configs[self.config_idx];
+        match self.action {
+            Action::Rename(new) => cfg.rename_key(self.key, new),
+            Action::Remove => cfg.remove_key(self.key),
+        }
+    }
+}
+
+/// The changes bringing the config up to date for OpenSSH `target`. Keys
+/// deprecated after `target` are left alone, and so are the ones silenced
+/// with `# sshe: ignore=deprecated-key`.
+pub fn changes(configs: &[Config], target: Version) -> Vec<Change> {
+    let mut changes = vec![];
+    for (i, cfg) in configs.iter().enumerate() {
+        for key in sshconfig::ALL_KEYS.iter().copied() {
+            if !cfg.columns.contains_key(&key) {
+                continue;
+            }
+            let Some(deprecation) = key.deprecation() else {
+                continue;
+            };
+            let ignored = cfg.ignored(Some(key));
+            if deprecation.since > target
+                || ignored
+                    .iter()
+                    .any(|x| *x == "deprecated-key" || *x == "all")
+            {
+                continue;
+            }
+
+            let (action, explanation) = match deprecation.replacement {
+                // Both names set the same option, keep the current one.
+                Some(new) if cfg.columns.contains_key(&new) => (
+                    Action::Remove,
+                    format!("{}, which is already set", deprecation.reason),
+                ),
+                Some(new) => (Action::Rename(new), deprecation.reason.to_owned()),
+                None => (Action::Remove, deprecation.reason.to_owned()),
+            };
+            changes.push(Change {
+                config_idx: i,
+                key,
+                action,
+                explanation,
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufWriter;
+
+    use super::*;
+    use crate::sshconfigfile::{self, Format};
+
+    fn configs(text: &str) -> Vec<Config> {
+        sshconfigfile::parse(text.as_bytes()).unwrap()
+    }
+
+    /// `Key explanation` for each change of `text`.
+    fn planned(text: &str, target: Version) -> Vec<String> {
+        changes(&configs(text), target)
+            .iter()
+            .map(|c| format!("{} {}", c.key.str(), c.explanation))
+            .collect()
+    }
+
+    /// `text` with every change for `target` applied, as written.
+    fn migrated(text: &str, target: Version) -> String {
+        let mut configs = configs(text);
+        for change in changes(&configs, target) {
+            change.apply(&mut configs);
+        }
+        let mut writer = BufWriter::new(vec![]);
+        sshconfigfile::save_config(&mut writer, &configs, &Format::default()).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(parse_version("9.6"), Ok((9, 6)));
+        assert_eq!(parse_version(" 10.0\n"), Ok((10, 0)));
+        assert_eq!(parse_version("OpenSSH_9.6p1"), Ok((9, 6)));
+        assert_eq!(parse_version("OpenSSH_8.9p1"), Ok((8, 9)));
+        for invalid in ["", "9", "nine.six", "9.x", "OpenSSH_", "LibreSSH_9.6"] {
+            assert_eq!(
+                parse_version(invalid),
+                Err(format!(
+                    "`{}` is not an OpenSSH version such as 9.6",
+                    invalid
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_keys_deprecated_after_the_target() {
+        let text = "Host a\n  PubkeyAcceptedKeyTypes +ssh-rsa\n  UseRoaming no\n";
+        assert!(planned(text, (7, 1)).is_empty());
+        assert_eq!(
+            planned(text, (8, 4)),
+            ["UseRoaming the roaming extension was removed in OpenSSH 7.2"]
+        );
+        assert_eq!(planned(text, (8, 5)).len(), 2);
+    }
+
+    #[test]
+    fn removes_keys_whose_replacement_is_set() {
+        let text =
+            "Host a\n  PubkeyAcceptedKeyTypes +ssh-rsa\n  PubkeyAcceptedAlgorithms +ssh-ed25519\n";
+        let changes = changes(&configs(text), LATEST);
+        assert!(matches!(changes[0].action, Action::Remove));
+        assert_eq!(
+            changes[0].explanation,
+            "renamed to PubkeyAcceptedAlgorithms in OpenSSH 8.5, which is already set"
+        );
+        assert_eq!(
+            migrated(text, LATEST),
+            "Host a\n  PubkeyAcceptedAlgorithms +ssh-ed25519\n"
+        );
+    }
+
+    #[test]
+    fn skips_ignored_keys() {
+        let text = "Host a\n  # sshe: ignore=deprecated-key\n  UseRoaming no\n  Protocol 2\n";
+        assert_eq!(
+            planned(text, LATEST),
+            ["Protocol protocol version 1 was removed in OpenSSH 7.6"]
+        );
+        assert!(planned(&format!("# sshe: ignore=all\n{}", text), LATEST).is_empty());
+    }
+
+    #[test]
+    fn diffs_every_line() {
+        let configs = configs("Host a\n  ChallengeResponseAuthentication no\n  Protocol 2\n");
+        let changes = changes(&configs, LATEST);
+        assert_eq!(
+            changes[0].diff(&configs),
+            [
+                "- ChallengeResponseAuthentication no",
+                "+ KbdInteractiveAuthentication no",
+            ]
+        );
+        assert_eq!(changes[1].diff(&configs), ["- Protocol 2"]);
+    }
+
+    #[test]
+    fn applies_changes_in_place() {
+        let text = "\
+Host a
+  # Old servers
+  PubkeyAcceptedKeyTypes +ssh-rsa
+  User me
+  UseRoaming no
+  HostName a.example
+";
+        assert_eq!(
+            migrated(text, (9, 6)),
+            "\
+Host a
+  # Old servers
+  PubkeyAcceptedAlgorithms +ssh-rsa
+  User me
+  HostName a.example
+"
+        );
+    }
+}
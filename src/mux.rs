@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    os::unix::{fs::MetadataExt, net::UnixStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+use sha1::{Digest, Sha1};
+
+use crate::{
+    certs, migrate, reachability,
+    settings::expand_tilde,
+    sshconfig::{self, Config, Key},
+    topology,
+};
+
+/// How long to wait for `ssh -O`.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// State of a host's ControlMaster socket.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    /// A master accepts connections on the socket.
+    Connected,
+    /// The socket is left over from a master that is gone.
+    Stale,
+    /// There is no socket.
+    Down,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Connected => write!(f, "connected (mux)"),
+            Status::Stale => write!(f, "stale socket"),
+            Status::Down => write!(f, "no master"),
+        }
+    }
+}
+
+/// The `ControlPath` of `alias` with its tokens expanded as ssh does, `None`
+/// when it is unset or `none`.
+pub fn control_path(configs: &[Config], alias: &str) -> Option<PathBuf> {
+    let effective = sshconfig::effective(configs, alias);
+    let value = |key| {
+        effective
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, v, _)| v.trim().to_owned())
+    };
+    let path = value(Key::ControlPath)?;
+    if path.eq_ignore_ascii_case("none") {
+        return None;
+    }
+
+    let target = reachability::target(configs, alias).ok()?;
+    let port = target.port.to_string();
+    let user = certs::user(configs, alias);
+    let local = local_hostname();
+    let short = local.split('.').next().unwrap_or_default().to_owned();
+    // The host of the last hop, the one ssh connects through.
+    let jump = value(Key::ProxyJump)
+        .and_then(|v| topology::parse_proxy_jump(&v).ok())
+        .and_then(|hops| hops.last().map(|h| h.host.clone()))
+        .unwrap_or_default();
+    let hashed_jump = if hashes_jump() { jump.as_str() } else { "" };
+    let hash = connection_hash(&[&local, &target.host, &port, &user, hashed_jump]);
+    let home = home::home_dir().unwrap_or_default();
+
+    let tokens = [
+        ('%', "%".to_owned()),
+        ('C', hash),
+        ('d', home.to_string_lossy().into_owned()),
+        ('h', target.host.clone()),
+        ('i', uid()),
+        ('j', jump),
+        ('k', value(Key::HostKeyAlias).unwrap_or(target.host)),
+        ('L', short),
+        ('l', local),
+        ('n', alias.to_owned()),
+        ('p', port),
+        ('r', user),
+        ('u', std::env::var("USER").unwrap_or_default()),
+    ];
+    Some(expand_tilde(&expand(&path, &tokens)))
+}
+
+/// The `%C` token: the SHA-1 of `%l%h%p%r%j`, in hex.
+fn connection_hash(parts: &[&str]) -> String {
+    let mut hash = Sha1::new();
+    for part in parts {
+        hash.update(part.as_bytes());
+    }
+    hash.finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether the installed ssh puts the `ProxyJump` host in `%C`, as OpenSSH
+/// does since 9.6. Older versions hash `%l%h%p%r` only.
+fn hashes_jump() -> bool {
+    static HASHES_JUMP: OnceLock<bool> = OnceLock::new();
+    *HASHES_JUMP.get_or_init(|| migrate::installed_version().unwrap_or(migrate::LATEST) >= (9, 6))
+}
+
+/// Replace each `%x` of `text` with the value of `x` in `tokens`. Unknown
+/// tokens are kept.
+fn expand(text: &str, tokens: &[(char, String)]) -> String {
+    let mut expanded = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(t) => match tokens.iter().find(|(k, _)| *k == t) {
+                Some((_, value)) => expanded.push_str(value),
+                None => {
+                    expanded.push('%');
+                    expanded.push(t);
+                }
+            },
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+fn local_hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|f| fs::read_to_string(f).ok())
+        .map(|h| h.trim().to_owned())
+        .unwrap_or_default()
+}
+
+/// The user id, as the owner of the process.
+fn uid() -> String {
+    fs::metadata("/proc/self").map_or(String::new(), |m| m.uid().to_string())
+}
+
+/// Whether a master listens on `socket`.
+pub fn status(socket: &Path) -> Status {
+    match UnixStream::connect(socket) {
+        Ok(_) => Status::Connected,
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Status::Stale,
+        Err(_) => Status::Down,
+    }
+}
+
+/// The master socket state of every host alias with a `ControlPath`.
+pub fn statuses(configs: &[Config]) -> HashMap<String, Status> {
+    topology::aliases(configs)
+        .into_iter()
+        .filter_map(|alias| {
+            let path = control_path(configs, alias)?;
+            Some((alias.to_owned(), status(&path)))
+        })
+        .collect()
+}
+
+/// Run `ssh -O operation` for `alias`, such as `check` or `exit`, reading
+/// the config from `config_file`. Returns what ssh printed. A master that
+/// does not answer within [`TIMEOUT`] is given up on.
+pub fn control(config_file: &Path, alias: &str, operation: &str) -> Result<String, String> {
+    let mut child = Command::new("ssh")
+        .arg("-F")
+        .arg(config_file)
+        .args(["-O", operation, alias])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Unable to run ssh: {}", e))?;
+    let start = Instant::now();
+    while child.try_wait().map_err(|e| e.to_string())?.is_none() {
+        if start.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("the master did not answer".to_owned());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let text = [&output.stderr, &output.stdout]
+        .iter()
+        .map(|o| String::from_utf8_lossy(o).trim().to_owned())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if output.status.success() {
+        Ok(text)
+    } else {
+        Err(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_connection() {
+        assert_eq!(
+            connection_hash(&["vm", "a.example", "22", "bob", ""]),
+            "69e7244a1cd2aff8f34d5d130507c9b7914be2af"
+        );
+        assert_eq!(
+            connection_hash(&["vm", "a.example", "22", "bob", "jump.example"]),
+            "33425497110ef79cab44674a825df00588a765cf"
+        );
+    }
+
+    #[test]
+    fn expands_tokens() {
+        let tokens = [('%', "%".to_owned()), ('h', "a.example".to_owned())];
+        assert_eq!(
+            expand("~/.ssh/cm-%h-%%-%x%", &tokens),
+            "~/.ssh/cm-a.example-%-%x%"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyEventKind},
+    execute,
+    terminal::{self, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{HighlightSpacing, List, ListItem, ListState, Paragraph},
+    Terminal, TerminalOptions, Viewport,
+};
+use tui_textarea::TextArea;
+
+use crate::{
+    fuzzy,
+    settings::{Action, Settings, PICK_ACTIONS},
+    sshconfig::{self, Config, Key},
+    topology,
+};
+
+/// Lines taken by the picker under the prompt.
+const HEIGHT: u16 = 12;
+
+/// Shells `sshe pick --init` has a keybinding snippet for.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Code binding Alt-S to insert a picked host at the cursor.
+    pub fn snippet(&self) -> &'static str {
+        match self {
+            Shell::Bash => {
+                r#"__sshe_pick() {
+  local host
+  host=$(sshe pick) || return
+  READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}$host${READLINE_LINE:$READLINE_POINT}"
+  READLINE_POINT=$((READLINE_POINT + ${#host}))
+}
+bind -x '"\es": __sshe_pick'
+"#
+            }
+            Shell::Zsh => {
+                r#"__sshe_pick() {
+  local host
+  host=$(sshe pick </dev/tty) && LBUFFER+=$host
+  zle reset-prompt
+}
+zle -N __sshe_pick
+bindkey '\es' __sshe_pick
+"#
+            }
+            Shell::Fish => {
+                r#"function __sshe_pick
+    set -l host (sshe pick)
+    and commandline -i -- $host
+    commandline -f repaint
+end
+bind \es __sshe_pick
+"#
+            }
+        }
+    }
+}
+
+/// A host alias offered by the picker, with the `HostName` it resolves to.
+struct Candidate<'a> {
+    alias: &'a str,
+    detail: String,
+}
+
+/// Let the user choose a host alias on the terminal, under the cursor.
+/// Drawn on `/dev/tty` so that stdout stays free for the result. Returns
+/// `None` when the picker is cancelled.
+pub fn pick<'a>(
+    configs: &'a [Config],
+    settings: &Settings,
+    query: &str,
+) -> io::Result<Option<&'a str>> {
+    let candidates: Vec<Candidate> = topology::aliases(configs)
+        .into_iter()
+        .map(|alias| Candidate {
+            alias,
+            detail: detail(configs, alias),
+        })
+        .collect();
+
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    enable_raw_mode()?;
+    let result = run(&candidates, settings, query, tty);
+    disable_raw_mode()?;
+    result
+}
+
+fn run<'a>(
+    candidates: &[Candidate<'a>],
+    settings: &Settings,
+    query: &str,
+    mut tty: File,
+) -> io::Result<Option<&'a str>> {
+    let area = viewport(&mut tty)?;
+    let mut terminal = Terminal::with_options(
+        CrosstermBackend::new(tty),
+        TerminalOptions {
+            viewport: Viewport::Fixed(area),
+        },
+    )?;
+    let mut textarea = TextArea::new(vec![query.to_owned()]);
+    textarea.set_cursor_line_style(Style::default());
+    textarea.move_cursor(tui_textarea::CursorMove::End);
+    let mut state = ListState::default().with_selected(Some(0));
+
+    let chosen = loop {
+        let texts: Vec<String> = candidates
+            .iter()
+            .map(|c| format!("{} {}", c.alias, c.detail))
+            .collect();
+        let matches = fuzzy::filter(&textarea.lines()[0], texts.iter().map(|x| x.as_str()));
+
+        terminal.draw(|f| {
+            let [input_area, count_area, list_area] = Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Fill(1),
+            ])
+            .areas(f.size());
+
+            let [prompt_area, text_area] =
+                Layout::horizontal([Constraint::Length(2), Constraint::Fill(1)]).areas(input_area);
+            f.render_widget(Paragraph::new("> "), prompt_area);
+            f.render_widget(textarea.widget(), text_area);
+            f.render_widget(
+                Line::raw(format!("  {}/{}", matches.len(), candidates.len())).dim(),
+                count_area,
+            );
+
+            let width = candidates.iter().map(|c| c.alias.len()).max().unwrap_or(0);
+            let items: Vec<ListItem> = matches
+                .iter()
+                .map(|i| {
+                    let c = &candidates[*i];
+                    ListItem::new(Line::from(vec![
+                        format!("{:<width$}  ", c.alias).into(),
+                        c.detail.clone().dim(),
+                    ]))
+                })
+                .collect();
+            let list = List::new(items)
+                .highlight_style(settings.theme.selected_style())
+                .highlight_symbol("> ")
+                .highlight_spacing(HighlightSpacing::Always);
+            f.render_stateful_widget(list, list_area, &mut state);
+        })?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match settings.keys.popup_action(&key, PICK_ACTIONS) {
+            Some(Action::Cancel) => break None,
+            Some(Action::Confirm) => {
+                break state
+                    .selected()
+                    .and_then(|i| matches.get(i))
+                    .map(|i| candidates[*i].alias);
+            }
+            Some(Action::Down) => state.select_next(),
+            Some(Action::Up) => state.select_previous(),
+            _ => {
+                // Ctrl-C cancels like in other pickers.
+                if key.code == event::KeyCode::Char('c')
+                    && key.modifiers.contains(event::KeyModifiers::CONTROL)
+                {
+                    break None;
+                }
+                if textarea.input(key) {
+                    state.select(Some(0));
+                }
+            }
+        }
+    };
+
+    terminal.clear()?;
+    execute!(terminal.backend_mut(), MoveTo(0, area.y))?;
+    Ok(chosen)
+}
+
+/// Make room for the picker under the cursor, scrolling the terminal if
+/// needed. `Viewport::Inline` would ask for the cursor position on stdout,
+/// which is where the result goes.
+fn viewport(tty: &mut File) -> io::Result<Rect> {
+    let (width, rows) = terminal::size()?;
+    let height = HEIGHT.min(rows);
+    tty.write_all("\r\n".repeat(height as usize - 1).as_bytes())?;
+    let bottom = cursor_row(tty)?;
+    Ok(Rect::new(
+        0,
+        (bottom + 1).saturating_sub(height),
+        width,
+        height,
+    ))
+}
+
+/// The 0-based row of the cursor, as reported by the terminal.
+fn cursor_row(tty: &mut File) -> io::Result<u16> {
+    tty.write_all(b"\x1b[6n")?;
+    tty.flush()?;
+    // The answer is `ESC [ row ; column R`.
+    let mut answer = vec![];
+    let mut byte = [0];
+    while byte[0] != b'R' {
+        tty.read_exact(&mut byte)?;
+        answer.push(byte[0]);
+    }
+    let answer = String::from_utf8_lossy(&answer);
+    answer
+        .rsplit_once('[')
+        .and_then(|(_, position)| position.split_once(';'))
+        .and_then(|(row, _)| row.parse::<u16>().ok())
+        .map(|row| row.saturating_sub(1))
+        .ok_or_else(|| io::Error::other("the terminal did not report the cursor position"))
+}
+
+/// `HostName` and description of a host, shown next to its alias.
+fn detail(configs: &[Config], alias: &str) -> String {
+    let host_name = field(configs, alias, Key::HostName);
+    let description = configs
+        .iter()
+        .find(|c| c.host.split_whitespace().any(|h| h == alias))
+        .and_then(|c| c.description());
+    match (host_name, description) {
+        (Some(h), Some(d)) => format!("{}  {}", h, d),
+        (Some(h), None) => h,
+        (None, Some(d)) => d,
+        (None, None) => String::new(),
+    }
+}
+
+/// The value ssh uses for `key` when connecting to `alias`.
+pub fn field(configs: &[Config], alias: &str, key: Key) -> Option<String> {
+    sshconfig::effective(configs, alias)
+        .into_iter()
+        .find(|(k, _, _)| *k == key)
+        .map(|(_, value, _)| value)
+}
@@ -0,0 +1,274 @@
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    sshconfig::{self, Config, Key},
+    topology,
+};
+
+/// How long to wait for each connection and for the banner.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where ssh connects for a host: its `HostName` and `Port`.
+#[derive(Clone)]
+pub struct Target {
+    pub host: String,
+    pub port: u16,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The outcome of checking a host.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Status {
+    /// The TCP connection succeeded. `banner` is the software version from
+    /// the SSH identification line, `None` when the server sent none.
+    Reachable {
+        banner: Option<String>,
+    },
+    Refused,
+    TimedOut,
+    Unresolvable,
+    /// Reached through ProxyJump or ProxyCommand, which are not followed.
+    Proxied,
+    Failed(String),
+}
+
+impl Status {
+    pub fn is_reachable(&self) -> bool {
+        matches!(self, Status::Reachable { .. })
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Reachable { banner: Some(b) } => write!(f, "reachable, {}", b),
+            Status::Reachable { banner: None } => write!(f, "reachable, no SSH banner"),
+            Status::Refused => write!(f, "connection refused"),
+            Status::TimedOut => write!(f, "timed out"),
+            Status::Unresolvable => write!(f, "unresolvable"),
+            Status::Proxied => write!(f, "not checked, reached through a jump host"),
+            Status::Failed(reason) => write!(f, "failed: {}", reason),
+        }
+    }
+}
+
+/// The `HostName` and `Port` ssh uses for `alias`, `%h` standing for the
+/// alias as in ssh_config.
+pub fn target(configs: &[Config], alias: &str) -> Result<Target, String> {
+    let effective = sshconfig::effective(configs, alias);
+    let value = |key| {
+        effective
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, v, _)| v.trim())
+    };
+    let host = value(Key::HostName).map_or(alias.to_owned(), |h| h.replace("%h", alias));
+    let port = match value(Key::Port) {
+        Some(p) => p.parse().map_err(|_| format!("invalid Port `{}`", p))?,
+        None => 22,
+    };
+    Ok(Target { host, port })
+}
+
+/// Check every host of `aliases` at once, each on its own thread. Results
+/// arrive on the returned channel as they are known, in no particular order.
+pub fn check_all(
+    configs: &[Config],
+    aliases: &[String],
+    timeout: Duration,
+) -> Receiver<(String, Status)> {
+    let (sender, receiver) = mpsc::channel();
+    for alias in aliases {
+        let alias = alias.clone();
+        let checked = if topology::jumps(configs, &alias).is_ok_and(|hops| !hops.is_empty()) {
+            Err(Status::Proxied)
+        } else {
+            target(configs, &alias).map_err(Status::Failed)
+        };
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let status = match checked {
+                Ok(target) => check(&target, timeout),
+                Err(status) => status,
+            };
+            // The receiver is gone when nobody waits for the result anymore.
+            let _ = sender.send((alias, status));
+        });
+    }
+    receiver
+}
+
+/// Connect to `target` and read its SSH identification banner. Each address
+/// the name resolves to is tried in turn, waiting at most `timeout` for each.
+pub fn check(target: &Target, timeout: Duration) -> Status {
+    let Ok(addresses) = (target.host.as_str(), target.port).to_socket_addrs() else {
+        return Status::Unresolvable;
+    };
+
+    let mut status = Status::Unresolvable;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => {
+                return Status::Reachable {
+                    banner: banner(stream, timeout),
+                }
+            }
+            Err(e) => {
+                status = match e.kind() {
+                    io::ErrorKind::ConnectionRefused => Status::Refused,
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Status::TimedOut,
+                    _ => Status::Failed(e.to_string()),
+                }
+            }
+        }
+    }
+    status
+}
+
+/// The software version of the `SSH-protoversion-softwareversion` line. The
+/// server may send other lines before it.
+fn banner(stream: TcpStream, timeout: Duration) -> Option<String> {
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    let reader = BufReader::new(stream.take(8192));
+    for line in reader.split(b'\n').take(20) {
+        let line = line.ok()?;
+        let line = String::from_utf8_lossy(&line);
+        if let Some(version) = line.trim_end().strip_prefix("SSH-") {
+            let software = version.split_once('-').map_or(version, |(_, s)| s);
+            return Some(software.to_owned());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpListener};
+
+    use super::*;
+    use crate::sshconfigfile;
+
+    const SHORT: Duration = Duration::from_millis(500);
+
+    /// A local server sending `greeting` to the first client.
+    fn listener(greeting: &'static str) -> Target {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(greeting.as_bytes()).unwrap();
+            // Keep the connection open until the client is done.
+            thread::sleep(Duration::from_secs(1));
+        });
+        Target {
+            host: "127.0.0.1".to_owned(),
+            port,
+        }
+    }
+
+    fn parse(text: &str) -> Vec<Config> {
+        sshconfigfile::parse(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn reads_the_banner() {
+        let target = listener("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3\r\n");
+        assert_eq!(
+            check(&target, SHORT),
+            Status::Reachable {
+                banner: Some("OpenSSH_9.6p1 Ubuntu-3".to_owned())
+            }
+        );
+    }
+
+    #[test]
+    fn skips_lines_before_the_banner() {
+        let target = listener("Welcome\r\nSSH-2.0-dropbear_2022.83\r\n");
+        assert_eq!(
+            check(&target, SHORT),
+            Status::Reachable {
+                banner: Some("dropbear_2022.83".to_owned())
+            }
+        );
+    }
+
+    #[test]
+    fn reachable_without_banner() {
+        let target = listener("");
+        assert_eq!(check(&target, SHORT), Status::Reachable { banner: None });
+    }
+
+    #[test]
+    fn refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let target = Target {
+            host: "127.0.0.1".to_owned(),
+            port,
+        };
+        assert_eq!(check(&target, SHORT), Status::Refused);
+    }
+
+    #[test]
+    fn unresolvable() {
+        let target = Target {
+            host: "host.invalid".to_owned(),
+            port: 22,
+        };
+        assert_eq!(check(&target, SHORT), Status::Unresolvable);
+    }
+
+    #[test]
+    fn target_from_effective_config() {
+        let configs = parse(
+            "Host web\n  HostName %h.example.com\n  Port 2222\nHost bad\n  Port ssh\nHost *\n  User me\n",
+        );
+        let web = target(&configs, "web").unwrap();
+        assert_eq!((web.host.as_str(), web.port), ("web.example.com", 2222));
+        let other = target(&configs, "other").unwrap();
+        assert_eq!((other.host.as_str(), other.port), ("other", 22));
+        assert!(target(&configs, "bad").is_err());
+    }
+
+    #[test]
+    fn checks_hosts_concurrently() {
+        let up = listener("SSH-2.0-OpenSSH_9.6\r\n");
+        let configs = parse(&format!(
+            "Host up\n  HostName 127.0.0.1\n  Port {}\nHost inside\n  ProxyJump up\n",
+            up.port
+        ));
+        let aliases = ["up".to_owned(), "inside".to_owned()];
+        let mut results: Vec<(String, Status)> =
+            check_all(&configs, &aliases, SHORT).iter().collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            results,
+            vec![
+                ("inside".to_owned(), Status::Proxied),
+                (
+                    "up".to_owned(),
+                    Status::Reachable {
+                        banner: Some("OpenSSH_9.6".to_owned())
+                    }
+                ),
+            ]
+        );
+    }
+}
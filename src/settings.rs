@@ -0,0 +1,652 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    style::{palette::tailwind::SLATE, Color, Modifier, Style},
+    symbols,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::sshconfigfile::Format;
+
+/// User settings read from `$XDG_CONFIG_HOME/sshe/config.toml`, falling back
+/// to `~/.config/sshe/config.toml`. Every field is optional, missing ones
+/// keep their default value.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub files: Files,
+    pub ui: Ui,
+    pub connect: Connect,
+    pub agent: Agent,
+    pub keys: KeyBindings,
+    pub theme: Theme,
+    /// Style used to write the config, and by `sshe fmt`.
+    pub format: Format,
+}
+
+/// Default files used when `--in-file` or `--out-file` are not given.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Files {
+    pub in_file: String,
+    pub out_file: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Ui {
+    /// Capture the mouse to click and scroll through the panes. Turning it
+    /// off keeps the terminal's own text selection.
+    pub mouse: bool,
+}
+
+/// Commands offered to connect to the selected host.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Connect {
+    /// `{host}` is replaced with the host alias, and `{config}` with the
+    /// config file as last read or written by sshe, so that the connection
+    /// uses the saved edits.
+    pub commands: Vec<String>,
+}
+
+/// Keys added to ssh-agent from sshe.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Agent {
+    /// Seconds the keys stay loaded, 0 keeping them until they are removed.
+    pub lifetime: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub selected_fg: Color,
+    pub selected_bg: Color,
+    pub focused_border: BorderKind,
+    pub unfocused_border: BorderKind,
+    pub popup_border: BorderKind,
+    pub border_color: Color,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderKind {
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+}
+
+macro_rules! actions {
+    ($($action:ident => $field:ident: [$($default:literal),*]),* $(,)?) => {
+        /// Everything a key can be bound to.
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum Action {
+            $($action,)*
+        }
+
+        /// Keys bound to each action, written as e.g. `"j"`, `"down"`,
+        /// `"ctrl-n"` or `"f1"`.
+        #[derive(Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct KeyBindings {
+            $(pub $field: Vec<KeyBinding>,)*
+        }
+
+        impl Default for KeyBindings {
+            fn default() -> Self {
+                Self {
+                    $($field: vec![$($default.parse().unwrap()),*],)*
+                }
+            }
+        }
+
+        impl KeyBindings {
+            pub fn bindings(&self, action: Action) -> &[KeyBinding] {
+                match action {
+                    $(Action::$action => &self.$field,)*
+                }
+            }
+        }
+
+        impl Action {
+            /// Name of the action in the config file.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Action::$action => stringify!($field),)*
+                }
+            }
+        }
+    };
+}
+
+actions!(
+    Quit => quit: ["q", "esc"],
+    Down => down: ["j", "down"],
+    Up => up: ["k", "up"],
+    Back => back: ["h", "left"],
+    Open => open: ["l", "right", "enter"],
+    First => first: ["g", "home"],
+    Last => last: ["G", "end"],
+    Delete => delete: ["d", "delete"],
+    New => new: ["n"],
+    AddKey => add_key: ["a"],
+    ToggleAllKeys => toggle_all_keys: ["t"],
+    ToggleHelp => toggle_help: ["?", "f1"],
+    ToggleProblems => toggle_problems: ["p"],
+    EditComment => edit_comment: ["#"],
+    EditTags => edit_tags: ["T"],
+    GroupBy => group_by: ["v"],
+    ToggleGroup => toggle_group: ["space"],
+    Filter => filter: ["f"],
+    UseSafer => use_safer: ["s"],
+    Migrate => migrate: ["M"],
+    Connect => connect: ["c"],
+    CheckReachability => check_reachability: ["r"],
+    KnownHosts => known_hosts: ["H"],
+    Keys => keys: ["K"],
+    NewKey => new_key: ["N"],
+    AddToAgent => add_to_agent: ["a"],
+    MuxCheck => mux_check: ["m"],
+    MuxExit => mux_exit: ["X"],
+    Tunnels => tunnels: ["u"],
+    ShowOrphans => show_orphans: ["o"],
+    Cancel => cancel: ["esc"],
+    Confirm => confirm: ["enter"],
+    Complete => complete: ["tab"],
+    CompletePrevious => complete_previous: ["backtab"],
+    NextField => next_field: ["tab"],
+    PreviousField => previous_field: ["backtab"],
+    Toggle => toggle: ["space"],
+    MoveUp => move_up: ["K"],
+    MoveDown => move_down: ["J"],
+);
+
+/// Actions available in the host list.
+pub const MAIN_ACTIONS: &[Action] = &[
+    Action::Quit,
+    Action::Down,
+    Action::Up,
+    Action::Open,
+    Action::First,
+    Action::Last,
+    Action::Delete,
+    Action::New,
+    Action::AddKey,
+    Action::ToggleAllKeys,
+    Action::ToggleHelp,
+    Action::ToggleProblems,
+    Action::EditComment,
+    Action::EditTags,
+    Action::GroupBy,
+    Action::ToggleGroup,
+    Action::Filter,
+    Action::Migrate,
+    Action::Connect,
+    Action::CheckReachability,
+    Action::KnownHosts,
+    Action::Keys,
+    Action::NewKey,
+    Action::MuxCheck,
+    Action::MuxExit,
+    Action::Tunnels,
+];
+
+/// Actions available in the Config pane.
+pub const SELECT_ACTIONS: &[Action] = &[
+    Action::Quit,
+    Action::Down,
+    Action::Up,
+    Action::Back,
+    Action::Open,
+    Action::Delete,
+    Action::AddKey,
+    Action::ToggleAllKeys,
+    Action::ToggleHelp,
+    Action::ToggleProblems,
+    Action::EditComment,
+    Action::EditTags,
+    Action::UseSafer,
+];
+
+/// Actions available while a text popup is open. Plain characters are
+/// always typed into the popup, so only bindings with a modifier or a
+/// special key apply here.
+pub const EDIT_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Complete,
+    Action::CompletePrevious,
+    Action::ToggleHelp,
+];
+
+pub const NEW_ACTIONS: &[Action] = &[Action::Cancel, Action::Confirm];
+
+pub const COMMENT_ACTIONS: &[Action] = &[Action::Cancel, Action::Confirm];
+
+pub const FILTER_ACTIONS: &[Action] = &[Action::Cancel, Action::Confirm];
+
+pub const FORWARD_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::NextField,
+    Action::PreviousField,
+    Action::ToggleHelp,
+];
+
+/// Actions available in the algorithm checklist, which has no text input.
+pub const ALGORITHM_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::First,
+    Action::Last,
+    Action::Toggle,
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::ToggleHelp,
+];
+
+pub const MIGRATION_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::Toggle,
+    Action::ToggleHelp,
+];
+
+pub const CONNECT_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::Toggle,
+];
+
+pub const KNOWN_HOSTS_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::Toggle,
+    Action::ShowOrphans,
+];
+
+pub const KEYS_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::AddToAgent,
+];
+
+pub const TUNNELS_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::Toggle,
+];
+
+pub const KEYGEN_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::NextField,
+    Action::PreviousField,
+];
+
+pub const PICK_ACTIONS: &[Action] = &[
+    Action::Cancel,
+    Action::Confirm,
+    Action::Down,
+    Action::Up,
+    Action::ToggleHelp,
+];
+
+const SCOPES: &[(&str, &[Action])] = &[
+    ("host list", MAIN_ACTIONS),
+    ("config pane", SELECT_ACTIONS),
+    ("edit popup", EDIT_ACTIONS),
+    ("new popup", NEW_ACTIONS),
+    ("comment popup", COMMENT_ACTIONS),
+    ("filter popup", FILTER_ACTIONS),
+    ("forward form", FORWARD_ACTIONS),
+    ("algorithm checklist", ALGORITHM_ACTIONS),
+    ("migration prompt", MIGRATION_ACTIONS),
+    ("connect popup", CONNECT_ACTIONS),
+    ("known hosts popup", KNOWN_HOSTS_ACTIONS),
+    ("keys popup", KEYS_ACTIONS),
+    ("new key form", KEYGEN_ACTIONS),
+    ("tunnels popup", TUNNELS_ACTIONS),
+    ("key picker", PICK_ACTIONS),
+];
+
+/// A single key with its modifiers.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Whether `key` triggers this binding. Shift is part of the character
+    /// itself (`G` rather than `shift-g`) so it is not compared.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let modifiers = key.modifiers - KeyModifiers::SHIFT;
+        self.code == key.code && self.modifiers == modifiers
+    }
+
+    /// A plain character, which text popups treat as input instead.
+    pub fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && self.modifiers.is_empty()
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(value: KeyBinding) -> Self {
+        value.to_string()
+    }
+}
+
+impl std::str::FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(x) = rest.strip_prefix("ctrl-").filter(|x| !x.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = x;
+            } else if let Some(x) = rest.strip_prefix("alt-").filter(|x| !x.is_empty()) {
+                modifiers |= KeyModifiers::ALT;
+                rest = x;
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest {
+            "esc" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            other => {
+                let mut chars = other.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match other.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                        Some(n @ 1..=12) => KeyCode::F(n),
+                        _ => return Err(format!("unknown key `{}`", s)),
+                    },
+                }
+            }
+        };
+
+        Ok(KeyBinding { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            _ => write!(f, "?"),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// The first action of `actions` bound to `key`.
+    pub fn action(&self, key: &KeyEvent, actions: &[Action]) -> Option<Action> {
+        actions
+            .iter()
+            .find(|a| self.bindings(**a).iter().any(|b| b.matches(key)))
+            .copied()
+    }
+
+    /// Like [`KeyBindings::action`], but plain characters are left to the
+    /// text input.
+    pub fn popup_action(&self, key: &KeyEvent, actions: &[Action]) -> Option<Action> {
+        actions
+            .iter()
+            .find(|a| {
+                self.bindings(**a)
+                    .iter()
+                    .any(|b| !b.is_text() && b.matches(key))
+            })
+            .copied()
+    }
+
+    /// Short description of the keys bound to `actions`, such as `j/k` or
+    /// `<esc>`. In a popup, plain characters are skipped as they are typed
+    /// into the text input.
+    pub fn hint(&self, actions: &[Action], popup: bool) -> String {
+        actions
+            .iter()
+            .filter_map(|a| self.bindings(*a).iter().find(|b| !(popup && b.is_text())))
+            .map(|b| {
+                if b.is_text() {
+                    b.to_string()
+                } else {
+                    format!("<{}>", b)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Reject a key bound to two different actions of the same mode.
+    fn validate(&self) -> Result<(), String> {
+        for (scope, actions) in SCOPES {
+            let mut seen: HashMap<KeyBinding, Action> = HashMap::new();
+            for action in actions.iter() {
+                for binding in self.bindings(*action) {
+                    match seen.insert(*binding, *action) {
+                        Some(other) if other != *action => {
+                            return Err(format!(
+                                "`{}` is bound to both `{}` and `{}` in the {}",
+                                binding,
+                                other.name(),
+                                action.name(),
+                                scope
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self {
+            in_file: "~/.ssh/config".to_owned(),
+            out_file: "~/.ssh/config.new".to_owned(),
+        }
+    }
+}
+
+impl Files {
+    pub fn in_file(&self) -> PathBuf {
+        expand_tilde(&self.in_file)
+    }
+
+    pub fn out_file(&self) -> PathBuf {
+        expand_tilde(&self.out_file)
+    }
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self { mouse: true }
+    }
+}
+
+impl Default for Connect {
+    fn default() -> Self {
+        Self {
+            commands: vec![
+                "ssh -F {config} {host}".to_owned(),
+                "sftp -F {config} {host}".to_owned(),
+                "mosh --ssh=\"ssh -F {config}\" {host}".to_owned(),
+            ],
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            selected_fg: Color::Reset,
+            selected_bg: SLATE.c800,
+            focused_border: BorderKind::Thick,
+            unfocused_border: BorderKind::Plain,
+            popup_border: BorderKind::Rounded,
+            border_color: Color::Reset,
+        }
+    }
+}
+
+impl Theme {
+    pub fn selected_style(&self) -> Style {
+        Style::new()
+            .fg(self.selected_fg)
+            .bg(self.selected_bg)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn border_style(&self) -> Style {
+        Style::new().fg(self.border_color)
+    }
+
+    /// Border of a pane, depending on whether it has the focus.
+    pub fn pane_border(&self, focused: bool) -> symbols::border::Set {
+        if focused {
+            self.focused_border.set()
+        } else {
+            self.unfocused_border.set()
+        }
+    }
+}
+
+impl BorderKind {
+    pub fn set(&self) -> symbols::border::Set {
+        match self {
+            BorderKind::Plain => symbols::border::PLAIN,
+            BorderKind::Rounded => symbols::border::ROUNDED,
+            BorderKind::Double => symbols::border::DOUBLE,
+            BorderKind::Thick => symbols::border::THICK,
+        }
+    }
+}
+
+impl Settings {
+    /// Location of the settings file, honouring `XDG_CONFIG_HOME`.
+    pub fn path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(x) if !x.is_empty() => PathBuf::from(x),
+            _ => home::home_dir()?.join(".config"),
+        };
+        Some(base.join("sshe").join("config.toml"))
+    }
+
+    /// Read and validate the settings at `path`. A missing file gives the
+    /// default settings.
+    pub fn load(path: &Path) -> Result<Settings, Box<dyn Error>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        };
+
+        let settings: Settings =
+            toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        settings
+            .keys
+            .validate()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Ok(settings)
+    }
+
+    /// The default settings as a config file.
+    pub fn default_toml() -> String {
+        toml::to_string(&Settings::default()).expect("default settings serialize")
+    }
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `path` for display, with the home directory as `~`.
+pub fn contract_tilde(path: &Path) -> String {
+    match home::home_dir().and_then(|h| path.strip_prefix(h).ok().map(Path::to_owned)) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
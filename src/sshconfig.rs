@@ -11,7 +11,9 @@ macro_rules! count {
 }
 
 macro_rules! key_literal {
-    ($($key:ident),*) => {
+    ($($key:ident => $desc:literal),*) => {
+        // Variants mirror the ssh_config(5) keywords, e.g. `VisualHostKey`.
+        #[allow(clippy::enum_variant_names)]
         #[derive(Clone, PartialEq, Eq, Hash, Copy)]
         pub enum Key {
             $(
@@ -25,7 +27,7 @@ macro_rules! key_literal {
             )*
         ];
 
-        
+
         impl Key {
             pub fn str(&self) -> &'static str {
                 use Key::*;
@@ -35,6 +37,16 @@ macro_rules! key_literal {
                     )*
                 }
             }
+
+            /// One-line summary of the keyword, as found in ssh_config(5).
+            pub fn description(&self) -> &'static str {
+                use Key::*;
+                match self {
+                    $(
+                        $key => $desc,
+                    )*
+                }
+            }
         }
 
         impl TryFrom<&str> for Key {
@@ -42,9 +54,10 @@ macro_rules! key_literal {
 
             fn try_from(value: &str) -> Result<Self, Self::Error> {
                 use Key::*;
+                // Keywords are case-insensitive, like in ssh.
                 match value {
                     $(
-                        stringify!($key) => Ok($key),
+                        _ if value.eq_ignore_ascii_case(stringify!($key)) => Ok($key),
                     )*
                     _ => Err(()),
                 }
@@ -54,21 +67,708 @@ macro_rules! key_literal {
 }
 
 key_literal!(
-    HostName,
-    User,
-    IdentityFile,
-    IdentitiesOnly,
-    LogLevel,
-    Port,
-    UserKnownHostsFile,
-    PasswordAuthentication,
-    StrictHostKeyChecking,
-    Compression,
-    CompressionLevel,
-    SendEnv
+    AddKeysToAgent => "Add keys to a running ssh-agent after use",
+    AddressFamily => "Address family to use when connecting (any, inet, inet6)",
+    BatchMode => "Disable user interaction such as password prompts",
+    BindAddress => "Local address to use as the source of the connection",
+    BindInterface => "Local interface to use as the source of the connection",
+    CanonicalDomains => "Domain suffixes to search when canonicalizing host names",
+    CanonicalizeFallbackLocal => "Fail or fall back to system lookup when canonicalization fails",
+    CanonicalizeHostname => "Perform explicit host name canonicalization",
+    CanonicalizeMaxDots => "Maximum dots in a host name before canonicalization is skipped",
+    CanonicalizePermittedCNAMEs => "Rules for following CNAMEs when canonicalizing",
+    CASignatureAlgorithms => "Algorithms allowed for CAs to sign certificates",
+    CertificateFile => "File containing a user certificate",
+    ChallengeResponseAuthentication => "Use challenge-response authentication (deprecated)",
+    ChannelTimeout => "Close inactive channels after the given interval",
+    CheckHostIP => "Also check the host IP address in the known_hosts file",
+    Cipher => "Cipher used by protocol version 1 (removed)",
+    Ciphers => "Ciphers allowed and their order of preference",
+    ClearAllForwardings => "Clear all local, remote and dynamic port forwardings",
+    Compression => "Use compression",
+    CompressionLevel => "Compression level (ignored by modern OpenSSH)",
+    ConnectionAttempts => "Number of connection attempts before exiting",
+    ConnectTimeout => "Timeout in seconds when connecting to the server",
+    ControlMaster => "Enable sharing of sessions over a single connection",
+    ControlPath => "Path to the control socket used for connection sharing",
+    ControlPersist => "Keep the master connection open in the background",
+    DynamicForward => "Local port forwarded over the secure channel as a SOCKS proxy",
+    EnableEscapeCommandline => "Enable the command line option in the escape character menu",
+    EnableSSHKeysign => "Enable ssh-keysign for host-based authentication",
+    EscapeChar => "Escape character for the session",
+    ExitOnForwardFailure => "Terminate the connection if a forwarding cannot be set up",
+    FingerprintHash => "Hash algorithm used when displaying key fingerprints",
+    ForkAfterAuthentication => "Go to the background just before command execution",
+    ForwardAgent => "Forward the authentication agent connection",
+    ForwardX11 => "Redirect X11 connections over the secure channel",
+    ForwardX11Timeout => "Timeout for untrusted X11 forwarding",
+    ForwardX11Trusted => "Give remote X11 clients full access to the display",
+    GatewayPorts => "Allow remote hosts to connect to local forwarded ports",
+    GlobalKnownHostsFile => "Files to use for the global host key database",
+    GSSAPIAuthentication => "Allow GSSAPI based user authentication",
+    GSSAPIDelegateCredentials => "Forward GSSAPI credentials to the server",
+    HashKnownHosts => "Hash host names and addresses added to known_hosts",
+    HostbasedAcceptedAlgorithms => "Signature algorithms used for host-based authentication",
+    HostbasedAuthentication => "Try rhosts based authentication with public key authentication",
+    HostbasedKeyTypes => "Key types used for host-based authentication (deprecated)",
+    HostKeyAlgorithms => "Host key signature algorithms the client wants to use",
+    HostKeyAlias => "Alias used instead of the real host name when looking up host keys",
+    HostName => "Real host name to log into",
+    IdentitiesOnly => "Only use the configured authentication identity files",
+    IdentityAgent => "UNIX-domain socket used to communicate with the authentication agent",
+    IdentityFile => "File from which the authentication identity is read",
+    IgnoreUnknown => "Patterns of unknown options to ignore",
+    IPQoS => "IPv4 type-of-service or DSCP class for connections",
+    KbdInteractiveAuthentication => "Use keyboard-interactive authentication",
+    KbdInteractiveDevices => "Methods to use in keyboard-interactive authentication",
+    KexAlgorithms => "Available key exchange algorithms",
+    KnownHostsCommand => "Command used to obtain a list of host keys",
+    LocalCommand => "Command to execute on the local machine after connecting",
+    LocalForward => "Local port forwarded over the secure channel to a remote address",
+    LogLevel => "Verbosity level used when logging messages",
+    LogVerbose => "Source files or functions with forced debug logging",
+    MACs => "MAC algorithms in order of preference",
+    NoHostAuthenticationForLocalhost => "Disable host authentication for localhost",
+    NumberOfPasswordPrompts => "Number of password prompts before giving up",
+    ObscureKeystrokeTiming => "Obscure keystroke timing from passive observers",
+    PasswordAuthentication => "Use password authentication",
+    PermitLocalCommand => "Allow local command execution via LocalCommand",
+    PermitRemoteOpen => "Destinations allowed for remote dynamic forwarding",
+    PKCS11Provider => "PKCS#11 provider to use",
+    Port => "Port number to connect on the remote host",
+    PreferredAuthentications => "Order in which authentication methods are tried",
+    Protocol => "Protocol versions to try (removed, only version 2 remains)",
+    ProxyCommand => "Command used to connect to the server",
+    ProxyJump => "Jump proxies to connect through",
+    ProxyUseFdpass => "ProxyCommand passes a connected file descriptor back",
+    PubkeyAcceptedAlgorithms => "Signature algorithms used for public key authentication",
+    PubkeyAcceptedKeyTypes => "Key types used for public key authentication (deprecated)",
+    PubkeyAuthentication => "Try public key authentication",
+    RekeyLimit => "Data or time limit before the session key is renegotiated",
+    RemoteCommand => "Command to execute on the remote machine after connecting",
+    RemoteForward => "Remote port forwarded over the secure channel to a local address",
+    RequestTTY => "Request a pseudo-tty for the session",
+    RequiredRSASize => "Minimum RSA key size in bits",
+    RevokedHostKeys => "Revoked host public keys",
+    RhostsRSAAuthentication => "Use rhosts with RSA host authentication (removed)",
+    RSAAuthentication => "Use protocol version 1 RSA authentication (removed)",
+    SecurityKeyProvider => "Library used when loading FIDO authenticator-hosted keys",
+    SendEnv => "Local environment variables sent to the server",
+    ServerAliveCountMax => "Server alive messages sent without a response before disconnecting",
+    ServerAliveInterval => "Seconds of inactivity before sending a server alive message",
+    SessionType => "Session type requested from the server",
+    SetEnv => "Environment variables and values sent to the server",
+    StdinNull => "Redirect stdin from /dev/null",
+    StreamLocalBindMask => "Octal file creation mask for local socket files",
+    StreamLocalBindUnlink => "Remove an existing Unix-domain socket before binding",
+    StrictHostKeyChecking => "Automatically add host keys or refuse changed ones",
+    SyslogFacility => "Facility code used when logging messages",
+    Tag => "Configuration tag name that may be matched by Match tagged",
+    TCPKeepAlive => "Send TCP keepalive messages",
+    Tunnel => "Request tun device forwarding",
+    TunnelDevice => "Tun devices to open on the client and server",
+    UpdateHostKeys => "Accept notifications of additional host keys from the server",
+    UsePrivilegedPort => "Use a privileged source port (removed)",
+    User => "User to log in as",
+    UseRoaming => "Resume connections with the roaming extension (removed)",
+    UserKnownHostsFile => "Files to use for the user host key database",
+    VerifyHostKeyDNS => "Verify the remote key using DNS and SSHFP records",
+    VisualHostKey => "Print an ASCII art representation of the host key",
+    XAuthLocation => "Full path name of the xauth program"
 );
 
+#[derive(Default)]
 pub struct Config {
+    /// Lines before the first `Host` line, global options and their
+    /// comments, written back as they are. Only the first block has them.
+    pub preamble: Vec<String>,
     pub host: String,
     pub columns: HashMap<Key, String>,
+    /// Keys in the order they were read, once per line. Keys set since then
+    /// are not in it.
+    pub order: Vec<Key>,
+    /// Comment lines above the `Host` line, `#` included. Blank lines
+    /// between paragraphs are kept as empty strings.
+    pub comment: Vec<String>,
+    /// Comment lines above the first line of each key.
+    pub key_comments: HashMap<Key, Vec<String>>,
+    /// Comment lines above the later lines of a key, by key and line
+    /// number from 1.
+    pub line_comments: HashMap<(Key, usize), Vec<String>>,
+    /// Values of keys set again although they are not repeatable. ssh only
+    /// uses the first one, the others are kept to be written back.
+    pub duplicates: HashMap<Key, Vec<String>>,
+    /// Comment lines after the last key of the block, with their original
+    /// indentation.
+    pub trailing: Vec<String>,
+}
+
+const YES_NO: &[&str] = &["yes", "no"];
+
+/// An OpenSSH version, as major and minor numbers.
+pub type Version = (u32, u32);
+
+/// Why a keyword was renamed or removed, see [`Key::deprecation`].
+pub struct Deprecation {
+    pub since: Version,
+    pub reason: &'static str,
+    pub replacement: Option<Key>,
+}
+
+/// The shape of the value a key accepts, used to offer completions and
+/// pick an editor.
+pub enum ValueKind {
+    Text,
+    Enum(&'static [&'static str]),
+    Path,
+    Host,
+    Forward,
+    Algorithms,
+}
+
+impl Key {
+    pub fn value_kind(&self) -> ValueKind {
+        use Key::*;
+        match self {
+            BatchMode
+            | CanonicalizeFallbackLocal
+            | CheckHostIP
+            | ClearAllForwardings
+            | Compression
+            | EnableEscapeCommandline
+            | EnableSSHKeysign
+            | ExitOnForwardFailure
+            | ForkAfterAuthentication
+            | ForwardAgent
+            | ForwardX11
+            | ForwardX11Trusted
+            | GatewayPorts
+            | GSSAPIAuthentication
+            | GSSAPIDelegateCredentials
+            | HashKnownHosts
+            | HostbasedAuthentication
+            | IdentitiesOnly
+            | KbdInteractiveAuthentication
+            | NoHostAuthenticationForLocalhost
+            | ObscureKeystrokeTiming
+            | PasswordAuthentication
+            | PermitLocalCommand
+            | ProxyUseFdpass
+            | StdinNull
+            | StreamLocalBindUnlink
+            | TCPKeepAlive
+            | VisualHostKey
+            | ChallengeResponseAuthentication
+            | RhostsRSAAuthentication
+            | RSAAuthentication
+            | UsePrivilegedPort
+            | UseRoaming => ValueKind::Enum(YES_NO),
+            AddKeysToAgent => ValueKind::Enum(&["yes", "no", "ask", "confirm"]),
+            AddressFamily => ValueKind::Enum(&["any", "inet", "inet6"]),
+            CanonicalizeHostname => ValueKind::Enum(&["no", "yes", "always", "none"]),
+            CompressionLevel => ValueKind::Enum(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]),
+            ControlMaster => ValueKind::Enum(&["no", "yes", "ask", "auto", "autoask"]),
+            FingerprintHash => ValueKind::Enum(&["sha256", "md5"]),
+            LogLevel => ValueKind::Enum(&[
+                "QUIET", "FATAL", "ERROR", "INFO", "VERBOSE", "DEBUG", "DEBUG1", "DEBUG2", "DEBUG3",
+            ]),
+            PubkeyAuthentication => ValueKind::Enum(&["yes", "no", "unbound", "host-bound"]),
+            RequestTTY => ValueKind::Enum(&["no", "yes", "force", "auto"]),
+            SessionType => ValueKind::Enum(&["none", "subsystem", "default"]),
+            StrictHostKeyChecking => ValueKind::Enum(&["yes", "accept-new", "no", "off", "ask"]),
+            SyslogFacility => ValueKind::Enum(&[
+                "DAEMON", "USER", "AUTH", "LOCAL0", "LOCAL1", "LOCAL2", "LOCAL3", "LOCAL4",
+                "LOCAL5", "LOCAL6", "LOCAL7",
+            ]),
+            Tunnel => ValueKind::Enum(&["no", "yes", "point-to-point", "ethernet"]),
+            UpdateHostKeys => ValueKind::Enum(&["no", "yes", "ask"]),
+            VerifyHostKeyDNS => ValueKind::Enum(&["no", "yes", "ask"]),
+            CertificateFile | ControlPath | GlobalKnownHostsFile | IdentityAgent | IdentityFile
+            | PKCS11Provider | RevokedHostKeys | SecurityKeyProvider | UserKnownHostsFile
+            | XAuthLocation => ValueKind::Path,
+            HostName | ProxyJump => ValueKind::Host,
+            LocalForward | RemoteForward | DynamicForward => ValueKind::Forward,
+            CASignatureAlgorithms
+            | Ciphers
+            | HostKeyAlgorithms
+            | HostbasedAcceptedAlgorithms
+            | KexAlgorithms
+            | MACs
+            | PubkeyAcceptedAlgorithms => ValueKind::Algorithms,
+            _ => ValueKind::Text,
+        }
+    }
+
+    /// Keys that can be given several times in a block, each line adding to
+    /// the others. Their values are kept one per line.
+    pub fn is_repeatable(&self) -> bool {
+        matches!(
+            self,
+            Key::CertificateFile
+                | Key::DynamicForward
+                | Key::IdentityFile
+                | Key::LocalForward
+                | Key::RemoteForward
+                | Key::SendEnv
+                | Key::SetEnv
+        )
+    }
+}
+
+/// Groups of keys that configure the same feature and are worth reading
+/// together.
+const RELATED: &[&[Key]] = {
+    use Key::*;
+    &[
+        &[
+            HostName,
+            Port,
+            User,
+            AddressFamily,
+            BindAddress,
+            BindInterface,
+        ],
+        &[ConnectTimeout, ConnectionAttempts],
+        &[
+            IdentityFile,
+            IdentitiesOnly,
+            CertificateFile,
+            IdentityAgent,
+            AddKeysToAgent,
+            PubkeyAuthentication,
+        ],
+        &[
+            StrictHostKeyChecking,
+            UserKnownHostsFile,
+            GlobalKnownHostsFile,
+            HashKnownHosts,
+            CheckHostIP,
+            UpdateHostKeys,
+            HostKeyAlias,
+            VerifyHostKeyDNS,
+            KnownHostsCommand,
+            RevokedHostKeys,
+        ],
+        &[
+            PasswordAuthentication,
+            KbdInteractiveAuthentication,
+            KbdInteractiveDevices,
+            PreferredAuthentications,
+            NumberOfPasswordPrompts,
+            BatchMode,
+        ],
+        &[GSSAPIAuthentication, GSSAPIDelegateCredentials],
+        &[
+            HostbasedAuthentication,
+            EnableSSHKeysign,
+            HostbasedAcceptedAlgorithms,
+        ],
+        &[
+            Ciphers,
+            MACs,
+            KexAlgorithms,
+            HostKeyAlgorithms,
+            PubkeyAcceptedAlgorithms,
+            HostbasedAcceptedAlgorithms,
+            CASignatureAlgorithms,
+            RequiredRSASize,
+        ],
+        &[ProxyJump, ProxyCommand, ProxyUseFdpass],
+        &[ControlMaster, ControlPath, ControlPersist],
+        &[
+            LocalForward,
+            RemoteForward,
+            DynamicForward,
+            GatewayPorts,
+            ExitOnForwardFailure,
+            ClearAllForwardings,
+            PermitRemoteOpen,
+        ],
+        &[StreamLocalBindMask, StreamLocalBindUnlink],
+        &[Compression, CompressionLevel],
+        &[ServerAliveInterval, ServerAliveCountMax, TCPKeepAlive],
+        &[
+            ForwardX11,
+            ForwardX11Trusted,
+            ForwardX11Timeout,
+            XAuthLocation,
+        ],
+        &[
+            CanonicalizeHostname,
+            CanonicalDomains,
+            CanonicalizeFallbackLocal,
+            CanonicalizeMaxDots,
+            CanonicalizePermittedCNAMEs,
+        ],
+        &[LocalCommand, PermitLocalCommand],
+        &[
+            RemoteCommand,
+            RequestTTY,
+            SessionType,
+            StdinNull,
+            ForkAfterAuthentication,
+        ],
+        &[SendEnv, SetEnv],
+        &[LogLevel, LogVerbose, SyslogFacility],
+        &[Tunnel, TunnelDevice],
+        &[PKCS11Provider, SecurityKeyProvider],
+    ]
+};
+
+impl Key {
+    /// Value used by ssh when the key is not set, as documented in
+    /// ssh_config(5).
+    pub fn default_value(&self) -> Option<&'static str> {
+        use Key::*;
+        let value = match self {
+            AddKeysToAgent => "no",
+            AddressFamily => "any",
+            BatchMode => "no",
+            CanonicalizeFallbackLocal => "yes",
+            CanonicalizeHostname => "no",
+            CanonicalizeMaxDots => "1",
+            CheckHostIP => "no",
+            ClearAllForwardings => "no",
+            Compression => "no",
+            CompressionLevel => "6",
+            ConnectionAttempts => "1",
+            ConnectTimeout => "the system TCP timeout",
+            ControlMaster => "no",
+            ControlPersist => "no",
+            EnableEscapeCommandline => "no",
+            EnableSSHKeysign => "no",
+            EscapeChar => "~",
+            ExitOnForwardFailure => "no",
+            FingerprintHash => "sha256",
+            ForkAfterAuthentication => "no",
+            ForwardAgent => "no",
+            ForwardX11 => "no",
+            ForwardX11Timeout => "20m",
+            ForwardX11Trusted => "no",
+            GatewayPorts => "no",
+            GlobalKnownHostsFile => "/etc/ssh/ssh_known_hosts /etc/ssh/ssh_known_hosts2",
+            GSSAPIAuthentication => "no",
+            GSSAPIDelegateCredentials => "no",
+            HashKnownHosts => "no",
+            HostbasedAuthentication => "no",
+            HostName => "the host alias",
+            IdentitiesOnly => "no",
+            IdentityFile => {
+                "~/.ssh/id_rsa, ~/.ssh/id_ecdsa, ~/.ssh/id_ecdsa_sk, ~/.ssh/id_ed25519, ~/.ssh/id_ed25519_sk"
+            }
+            IPQoS => "af21 cs1",
+            KbdInteractiveAuthentication => "yes",
+            LogLevel => "INFO",
+            NoHostAuthenticationForLocalhost => "no",
+            NumberOfPasswordPrompts => "3",
+            ObscureKeystrokeTiming => "yes",
+            PasswordAuthentication => "yes",
+            PermitLocalCommand => "no",
+            Port => "22",
+            PreferredAuthentications => {
+                "gssapi-with-mic,hostbased,publickey,keyboard-interactive,password"
+            }
+            ProxyUseFdpass => "no",
+            PubkeyAuthentication => "yes",
+            RekeyLimit => "default none",
+            RequiredRSASize => "1024",
+            ServerAliveCountMax => "3",
+            ServerAliveInterval => "0",
+            SessionType => "default",
+            StdinNull => "no",
+            StreamLocalBindMask => "0177",
+            StreamLocalBindUnlink => "no",
+            StrictHostKeyChecking => "ask",
+            SyslogFacility => "USER",
+            TCPKeepAlive => "yes",
+            Tunnel => "no",
+            TunnelDevice => "any:any",
+            UpdateHostKeys => "yes",
+            User => "the local user name",
+            UserKnownHostsFile => "~/.ssh/known_hosts ~/.ssh/known_hosts2",
+            VerifyHostKeyDNS => "no",
+            VisualHostKey => "no",
+            XAuthLocation => "/usr/X11R6/bin/xauth",
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Why a keyword should not be used anymore, since which OpenSSH
+    /// version, and the keyword replacing it if there is one.
+    pub fn deprecation(&self) -> Option<Deprecation> {
+        use Key::*;
+        let (since, reason, replacement) = match self {
+            ChallengeResponseAuthentication => (
+                (8, 7),
+                "renamed to KbdInteractiveAuthentication in OpenSSH 8.7",
+                Some(KbdInteractiveAuthentication),
+            ),
+            Cipher => (
+                (7, 6),
+                "only used by protocol version 1, removed in OpenSSH 7.6",
+                None,
+            ),
+            CompressionLevel => (
+                (7, 4),
+                "only used by protocol version 1, removed in OpenSSH 7.4",
+                None,
+            ),
+            HostbasedKeyTypes => (
+                (8, 5),
+                "renamed to HostbasedAcceptedAlgorithms in OpenSSH 8.5",
+                Some(HostbasedAcceptedAlgorithms),
+            ),
+            Protocol => (
+                (7, 6),
+                "protocol version 1 was removed in OpenSSH 7.6",
+                None,
+            ),
+            PubkeyAcceptedKeyTypes => (
+                (8, 5),
+                "renamed to PubkeyAcceptedAlgorithms in OpenSSH 8.5",
+                Some(PubkeyAcceptedAlgorithms),
+            ),
+            RhostsRSAAuthentication => (
+                (7, 4),
+                "only used by protocol version 1, removed in OpenSSH 7.4",
+                None,
+            ),
+            RSAAuthentication => (
+                (7, 4),
+                "only used by protocol version 1, removed in OpenSSH 7.4",
+                None,
+            ),
+            UsePrivilegedPort => ((7, 5), "removed in OpenSSH 7.5", None),
+            UseRoaming => (
+                (7, 2),
+                "the roaming extension was removed in OpenSSH 7.2",
+                None,
+            ),
+            _ => return None,
+        };
+        Some(Deprecation {
+            since,
+            reason,
+            replacement,
+        })
+    }
+
+    /// Other keys that configure the same feature.
+    pub fn related(&self) -> Vec<Key> {
+        let mut result: Vec<Key> = vec![];
+        for group in RELATED.iter().filter(|g| g.contains(self)) {
+            for k in group.iter() {
+                if k != self && !result.contains(k) {
+                    result.push(*k);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Config {
+    /// Remove every line of `key`, with their comments.
+    pub fn remove_key(&mut self, key: Key) {
+        self.columns.remove(&key);
+        self.order.retain(|k| *k != key);
+        self.key_comments.remove(&key);
+        self.line_comments.retain(|(k, _), _| *k != key);
+        self.duplicates.remove(&key);
+    }
+
+    /// Give every line of `key` the name `new`, where it is.
+    pub fn rename_key(&mut self, key: Key, new: Key) {
+        if let Some(value) = self.columns.remove(&key) {
+            self.columns.insert(new, value);
+        }
+        for k in self.order.iter_mut().filter(|k| **k == key) {
+            *k = new;
+        }
+        if let Some(comments) = self.key_comments.remove(&key) {
+            self.key_comments.insert(new, comments);
+        }
+        self.line_comments = std::mem::take(&mut self.line_comments)
+            .into_iter()
+            .map(|((k, n), c)| ((if k == key { new } else { k }, n), c))
+            .collect();
+        if let Some(duplicates) = self.duplicates.remove(&key) {
+            self.duplicates.insert(new, duplicates);
+        }
+    }
+
+    /// The paragraph of comment right above the `Host` line, which describes
+    /// the host, e.g. `owner: team-x, purpose: db`. `# sshe:` lines are not
+    /// part of it.
+    pub fn description(&self) -> Option<String> {
+        let text = self.comment[self.description_start()..]
+            .iter()
+            .filter(|x| sshe_directive(x).is_none())
+            .map(|x| x.trim_start_matches('#').trim())
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Replace the description paragraph, keeping the comments before it and
+    /// the `# sshe:` lines. An empty `text` removes the description.
+    pub fn set_description(&mut self, text: &str) {
+        let directives: Vec<String> = self
+            .comment
+            .drain(self.description_start()..)
+            .filter(|x| sshe_directive(x).is_some())
+            .collect();
+        let text = text.trim();
+        if text.is_empty() && directives.is_empty() {
+            while self.comment.last().is_some_and(|x| x.is_empty()) {
+                self.comment.pop();
+            }
+        }
+        if !text.is_empty() {
+            self.comment.push(format!("# {}", text));
+        }
+        self.comment.extend(directives);
+    }
+
+    fn description_start(&self) -> usize {
+        self.comment
+            .iter()
+            .rposition(|x| x.is_empty())
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Tags of the host, from `# sshe: tags=prod,db` comments and the
+    /// `Tag` keyword.
+    pub fn tags(&self) -> Vec<String> {
+        let mut result: Vec<String> = vec![];
+        let from_comment = self
+            .comment
+            .iter()
+            .filter_map(|x| sshe_directive(x))
+            .flat_map(|x| x.split_whitespace())
+            .filter_map(|x| x.strip_prefix("tags="))
+            .flat_map(|x| x.split(','));
+        let from_key = self.columns.get(&Key::Tag).map(|x| x.as_str());
+        for tag in from_comment.chain(from_key).map(str::trim) {
+            if !tag.is_empty() && !result.iter().any(|x| x == tag) {
+                result.push(tag.to_owned());
+            }
+        }
+        result
+    }
+
+    /// Lint rules silenced by `# sshe: ignore=rule,...` comments: above the
+    /// `Host` line for the whole block, or above `key` for that key only.
+    pub fn ignored(&self, key: Option<Key>) -> Vec<&str> {
+        let key_comments = key
+            .and_then(|k| self.key_comments.get(&k))
+            .into_iter()
+            .flatten();
+        self.comment
+            .iter()
+            .chain(key_comments)
+            .filter_map(|x| sshe_directive(x))
+            .flat_map(|x| x.split_whitespace())
+            .filter_map(|x| x.strip_prefix("ignore="))
+            .flat_map(|x| x.split(','))
+            .collect()
+    }
+
+    /// Set the tags kept in the `# sshe:` comment. A `Tag` keyword is left
+    /// untouched.
+    pub fn set_tags(&mut self, tags: &[String]) {
+        let value = (!tags.is_empty()).then(|| format!("tags={}", tags.join(",")));
+        let existing = self
+            .comment
+            .iter()
+            .position(|x| sshe_directive(x).is_some());
+
+        let Some(i) = existing else {
+            if let Some(value) = value {
+                self.comment.push(format!("# sshe: {}", value));
+            }
+            return;
+        };
+
+        let mut fields: Vec<String> = sshe_directive(&self.comment[i])
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter(|x| !x.starts_with("tags="))
+            .map(|x| x.to_owned())
+            .collect();
+        fields.extend(value);
+
+        if fields.is_empty() {
+            self.comment.remove(i);
+        } else {
+            self.comment[i] = format!("# sshe: {}", fields.join(" "));
+        }
+    }
+
+    /// Whether this block applies to `host`: at least one pattern of the
+    /// `Host` line matches and no negated (`!`) pattern does.
+    pub fn matches(&self, host: &str) -> bool {
+        let mut matched = false;
+        for pattern in self.host.split_whitespace() {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if glob_match(negated, host) {
+                    return false;
+                }
+            } else if glob_match(pattern, host) {
+                matched = true;
+            }
+        }
+        matched
+    }
+}
+
+/// The content of a `# sshe: ...` comment line, which holds data for sshe
+/// itself such as tags.
+pub fn sshe_directive(line: &str) -> Option<&str> {
+    line.strip_prefix('#')?.trim_start().strip_prefix("sshe:")
+}
+
+/// The values ssh would use for `host`, with the index of the block each one
+/// comes from. As with ssh, the first obtained value of a key wins.
+pub fn effective(configs: &[Config], host: &str) -> Vec<(Key, String, usize)> {
+    let mut result: Vec<(Key, String, usize)> = vec![];
+    for (i, cfg) in configs.iter().enumerate().filter(|(_, c)| c.matches(host)) {
+        for k in ALL_KEYS.iter() {
+            if let Some(v) = cfg.columns.get(k) {
+                if !result.iter().any(|(x, _, _)| x == k) {
+                    result.push((*k, v.clone(), i));
+                }
+            }
+        }
+    }
+    result.sort_by_key(|(k, _, _)| ALL_KEYS.iter().position(|x| x == k));
+    result
+}
+
+/// Match `text` against a ssh_config pattern, where `*` matches any run of
+/// characters and `?` exactly one. Host names are compared case-insensitively.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_ascii_lowercase().chars().collect();
+    let t: Vec<char> = text.to_ascii_lowercase().chars().collect();
+
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
 }
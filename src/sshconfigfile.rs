@@ -1,6 +1,8 @@
 use std::{collections::HashMap, io::{BufRead, BufWriter, Write}};
 
-use crate::sshconfig::{Config, Key};
+use serde::{Deserialize, Serialize};
+
+use crate::sshconfig::{Config, Key, ALL_KEYS};
 
 /// For each parameter, the first obtained value will be used. The
 /// configuration files contain sections separated by ''Host'' specifications,
@@ -20,30 +22,71 @@ use crate::sshconfig::{Config, Key};
 /// specifying configuration options using the ssh, scp, and sftp -o option.
 /// Arguments may optionally be enclosed in double quotes (") in order to
 /// represent arguments containing spaces.
+///
+/// Keys such as `IdentityFile` or `LocalForward` add up when repeated, their
+/// lines are all kept. For the other keys only the first line is used, as
+/// ssh ignores the later ones, which are kept aside to be written back.
+///
+/// Comments are kept with what follows them: the ones right above a ''Host''
+/// line become the comment of that block, the ones above a keyword become the
+/// comment of that line. Indented comments after the last key of a block, and
+/// comments at the end of the file, stay at the end of their block. Global
+/// options before the first ''Host'' line are kept as they are.
 pub fn parse<R: BufRead>(reader: R) -> Result<Vec<Config>, Box<dyn std::error::Error>> {
-    let mut context: Option<(String, HashMap<Key, String>)> = None;
+    let mut context: Option<Config> = None;
+    let mut comments: Vec<String> = vec![];
+    let mut preamble: Vec<String> = vec![];
+    let mut indented = 0;
     let lines = reader.lines();
 
     let mut result = vec![];
 
     for line in lines {
         let line = line?;
+        let raw = line.as_str();
         let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
+        if line.is_empty() {
+            if comments.last().is_some_and(|x| !x.is_empty()) {
+                comments.push(String::new());
+            } else if context.is_none()
+                && comments.is_empty()
+                && preamble.last().is_some_and(|x| !x.is_empty())
+            {
+                preamble.push(String::new());
+            }
             continue;
         }
-        let (key, value) = line
-            .split_once(" =")
-            .or_else(|| line.split_once('='))
-            .or_else(|| line.split_once(' '))
+        if line.starts_with('#') {
+            if comments.len() == indented && raw.starts_with(char::is_whitespace) {
+                indented += 1;
+            }
+            comments.push(raw.trim_end().to_owned());
+            continue;
+        }
+
+        // The keyword ends at the first space or '=', as in `SetEnv A=1`.
+        let end = line
+            .find(|c: char| c.is_whitespace() || c == '=')
             .ok_or("invalid key value syntax")?;
+        let (key, value) = line.split_at(end);
+        let value = value.trim_start();
+        let value = value.strip_prefix('=').unwrap_or(value).trim_start();
 
         match key {
-            "Host" => {
-                if let Some((host, map)) = context {
-                    result.push(Config { host, columns: map });
+            _ if key.eq_ignore_ascii_case("Host") => {
+                if let Some(mut cfg) = context {
+                    cfg.trailing = comments.drain(..indented).collect();
+                    result.push(cfg);
                 };
-                context = Some((value.to_owned(), HashMap::new()));
+                while preamble.last().is_some_and(|x| x.is_empty()) {
+                    preamble.pop();
+                }
+                context = Some(Config {
+                    preamble: std::mem::take(&mut preamble),
+                    host: value.to_owned(),
+                    comment: trim_comments(take_comments(&mut comments)),
+                    ..Default::default()
+                });
             }
             other => {
                 let key = match other.try_into() {
@@ -51,30 +94,450 @@ pub fn parse<R: BufRead>(reader: R) -> Result<Vec<Config>, Box<dyn std::error::E
                     Err(_) => return Err(format!("Unknown key: `{}`", other).into()),
                 };
 
-                if let Some(ctx) = context.as_mut() {
-                    ctx.1.insert(key, value.to_owned());
+                let Some(ctx) = context.as_mut() else {
+                    preamble.append(&mut comments);
+                    preamble.push(raw.trim_end().to_owned());
+                    indented = 0;
+                    continue;
+                };
+                match ctx.columns.get_mut(&key) {
+                    Some(v) if key.is_repeatable() => {
+                        v.push('\n');
+                        v.push_str(value);
+                    }
+                    Some(_) => ctx.duplicates.entry(key).or_default().push(value.to_owned()),
+                    None => {
+                        ctx.columns.insert(key, value.to_owned());
+                    }
+                }
+                let n = ctx.order.iter().filter(|k| **k == key).count();
+                ctx.order.push(key);
+                let comment = trim_comments(take_comments(&mut comments));
+                if !comment.is_empty() {
+                    if n == 0 {
+                        ctx.key_comments.insert(key, comment);
+                    } else {
+                        ctx.line_comments.insert((key, n), comment);
+                    }
                 }
             },
         }
+        indented = 0;
     }
 
-    if let Some((host, map)) = context {
-        result.push(Config { host, columns: map });
+    if let Some(mut cfg) = context {
+        cfg.trailing = take_comments(&mut comments);
+        result.push(cfg);
     };
 
     Ok(result)
 }
 
-pub fn save_config<T: Write>(writer: &mut BufWriter<T>, cfg: &[Config]) -> Result<(), Box<dyn std::error::Error>> {
-    for cfg in cfg {
-        writeln!(writer, "Host {}", cfg.host)?;
+/// Take the pending comment lines, without the blank lines that separated
+/// them from the next item.
+fn take_comments(comments: &mut Vec<String>) -> Vec<String> {
+    while comments.last().is_some_and(|x| x.is_empty()) {
+        comments.pop();
+    }
+    std::mem::take(comments)
+}
+
+fn trim_comments(comments: Vec<String>) -> Vec<String> {
+    comments.into_iter().map(|x| x.trim_start().to_owned()).collect()
+}
+
+/// How [`save_config`] writes a config. The defaults keep the file as
+/// close as possible to what was read.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Format {
+    pub casing: Casing,
+    /// Spaces before each key of a block.
+    pub indent: usize,
+    /// Empty lines between two blocks.
+    pub blank_lines: usize,
+    pub order: Order,
+    pub quoting: Quoting,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            casing: Casing::Canonical,
+            indent: 2,
+            blank_lines: 1,
+            order: Order::File,
+            quoting: Quoting::Keep,
+        }
+    }
+}
+
+/// How keywords are spelled.
+#[derive(Serialize, Deserialize, Clone, Copy, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Casing {
+    /// As in ssh_config(5), e.g. `HostName`.
+    Canonical,
+    Lowercase,
+}
+
+/// How the keys of a block are ordered.
+#[derive(Serialize, Deserialize, Clone, Copy, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// As they were read, keys added since then go last.
+    File,
+    /// `HostName`, `User` and `Port` first, then the others alphabetically.
+    Canonical,
+}
+
+/// What happens to double quotes around arguments.
+#[derive(Serialize, Deserialize, Clone, Copy, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Quoting {
+    Keep,
+    /// Only quote arguments containing spaces.
+    Minimal,
+}
+
+/// Keys whose value is a command, passed as is to the shell.
+const COMMAND_KEYS: &[Key] = &[
+    Key::KnownHostsCommand,
+    Key::LocalCommand,
+    Key::ProxyCommand,
+    Key::RemoteCommand,
+];
+
+impl Format {
+    fn keyword(&self, keyword: &str) -> String {
+        match self.casing {
+            Casing::Canonical => keyword.to_owned(),
+            Casing::Lowercase => keyword.to_lowercase(),
+        }
+    }
+
+    /// The key lines of `cfg` to write, each with the comment lines above
+    /// it.
+    fn lines<'a>(&self, cfg: &'a Config) -> Vec<(Key, &'a [String], &'a str)> {
+        let mut result = vec![];
+        match self.order {
+            Order::File => {
+                let rest = ALL_KEYS.iter().filter(|k| !cfg.order.contains(k));
+                let mut written: HashMap<Key, usize> = HashMap::new();
+                for (i, key) in cfg.order.iter().enumerate() {
+                    let lines = key_lines(cfg, *key);
+                    let start = written.entry(*key).or_default();
+                    // The last line read of a key is followed by the ones
+                    // added since.
+                    let end = if cfg.order[i + 1..].contains(key) {
+                        (*start + 1).min(lines.len())
+                    } else {
+                        lines.len()
+                    };
+                    let lines = lines.into_iter().take(end).skip(*start);
+                    result.extend(lines.map(|(c, v)| (*key, c, v)));
+                    *start = end.max(*start);
+                }
+                for key in rest {
+                    result.extend(key_lines(cfg, *key).into_iter().map(|(c, v)| (*key, c, v)));
+                }
+            }
+            Order::Canonical => {
+                let mut keys: Vec<Key> = vec![];
+                for key in [Key::HostName, Key::User, Key::Port].iter().chain(ALL_KEYS.iter()) {
+                    if !keys.contains(key) {
+                        keys.push(*key);
+                    }
+                }
+                for key in keys {
+                    result.extend(key_lines(cfg, key).into_iter().map(|(c, v)| (key, c, v)));
+                }
+            }
+        }
+        result
+    }
+
+    fn value(&self, key: Key, value: &str) -> String {
+        let value = value.trim();
+        if matches!(self.quoting, Quoting::Keep) || COMMAND_KEYS.contains(&key) {
+            return value.to_owned();
+        }
+        split_arguments(value)
+            .into_iter()
+            .map(|arg| {
+                let inner = arg.trim_matches('"');
+                if arg.len() >= 2 && arg.starts_with('"') && arg.ends_with('"') {
+                    if inner.is_empty() || inner.contains(char::is_whitespace) {
+                        arg
+                    } else {
+                        inner.to_owned()
+                    }
+                } else {
+                    arg
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The lines of `key` in `cfg`, in the order they were read, each with the
+/// comment lines above it. Empty when the key is not set.
+fn key_lines(cfg: &Config, key: Key) -> Vec<(&[String], &str)> {
+    let Some(value) = cfg.columns.get(&key) else {
+        return vec![];
+    };
+    let values: Vec<&str> = if key.is_repeatable() {
+        value.lines().collect()
+    } else {
+        let duplicates = cfg.duplicates.get(&key).into_iter().flatten();
+        std::iter::once(value.as_str()).chain(duplicates.map(|x| x.as_str())).collect()
+    };
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let comment = match i {
+                0 => cfg.key_comments.get(&key),
+                _ => cfg.line_comments.get(&(key, i)),
+            };
+            (comment.map_or(&[][..], |c| c.as_slice()), v)
+        })
+        .collect()
+}
+
+/// Split on whitespace outside of double quotes, keeping the quotes.
+fn split_arguments(value: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+pub fn save_config<T: Write>(
+    writer: &mut BufWriter<T>,
+    cfg: &[Config],
+    format: &Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let indent = " ".repeat(format.indent);
+    for (i, cfg) in cfg.iter().enumerate() {
+        if i > 0 {
+            for _ in 0..format.blank_lines {
+                writeln!(writer)?;
+            }
+        }
+        if !cfg.preamble.is_empty() {
+            for line in &cfg.preamble {
+                writeln!(writer, "{}", line)?;
+            }
+            for _ in 0..format.blank_lines {
+                writeln!(writer)?;
+            }
+        }
+        for line in &cfg.comment {
+            writeln!(writer, "{}", line)?;
+        }
+        writeln!(writer, "{} {}", format.keyword("Host"), cfg.host.trim())?;
 
-        for (k, v) in &cfg.columns {
-            writeln!(writer, "  {} {}", k.str(), v)?;
+        for (k, comment, v) in format.lines(cfg) {
+            for line in comment {
+                write_indented(writer, &indent, line)?;
+            }
+            let line = format!("{} {}", format.keyword(k.str()), format.value(k, v));
+            write_indented(writer, &indent, &line)?;
         }
 
-        writeln!(writer)?;
+        // Indented trailing comments belong to the block and follow its
+        // indentation, the others stay in the first column.
+        for line in &cfg.trailing {
+            match line.strip_prefix(char::is_whitespace) {
+                Some(_) => write_indented(writer, &indent, line.trim_start())?,
+                None => writeln!(writer, "{}", line)?,
+            }
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Lines of `original` missing from `formatted`, compared without what a
+/// [`Format`] changes: indentation, keyword case, quotes and order.
+pub fn lost_lines(original: &str, formatted: &str) -> Vec<String> {
+    let normalize = |line: &str| {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return line.to_owned();
+        }
+        let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+        let (key, value) = line.split_at(end);
+        let value = value.trim_start();
+        let value = value.strip_prefix('=').unwrap_or(value).replace('"', "");
+        let words: Vec<&str> = value.split_whitespace().collect();
+        format!("{} {}", key.to_lowercase(), words.join(" "))
+    };
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for line in formatted.lines() {
+        *counts.entry(normalize(line)).or_default() += 1;
+    }
+    let mut lost = vec![];
+    for line in original.lines().filter(|x| !x.trim().is_empty()) {
+        match counts.get_mut(&normalize(line)) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => lost.push(line.trim().to_owned()),
+        }
+    }
+    lost
+}
+
+fn write_indented<T: Write>(
+    writer: &mut BufWriter<T>,
+    indent: &str,
+    line: &str,
+) -> std::io::Result<()> {
+    if line.is_empty() {
+        writeln!(writer)
+    } else {
+        writeln!(writer, "{}{}", indent, line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufWriter;
+
+    use super::*;
+
+    fn round_trip(text: &str) -> String {
+        let configs = parse(text.as_bytes()).unwrap();
+        let mut writer = BufWriter::new(vec![]);
+        save_config(&mut writer, &configs, &Format::default()).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keeps_repeated_keys() {
+        let text = "\
+Host a
+  IdentityFile ~/.ssh/one
+  # The backup key
+  IdentityFile ~/.ssh/two
+  CertificateFile ~/.ssh/one-cert.pub
+  CertificateFile ~/.ssh/two-cert.pub
+  SendEnv LANG
+  SendEnv LC_*
+  SetEnv A=1
+  SetEnv B=2
+  LocalForward 8080 localhost:80
+  LocalForward 8443 localhost:443
+";
+        let configs = parse(text.as_bytes()).unwrap();
+        assert_eq!(configs[0].columns[&Key::IdentityFile], "~/.ssh/one\n~/.ssh/two");
+        assert_eq!(configs[0].columns[&Key::SetEnv], "A=1\nB=2");
+        assert_eq!(round_trip(text), text);
+    }
+
+    #[test]
+    fn keeps_comments_between_repeated_keys() {
+        let text = "\
+Host a
+  # The main key
+  IdentityFile ~/.ssh/one
+  User me
+  # The backup key
+  IdentityFile ~/.ssh/two
+  IdentityFile ~/.ssh/three
+";
+        assert_eq!(round_trip(text), text);
+
+        let mut configs = parse(text.as_bytes()).unwrap();
+        let added = "~/.ssh/one\n~/.ssh/two\n~/.ssh/three\n~/.ssh/four";
+        configs[0].columns.insert(Key::IdentityFile, added.to_owned());
+        let mut writer = BufWriter::new(vec![]);
+        save_config(&mut writer, &configs, &Format::default()).unwrap();
+        let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(written, format!("{}  IdentityFile ~/.ssh/four\n", text));
+    }
+
+    #[test]
+    fn keeps_the_first_value_of_other_keys() {
+        let text = "\
+Host a
+  User first
+  Port 22
+  User second
+";
+        let configs = parse(text.as_bytes()).unwrap();
+        assert_eq!(configs[0].columns[&Key::User], "first");
+        assert_eq!(round_trip(text), text);
+    }
+
+    #[test]
+    fn keeps_duplicates_with_their_key() {
+        let text = "\
+Host a
+  User first
+  Port 22
+  # Ignored by ssh
+  User second
+";
+        let mut configs = parse(text.as_bytes()).unwrap();
+        let format = Format {
+            order: Order::Canonical,
+            ..Format::default()
+        };
+        let mut writer = BufWriter::new(vec![]);
+        save_config(&mut writer, &configs, &format).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "Host a\n  User first\n  # Ignored by ssh\n  User second\n  Port 22\n"
+        );
+
+        configs[0].remove_key(Key::User);
+        let mut writer = BufWriter::new(vec![]);
+        save_config(&mut writer, &configs, &Format::default()).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner().unwrap()).unwrap(), "Host a\n  Port 22\n");
+    }
+
+    #[test]
+    fn keeps_global_options() {
+        let text = "\
+# Defaults
+ServerAliveInterval 30
+
+User me
+
+Host a
+  HostName a.example
+";
+        let configs = parse(text.as_bytes()).unwrap();
+        assert_eq!(configs.len(), 1);
+        assert!(!configs[0].columns.contains_key(&Key::User));
+        assert_eq!(round_trip(text), text);
+    }
+
+    #[test]
+    fn finds_lost_lines() {
+        let text = "Host a\n  User me\n  # A comment\n  IdentityFile \"~/.ssh/one\"\n";
+        let formatted = "host a\n# A comment\nidentityfile ~/.ssh/one\n";
+        assert_eq!(lost_lines(text, formatted), ["User me"]);
+        assert!(lost_lines(text, &round_trip(text)).is_empty());
+        assert_eq!(lost_lines("User me\n", &round_trip("User me\n")), ["User me"]);
+    }
+}
@@ -0,0 +1,513 @@
+use std::fmt;
+
+use crate::sshconfig::{self, Config, Key};
+
+/// A jump host as written in `ProxyJump` or found in a `ProxyCommand`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Hop {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl fmt::Display for Hop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(user) = &self.user {
+            write!(f, "{}@", user)?;
+        }
+        if self.host.contains(':') {
+            write!(f, "[{}]", self.host)?;
+        } else {
+            write!(f, "{}", self.host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+/// Something wrong with the way a host is reached.
+pub enum Problem {
+    /// The `ProxyJump` or `ProxyCommand` of `host` cannot be understood.
+    Invalid { host: String, reason: String },
+    /// `hop` is neither a host of the config nor a plain address.
+    Undefined { host: String, hop: String },
+    /// Each host of the list jumps through the next one, the last is the
+    /// first again.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Invalid { host, reason } => write!(f, "{}: {}", host, reason),
+            Problem::Undefined { host, hop } => {
+                write!(f, "{}: jump host `{}` is not defined", host, hop)
+            }
+            Problem::Cycle(hosts) => write!(f, "jump cycle: {}", hosts.join(" -> ")),
+        }
+    }
+}
+
+/// Every jump taken to reach a host, in connection order, and whatever
+/// went wrong while following them.
+#[derive(Default)]
+pub struct Route {
+    pub hops: Vec<Hop>,
+    pub problems: Vec<Problem>,
+}
+
+/// Follow the jump hosts of `host`. The first hop is reached with its own
+/// configuration, which may jump again, the following ones through the
+/// hop before them.
+pub fn route(configs: &[Config], host: &str) -> Route {
+    let mut route = Route::default();
+    walk(configs, host, &mut vec![], &mut route);
+    route
+}
+
+fn walk(configs: &[Config], host: &str, stack: &mut Vec<String>, route: &mut Route) {
+    if let Some(i) = stack.iter().position(|h| h.eq_ignore_ascii_case(host)) {
+        let mut cycle = stack[i..].to_vec();
+        cycle.push(host.to_owned());
+        route.problems.push(Problem::Cycle(cycle));
+        return;
+    }
+
+    let hops = match jumps(configs, host) {
+        Ok(hops) => hops,
+        Err(reason) => {
+            route.problems.push(Problem::Invalid {
+                host: host.to_owned(),
+                reason,
+            });
+            return;
+        }
+    };
+
+    for hop in hops.iter().filter(|hop| !is_known(configs, &hop.host)) {
+        route.problems.push(Problem::Undefined {
+            host: host.to_owned(),
+            hop: hop.to_string(),
+        });
+    }
+
+    if let Some(first) = hops.first() {
+        stack.push(host.to_owned());
+        walk(configs, &first.host, stack, route);
+        stack.pop();
+    }
+    route.hops.extend(hops);
+}
+
+/// The jump hosts configured for `host`, taken from whichever of
+/// `ProxyJump` and `ProxyCommand` ssh would use.
+pub fn jumps(configs: &[Config], host: &str) -> Result<Vec<Hop>, String> {
+    let values = sshconfig::effective(configs, host);
+    let find = |key: Key| values.iter().find(|(k, _, _)| *k == key);
+
+    match (find(Key::ProxyJump), find(Key::ProxyCommand)) {
+        (Some((_, jump, i)), Some((_, _, j))) if i <= j => parse_proxy_jump(jump),
+        (Some((_, jump, _)), None) => parse_proxy_jump(jump),
+        (_, Some((_, command, _))) => parse_proxy_command(command).map(|h| h.into_iter().collect()),
+        (None, None) => Ok(vec![]),
+    }
+}
+
+/// Parse a `ProxyJump` value: `none` or a comma separated list of
+/// `[user@]host[:port]` and `ssh://[user@]host[:port]`.
+pub fn parse_proxy_jump(value: &str) -> Result<Vec<Hop>, String> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(vec![]);
+    }
+    value
+        .split(',')
+        .map(|hop| {
+            let hop = hop.trim();
+            parse_hop(hop.strip_prefix("ssh://").unwrap_or(hop))
+        })
+        .collect()
+}
+
+/// Find the jump host of a `ProxyCommand` of the `ssh -W %h:%p host` or
+/// `ssh host nc %h %p` form. Returns `None` for `none` and for commands that
+/// do not go through ssh.
+pub fn parse_proxy_command(value: &str) -> Result<Option<Hop>, String> {
+    let mut words = value.split_whitespace().peekable();
+    if words.peek() == Some(&"exec") {
+        words.next();
+    }
+    match words.next() {
+        Some(program) if program == "ssh" || program.ends_with("/ssh") => {}
+        _ => return Ok(None),
+    }
+
+    let (mut user, mut port, mut destination) = (None, None, None);
+    let mut forwards = false;
+    while let Some(word) = words.next() {
+        let Some(flags) = word.strip_prefix('-') else {
+            if destination.is_none() {
+                destination = Some(word);
+                continue;
+            }
+            // Older configs run netcat on the jump host.
+            forwards |= word == "nc" || word.ends_with("/nc");
+            break;
+        };
+
+        for (i, flag) in flags.char_indices() {
+            if !"BbcDEeFIiJLlmOoPpQRSWw".contains(flag) {
+                continue;
+            }
+            let rest = &flags[i + flag.len_utf8()..];
+            let arg = if rest.is_empty() {
+                words
+                    .next()
+                    .ok_or(format!("missing argument to -{}", flag))?
+            } else {
+                rest
+            };
+            match flag {
+                'W' => forwards = true,
+                'l' => user = Some(arg.to_owned()),
+                'p' => port = Some(parse_port(arg)?),
+                _ => {}
+            }
+            break;
+        }
+    }
+
+    if !forwards {
+        return Ok(None);
+    }
+    let destination = destination.ok_or("ProxyCommand ssh has no destination")?;
+    let mut hop = parse_hop(destination)?;
+    hop.user = hop.user.or(user);
+    hop.port = hop.port.or(port);
+    Ok(Some(hop))
+}
+
+fn parse_hop(text: &str) -> Result<Hop, String> {
+    let (user, rest) = match text.rsplit_once('@') {
+        Some((user, rest)) if !user.is_empty() => (Some(user.to_owned()), rest),
+        Some(_) => return Err(format!("`{}` has an empty user", text)),
+        None => (None, text),
+    };
+
+    let (host, port) = if let Some(bracketed) = rest.strip_prefix('[') {
+        let (host, after) = bracketed
+            .split_once(']')
+            .ok_or(format!("`{}` has an unclosed `[`", text))?;
+        match after.strip_prefix(':') {
+            Some(port) => (host, Some(port)),
+            None if after.is_empty() => (host, None),
+            None => return Err(format!("`{}` is not a valid host", text)),
+        }
+    } else {
+        match rest.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (rest, None),
+        }
+    };
+
+    let valid = |c: char| c.is_ascii_alphanumeric() || "-._%:".contains(c);
+    if host.is_empty() || !host.chars().all(valid) {
+        return Err(format!("`{}` is not a valid host", text));
+    }
+
+    Ok(Hop {
+        user,
+        host: host.to_owned(),
+        port: port.map(parse_port).transpose()?,
+    })
+}
+
+fn parse_port(text: &str) -> Result<u16, String> {
+    match text.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(port),
+        _ => Err(format!("`{}` is not a valid port", text)),
+    }
+}
+
+/// Whether `host` names a host block of the config, or looks like something
+/// ssh can connect to directly: an address, a dotted name or `localhost`.
+fn is_known(configs: &[Config], host: &str) -> bool {
+    configs
+        .iter()
+        .flat_map(|cfg| cfg.host.split_whitespace())
+        .any(|alias| alias.eq_ignore_ascii_case(host))
+        || host.parse::<std::net::IpAddr>().is_ok()
+        || host.contains('.')
+        || host.eq_ignore_ascii_case("localhost")
+}
+
+/// Host aliases of the config, without patterns.
+pub fn aliases(configs: &[Config]) -> Vec<&str> {
+    let mut result: Vec<&str> = vec![];
+    for alias in configs.iter().flat_map(|cfg| cfg.host.split_whitespace()) {
+        if !alias.contains(['*', '?', '!']) && !result.contains(&alias) {
+            result.push(alias);
+        }
+    }
+    result
+}
+
+/// The jump topology of the whole config in Graphviz format. An edge goes
+/// from each hop to the next one, undefined hops are dashed and jumps that
+/// take part in a cycle are red.
+pub fn dot(configs: &[Config]) -> String {
+    let mut edges: Vec<(String, String)> = vec![];
+    let mut undefined: Vec<String> = vec![];
+    let mut cycles: Vec<(String, String)> = vec![];
+
+    for alias in aliases(configs) {
+        let Ok(hops) = jumps(configs, alias) else {
+            continue;
+        };
+        let path: Vec<&str> = hops
+            .iter()
+            .map(|hop| hop.host.as_str())
+            .chain([alias])
+            .collect();
+        for pair in path.windows(2) {
+            let edge = (pair[0].to_owned(), pair[1].to_owned());
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+
+        for problem in route(configs, alias).problems {
+            match problem {
+                Problem::Undefined { hop, .. } => {
+                    let host = parse_hop(&hop).map_or(hop, |h| h.host);
+                    if !undefined.contains(&host) {
+                        undefined.push(host);
+                    }
+                }
+                // Listed as jumped-through hosts, the edges go the other way.
+                Problem::Cycle(hosts) => {
+                    for pair in hosts.windows(2) {
+                        cycles.push((pair[1].to_owned(), pair[0].to_owned()));
+                    }
+                }
+                Problem::Invalid { .. } => {}
+            }
+        }
+    }
+
+    let mut out = String::from("digraph sshe {\n    rankdir=LR;\n");
+    for host in undefined {
+        out += &format!("    {:?} [style=dashed];\n", host);
+    }
+    for (from, to) in edges {
+        let style = if cycles.contains(&(from.clone(), to.clone())) {
+            " [color=red]"
+        } else {
+            ""
+        };
+        out += &format!("    {:?} -> {:?}{};\n", from, to, style);
+    }
+    out += "}\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sshconfigfile;
+
+    fn configs(text: &str) -> Vec<Config> {
+        sshconfigfile::parse(text.as_bytes()).unwrap()
+    }
+
+    fn jump(value: &str) -> Result<Vec<String>, String> {
+        parse_proxy_jump(value).map(|hops| hops.iter().map(|h| h.to_string()).collect())
+    }
+
+    fn command(value: &str) -> Result<Option<String>, String> {
+        parse_proxy_command(value).map(|hop| hop.map(|h| h.to_string()))
+    }
+
+    fn problems(text: &str, host: &str) -> Vec<String> {
+        let configs = configs(text);
+        route(&configs, host)
+            .problems
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_proxy_jump() {
+        assert_eq!(jump("none"), Ok(vec![]));
+        assert_eq!(
+            jump("bastion, me@gw:2222,ssh://root@edge:22"),
+            Ok(vec![
+                "bastion".to_owned(),
+                "me@gw:2222".to_owned(),
+                "root@edge:22".to_owned()
+            ])
+        );
+        let hops = parse_proxy_jump("me@[2001:db8::1]:2222").unwrap();
+        assert_eq!(hops[0].user.as_deref(), Some("me"));
+        assert_eq!(hops[0].host, "2001:db8::1");
+        assert_eq!(hops[0].port, Some(2222));
+        assert_eq!(hops[0].to_string(), "me@[2001:db8::1]:2222");
+        assert_eq!(jump("[::1]"), Ok(vec!["[::1]".to_owned()]));
+    }
+
+    #[test]
+    fn rejects_invalid_proxy_jumps() {
+        assert_eq!(jump("@gw"), Err("`@gw` has an empty user".to_owned()));
+        assert_eq!(jump("[::1"), Err("`[::1` has an unclosed `[`".to_owned()));
+        assert_eq!(
+            jump("[::1]x"),
+            Err("`[::1]x` is not a valid host".to_owned())
+        );
+        assert_eq!(jump("gw:0"), Err("`0` is not a valid port".to_owned()));
+        assert_eq!(jump("gw:ssh"), Err("`ssh` is not a valid port".to_owned()));
+        assert_eq!(jump("a,,b"), Err("`` is not a valid host".to_owned()));
+        assert_eq!(jump("g w"), Err("`g w` is not a valid host".to_owned()));
+    }
+
+    #[test]
+    fn parses_proxy_command() {
+        assert_eq!(
+            command("ssh -W %h:%p bastion"),
+            Ok(Some("bastion".to_owned()))
+        );
+        assert_eq!(
+            command("exec /usr/bin/ssh -q -l me -p 2222 -W %h:%p bastion"),
+            Ok(Some("me@bastion:2222".to_owned()))
+        );
+        assert_eq!(
+            command("ssh -oStrictHostKeyChecking=no -i key root@gw:22 -W %h:%p"),
+            Ok(Some("root@gw:22".to_owned()))
+        );
+        // The user and port given with the destination win.
+        assert_eq!(
+            command("ssh -l other -p 1 me@gw:2 -W %h:%p"),
+            Ok(Some("me@gw:2".to_owned()))
+        );
+        assert_eq!(command("ssh gw nc %h %p"), Ok(Some("gw".to_owned())));
+        assert_eq!(command("ssh gw /bin/nc %h %p"), Ok(Some("gw".to_owned())));
+        assert_eq!(
+            command("ssh -W[%h]:%p [2001:db8::1]"),
+            Ok(Some("[2001:db8::1]".to_owned()))
+        );
+    }
+
+    #[test]
+    fn ignores_other_proxy_commands() {
+        assert_eq!(command("none"), Ok(None));
+        assert_eq!(command("nc -X 5 -x proxy:1080 %h %p"), Ok(None));
+        assert_eq!(command("ssh gw echo hello"), Ok(None));
+        assert_eq!(
+            command("ssh -W %h:%p"),
+            Err("ProxyCommand ssh has no destination".to_owned())
+        );
+        assert_eq!(command("ssh -W"), Err("missing argument to -W".to_owned()));
+        assert_eq!(
+            command("ssh -p x -W %h:%p gw"),
+            Err("`x` is not a valid port".to_owned())
+        );
+    }
+
+    #[test]
+    fn follows_jump_chains() {
+        let text = "\
+Host app
+  ProxyJump gw,edge
+Host gw
+  ProxyJump bastion.example
+Host edge
+  HostName edge.example
+";
+        let configs = configs(text);
+        let route = route(&configs, "app");
+        let hops: Vec<String> = route.hops.iter().map(|h| h.to_string()).collect();
+        assert_eq!(hops, ["bastion.example", "gw", "edge"]);
+        assert!(route.problems.is_empty());
+    }
+
+    #[test]
+    fn uses_whichever_proxy_key_comes_first() {
+        let text = "\
+Host a
+  ProxyCommand ssh -W %h:%p one
+Host *
+  ProxyJump two
+";
+        let configs = configs(text);
+        let hops = jumps(&configs, "a").unwrap();
+        assert_eq!(hops[0].host, "one");
+        let hops = jumps(&configs, "b").unwrap();
+        assert_eq!(hops[0].host, "two");
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let text = "Host a\n  ProxyJump b\nHost b\n  ProxyJump c\nHost c\n  ProxyJump a\n";
+        assert_eq!(problems(text, "a"), ["jump cycle: a -> b -> c -> a"]);
+        assert_eq!(problems(text, "b"), ["jump cycle: b -> c -> a -> b"]);
+        assert_eq!(
+            problems("Host a\n  ProxyJump A\n", "a"),
+            ["jump cycle: a -> A"]
+        );
+        // Reaching the same host twice without a loop is fine.
+        let text = "Host a\n  ProxyJump b,b\nHost b\n  HostName b.example\n";
+        assert!(problems(text, "a").is_empty());
+    }
+
+    #[test]
+    fn reports_undefined_and_invalid_hops() {
+        let text = "\
+Host a
+  ProxyJump b,nowhere,10.0.0.1,gw.example,localhost
+Host b
+  ProxyJump b1:0
+";
+        assert_eq!(
+            problems(text, "a"),
+            [
+                "a: jump host `nowhere` is not defined",
+                "b: `0` is not a valid port",
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_aliases_without_patterns() {
+        let configs = configs("Host a b\nHost web* !c d\nHost a\nHost *\n");
+        assert_eq!(aliases(&configs), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn writes_dot() {
+        let text = "\
+Host app
+  ProxyJump gw
+Host gw
+  ProxyJump ghost
+Host x
+  ProxyJump y
+Host y
+  ProxyJump x
+";
+        assert_eq!(
+            dot(&configs(text)),
+            "\
+digraph sshe {
+    rankdir=LR;
+    \"ghost\" [style=dashed];
+    \"gw\" -> \"app\";
+    \"ghost\" -> \"gw\";
+    \"y\" -> \"x\" [color=red];
+    \"x\" -> \"y\" [color=red];
+}
+"
+        );
+    }
+}
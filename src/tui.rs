@@ -4,6 +4,7 @@ use color_eyre::config::HookBuilder;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     crossterm::{
+        event::{DisableMouseCapture, EnableMouseCapture},
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         ExecutableCommand,
     },
@@ -25,13 +26,23 @@ pub fn init_error_hooks() -> color_eyre::Result<()> {
     Ok(())
 }
 
-pub fn init_terminal() -> io::Result<Terminal<impl Backend>> {
-    stdout().execute(EnterAlternateScreen)?;
-    enable_raw_mode()?;
+pub fn init_terminal(mouse: bool) -> io::Result<Terminal<impl Backend>> {
+    resume_terminal(mouse)?;
     Terminal::new(CrosstermBackend::new(stdout()))
 }
 
+/// Take the terminal back after [`restore_terminal`], e.g. once a child
+/// process that needed it has exited.
+pub fn resume_terminal(mouse: bool) -> io::Result<()> {
+    stdout().execute(EnterAlternateScreen)?;
+    if mouse {
+        stdout().execute(EnableMouseCapture)?;
+    }
+    enable_raw_mode()
+}
+
 pub fn restore_terminal() -> io::Result<()> {
+    stdout().execute(DisableMouseCapture)?;
     stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()
 }
@@ -0,0 +1,275 @@
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    forward,
+    sshconfig::{Config, Key},
+    topology,
+};
+
+/// How long a new tunnel is watched for an early exit, such as a refused
+/// login or a port already in use.
+const STARTUP: Duration = Duration::from_secs(1);
+
+/// An `ssh -N` started for a host.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Tunnel {
+    pub alias: String,
+    pub pid: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct State {
+    #[serde(default, rename = "tunnel")]
+    tunnels: Vec<Tunnel>,
+}
+
+/// Where the state file and the logs of the tunnels are kept:
+/// `$XDG_RUNTIME_DIR/sshe`, or the temporary directory without it.
+pub fn state_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|d| !d.is_empty())
+        .map_or_else(std::env::temp_dir, PathBuf::from);
+    base.join("sshe")
+}
+
+/// The log of the tunnel of `alias`, with what ssh printed.
+pub fn log_file(dir: &Path, alias: &str) -> PathBuf {
+    dir.join(format!("tunnel-{}.log", alias))
+}
+
+/// The tunnels of the state file in `dir` that still run.
+pub fn load(dir: &Path) -> Vec<Tunnel> {
+    let state: State = fs::read_to_string(dir.join("tunnels.toml"))
+        .ok()
+        .and_then(|text| toml::from_str(&text).ok())
+        .unwrap_or_default();
+    state.tunnels.into_iter().filter(is_running).collect()
+}
+
+fn save(dir: &Path, tunnels: Vec<Tunnel>) -> Result<(), String> {
+    let text = toml::to_string(&State { tunnels }).map_err(|e| e.to_string())?;
+    fs::write(dir.join("tunnels.toml"), text).map_err(|e| e.to_string())
+}
+
+/// Whether the process of `tunnel` runs and is still the ssh started for
+/// its host, and not another one given the same pid.
+pub fn is_running(tunnel: &Tunnel) -> bool {
+    let Ok(cmdline) = fs::read(format!("/proc/{}/cmdline", tunnel.pid)) else {
+        return false;
+    };
+    let args: Vec<&[u8]> = cmdline.split(|b| *b == 0).collect();
+    args.contains(&b"-N".as_slice()) && args.contains(&tunnel.alias.as_bytes())
+}
+
+/// Start `ssh -N` for `alias` with `ssh`, reading the config from
+/// `config_file`, and record it in the state file of `dir`. The child is
+/// returned for the caller to reap, the tunnel outlives sshe otherwise.
+pub fn start(ssh: &str, dir: &Path, config_file: &Path, alias: &str) -> Result<Child, String> {
+    let mut tunnels = load(dir);
+    if tunnels.iter().any(|t| t.alias == alias) {
+        return Err(format!("a tunnel for {} is already running", alias));
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+    let log = log_file(dir, alias);
+    let stderr = fs::File::create(&log).map_err(|e| e.to_string())?;
+
+    let mut child = Command::new(ssh)
+        .arg("-F")
+        .arg(config_file)
+        .args(["-N", "-o", "ExitOnForwardFailure=yes", alias])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(stderr)
+        // Out of the terminal's process group, so that Ctrl-C in it
+        // leaves the tunnel up.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Unable to run {}: {}", ssh, e))?;
+
+    let start = Instant::now();
+    while start.elapsed() < STARTUP {
+        if let Ok(Some(status)) = child.try_wait() {
+            let output = fs::read_to_string(&log).unwrap_or_default();
+            return Err(match output.lines().rev().find(|l| !l.trim().is_empty()) {
+                Some(line) => line.trim().to_owned(),
+                None => format!("ssh ended with {}", status),
+            });
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    tunnels.push(Tunnel {
+        alias: alias.to_owned(),
+        pid: child.id(),
+    });
+    save(dir, tunnels)?;
+    Ok(child)
+}
+
+/// Stop the tunnel of `alias` and forget it.
+pub fn stop(dir: &Path, alias: &str) -> Result<(), String> {
+    let mut tunnels = load(dir);
+    let Some(i) = tunnels.iter().position(|t| t.alias == alias) else {
+        return Err(format!("no tunnel is running for {}", alias));
+    };
+    let status = Command::new("kill")
+        .arg(tunnels[i].pid.to_string())
+        .status()
+        .map_err(|e| format!("Unable to run kill: {}", e))?;
+    if !status.success() {
+        return Err(format!("Unable to stop the tunnel of {}", alias));
+    }
+    tunnels.remove(i);
+    save(dir, tunnels)
+}
+
+/// The local ports the `LocalForward` and `DynamicForward` lines of the
+/// blocks matching `alias` listen on.
+pub fn ports(configs: &[Config], alias: &str) -> Vec<u16> {
+    let mut ports = vec![];
+    for cfg in configs.iter().filter(|c| c.matches(alias)) {
+        for key in [Key::LocalForward, Key::DynamicForward] {
+            for forward in forward::forwards(cfg, key) {
+                match forward.local_port(key) {
+                    Some((_, port)) if !ports.contains(&port) => ports.push(port),
+                    _ => {}
+                }
+            }
+        }
+    }
+    ports
+}
+
+/// The host aliases with local forwards, the ones a tunnel is useful for.
+pub fn hosts(configs: &[Config]) -> Vec<String> {
+    topology::aliases(configs)
+        .into_iter()
+        .filter(|alias| !ports(configs, alias).is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// `5432 listening, 8080 not listening`.
+pub fn port_summary(ports: &[u16], listening: &HashSet<u16>) -> String {
+    ports
+        .iter()
+        .map(|p| {
+            if listening.contains(p) {
+                format!("{} listening", p)
+            } else {
+                format!("{} not listening", p)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The TCP ports something listens on, from `/proc/net/tcp` and `tcp6`.
+pub fn listening() -> HashSet<u16> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|f| fs::read_to_string(f).ok())
+        .flat_map(|text| parse_listening(&text))
+        .collect()
+}
+
+/// The local ports of the `LISTEN` sockets of a `/proc/net/tcp` table.
+fn parse_listening(text: &str) -> Vec<u16> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let local = fields.nth(1)?;
+            // The state, after the remote address.
+            if fields.nth(1)? != "0A" {
+                return None;
+            }
+            let (_, port) = local.rsplit_once(':')?;
+            u16::from_str_radix(port, 16).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    /// A fresh directory holding the state and a fake ssh running `script`.
+    fn setup(name: &str, script: &str) -> (PathBuf, String) {
+        let dir =
+            std::env::temp_dir().join(format!("sshe-tunnels-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let ssh = dir.join("ssh");
+        fs::write(&ssh, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+        (dir, ssh.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn starts_and_stops_a_tunnel() {
+        let (dir, ssh) = setup(
+            "start",
+            "echo \"$@\" > \"$(dirname \"$0\")/args\"\nsleep 30",
+        );
+        let mut child = start(&ssh, &dir, Path::new("cfg"), "db").unwrap();
+
+        let tunnels = load(&dir);
+        assert_eq!(
+            tunnels,
+            vec![Tunnel {
+                alias: "db".to_owned(),
+                pid: child.id()
+            }]
+        );
+        let args = fs::read_to_string(dir.join("args")).unwrap();
+        assert_eq!(args.trim(), "-F cfg -N -o ExitOnForwardFailure=yes db");
+        assert!(start(&ssh, &dir, Path::new("cfg"), "db").is_err());
+
+        stop(&dir, "db").unwrap();
+        child.wait().unwrap();
+        assert!(load(&dir).is_empty());
+        assert!(stop(&dir, "db").is_err());
+    }
+
+    #[test]
+    fn reports_early_exit() {
+        let (dir, ssh) = setup(
+            "fail",
+            "echo 'Permission denied (publickey).' >&2\nexit 255",
+        );
+        let error = start(&ssh, &dir, Path::new("cfg"), "db").unwrap_err();
+        assert_eq!(error, "Permission denied (publickey).");
+        assert!(load(&dir).is_empty());
+    }
+
+    #[test]
+    fn forgets_exited_tunnels() {
+        let (dir, ssh) = setup("exited", "sleep 30");
+        let mut child = start(&ssh, &dir, Path::new("cfg"), "db").unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(load(&dir).is_empty());
+    }
+
+    #[test]
+    fn parses_listening_ports() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1538 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:A1B2 0100007F:1538 01 00000000:00000000 00:00000000 00000000  1000        0 2 1 0000000000000000 20 4 30 10 -1
+";
+        assert_eq!(parse_listening(table), vec![5432]);
+    }
+}
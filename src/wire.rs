@@ -0,0 +1,158 @@
+//! The SSH wire encoding of RFC 4251, used by keys, certificates and the
+//! agent protocol.
+
+/// Reads values off the front of a buffer.
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The number of bytes left.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("truncated data".to_owned());
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// A length-prefixed byte string.
+    pub fn string(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    pub fn text(&mut self) -> Result<String, String> {
+        String::from_utf8(self.string()?.to_vec()).map_err(|_| "invalid UTF-8".to_owned())
+    }
+
+    /// The significant bits of a positive `mpint`.
+    pub fn mpint_bits(&mut self) -> Result<u32, String> {
+        let value = self.string()?;
+        let value = &value[value.iter().take_while(|b| **b == 0).count()..];
+        Ok(match value.first() {
+            Some(first) => (value.len() as u32 - 1) * 8 + (8 - first.leading_zeros()),
+            None => 0,
+        })
+    }
+}
+
+/// Builds a buffer of values.
+#[derive(Default)]
+pub struct Writer {
+    pub data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_be_bytes());
+    }
+
+    /// A length-prefixed byte string.
+    pub fn string(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.data.extend(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_values() {
+        let mut writer = Writer::default();
+        writer.u8(7);
+        writer.u32(0xdead_beef);
+        writer.string(b"ssh-ed25519");
+        writer.string(b"");
+        writer.string("héllo".as_bytes());
+
+        let mut reader = Reader::new(&writer.data);
+        assert_eq!(reader.len(), 1 + 4 + (4 + 11) + 4 + (4 + 6));
+        assert_eq!(reader.u8(), Ok(7));
+        assert_eq!(reader.u32(), Ok(0xdead_beef));
+        assert_eq!(reader.string(), Ok(b"ssh-ed25519".as_slice()));
+        assert_eq!(reader.text(), Ok(String::new()));
+        assert_eq!(reader.text(), Ok("héllo".to_owned()));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn encodes_big_endian() {
+        let mut writer = Writer::default();
+        writer.u32(1);
+        writer.string(b"ab");
+        assert_eq!(writer.data, [0, 0, 0, 1, 0, 0, 0, 2, b'a', b'b']);
+
+        let data = [
+            0, 0, 0, 1, 0, 0, 0, 2, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+        ];
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.u64(), Ok(0x1_0000_0002));
+        assert_eq!(reader.u64(), Ok(u64::MAX - 1));
+    }
+
+    #[test]
+    fn counts_mpint_bits() {
+        let bits = |value: &[u8]| {
+            let mut writer = Writer::default();
+            writer.string(value);
+            Reader::new(&writer.data).mpint_bits()
+        };
+        assert_eq!(bits(&[]), Ok(0));
+        assert_eq!(bits(&[0, 0]), Ok(0));
+        assert_eq!(bits(&[1]), Ok(1));
+        // The leading zero keeps a high bit from making the value negative.
+        assert_eq!(bits(&[0, 0x80, 0]), Ok(16));
+        assert_eq!(bits(&[0x7f, 0xff, 0xff]), Ok(23));
+    }
+
+    #[test]
+    fn rejects_truncated_and_invalid_data() {
+        assert_eq!(
+            Reader::new(&[0, 0, 1]).u32(),
+            Err("truncated data".to_owned())
+        );
+        assert_eq!(Reader::new(&[]).u8(), Err("truncated data".to_owned()));
+        assert_eq!(
+            Reader::new(&[0, 0, 0, 5, b'a']).string(),
+            Err("truncated data".to_owned())
+        );
+        assert_eq!(
+            Reader::new(&[0, 0, 0, 1, 0xff]).text(),
+            Err("invalid UTF-8".to_owned())
+        );
+    }
+}